@@ -1,18 +1,17 @@
 use std::fs::create_dir_all;
-use std::process::Command;
 
 use clap::ArgMatches;
-use rusoto_core::ChainProvider;
 
-use aws::credentials_provider;
+use aws::{CredentialsProvider, credentials_provider};
 use encryption::Encryptor;
 use error::KawsResult;
 use pki::{CertificateAuthority, CertificateSigningRequest};
 use process::execute_child_process;
+use terraform::Outputs;
 
 pub struct Admin<'a> {
     admin: &'a str,
-    aws_credentials_provider: ChainProvider,
+    aws_credentials_provider: CredentialsProvider,
     cluster: &'a str,
     groups: Option<Vec<&'a str>>,
 }
@@ -22,6 +21,8 @@ impl<'a> Admin<'a> {
         Admin {
             admin: matches.value_of("name").expect("clap should have required name"),
             aws_credentials_provider: credentials_provider(
+                matches.value_of("cluster"),
+                matches.value_of("region"),
                 matches.value_of("aws-credentials-path"),
                 matches.value_of("aws-credentials-profile"),
             ),
@@ -52,16 +53,12 @@ impl<'a> Admin<'a> {
         csr.write_to_file(&csr_path)?;
         key.write_to_file_unencrypted(&key_path)?;
 
-        Ok(Some(format!(
-            "Certificate signing request created! Commit changes to Git and ask an\n\
-            administrator to generate your client certificate."
-        )))
+        Ok(Some("Certificate signing request created! Commit changes to Git and ask an\n\
+            administrator to generate your client certificate.".to_owned()))
     }
 
     pub fn install(&mut self) -> KawsResult {
-        let domain = self.domain()?.expect(
-            "Terraform should have had a value for the domain output"
-        );
+        let domain = Outputs::load(self.cluster)?.domain;
 
         log_wrap!("Configuring kubectl", {
             // set cluster
@@ -106,9 +103,7 @@ impl<'a> Admin<'a> {
     }
 
     pub fn sign(&mut self) -> KawsResult {
-        let region = self.region()?.expect(
-            "Terraform should have had a value for the region output"
-        );
+        let region = Outputs::load(self.cluster)?.region;
 
         let admin_csr_path = format!("clusters/{}/{}-csr.pem", self.cluster, self.admin);
         let admin_cert_path = format!("clusters/{}/{}.pem", self.cluster, self.admin);
@@ -139,20 +134,4 @@ impl<'a> Admin<'a> {
             self.cluster,
         )))
     }
-
-    fn domain(&self) -> KawsResult {
-        self.output("domain")
-    }
-
-    fn region(&self) -> KawsResult {
-        self.output("region")
-    }
-
-    fn output(&self, output_name: &str) -> KawsResult {
-        let output = Command::new("kaws")
-            .args(&["cluster", "output", self.cluster, output_name])
-            .output()?;
-
-        Ok(Some(String::from_utf8_lossy(&output.stdout).trim_right().to_string()))
-    }
 }
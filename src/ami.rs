@@ -0,0 +1,306 @@
+use std::fs::{File, create_dir_all, metadata};
+use std::io::{Read, Write};
+use std::time::{Duration, SystemTime};
+
+use clap::ArgMatches;
+use hyper::status::StatusCode;
+use rusoto_core::default_tls_client;
+use serde_json::{Value, from_str};
+
+use config::{ClusterConfig, config_path, toml_string};
+use error::{KawsError, KawsResult};
+use repository::ignore_path;
+use settings::edit;
+
+/// The Container Linux release channels, from least to most stable.
+pub const OS_CHANNELS: &'static [&'static str] = &["alpha", "beta", "stable"];
+
+/// Where downloaded release manifests are kept, relative to the root of the repository.
+const CACHE_DIR: &'static str = ".os-manifests";
+
+/// How long a cached manifest of a channel's latest release is used before it is downloaded
+/// again. Manifests of specific releases never change, so they are always used once cached.
+const CACHE_MAX_AGE_SECS: u64 = 24 * 60 * 60;
+
+/// Finds the Container Linux AMI for a region from the release manifest of a channel, either for
+/// the channel's latest release or for a specific one.
+///
+/// Manifests are read from a local file when one is given, and otherwise downloaded from the
+/// Container Linux release servers and cached in `.os-manifests`.
+pub struct AmiResolver<'a> {
+    channel: &'a str,
+    manifest: Option<&'a str>,
+    version: Option<&'a str>,
+}
+
+/// Moves an existing cluster to the AMI of the latest Container Linux release in its channel, or
+/// to a specific release.
+pub struct AmiUpdate<'a> {
+    channel: Option<&'a str>,
+    cluster: &'a str,
+    manifest: Option<&'a str>,
+    version: Option<&'a str>,
+}
+
+/// A Container Linux release and its AMI in one region.
+pub struct OsRelease {
+    pub ami: String,
+    pub version: String,
+}
+
+impl<'a> AmiResolver<'a> {
+    pub fn new(channel: &'a str, version: Option<&'a str>, manifest: Option<&'a str>) -> Self {
+        AmiResolver {
+            channel: channel,
+            manifest: manifest,
+            version: version,
+        }
+    }
+
+    pub fn resolve(&self, region: &str) -> Result<OsRelease, KawsError> {
+        if region.is_empty() {
+            return Err(KawsError::new(
+                "A region is required to find the Container Linux AMI for a channel.".to_owned(),
+            ));
+        }
+
+        let contents = match self.manifest {
+            Some(path) => {
+                let mut contents = String::new();
+
+                File::open(path)?.read_to_string(&mut contents)?;
+
+                contents
+            }
+            None => self.cached_manifest()?,
+        };
+
+        let source = self.manifest.map_or_else(|| self.url(), |path| path.to_owned());
+
+        self.parse(&contents, region).map_err(|error| {
+            KawsError::new(format!("Container Linux release manifest {}: {}", source, error))
+        })
+    }
+
+    // Private
+
+    fn cache_path(&self) -> String {
+        format!("{}/{}-{}.json", CACHE_DIR, self.channel, self.version.unwrap_or("current"))
+    }
+
+    /// The manifest from the cache if it's there and fresh enough, or else downloaded. If the
+    /// download fails, an outdated cached manifest is used rather than failing.
+    fn cached_manifest(&self) -> Result<String, KawsError> {
+        let path = self.cache_path();
+
+        let age = metadata(&path).and_then(|metadata| metadata.modified()).ok().map(|modified| {
+            SystemTime::now().duration_since(modified).unwrap_or(Duration::from_secs(0))
+        });
+
+        let fresh = match age {
+            Some(_) if self.version.is_some() => true,
+            Some(age) => age < Duration::from_secs(CACHE_MAX_AGE_SECS),
+            None => false,
+        };
+
+        let mut contents = String::new();
+
+        if fresh {
+            debug!("Using cached Container Linux release manifest {}", path);
+
+            File::open(&path)?.read_to_string(&mut contents)?;
+
+            return Ok(contents);
+        }
+
+        match self.download() {
+            Ok(downloaded) => {
+                log_wrap!("Caching Container Linux release manifest", {
+                    // Repositories created by older versions of kaws don't ignore the cache.
+                    ignore_path(".gitignore", CACHE_DIR)?;
+                    create_dir_all(CACHE_DIR)?;
+
+                    let mut file = File::create(&path)?;
+
+                    file.write_all(downloaded.as_bytes())?;
+                });
+
+                Ok(downloaded)
+            }
+            Err(error) => {
+                if age.is_none() {
+                    return Err(error);
+                }
+
+                println!("{} Using the cached copy from {} instead.", error, path);
+
+                File::open(&path)?.read_to_string(&mut contents)?;
+
+                Ok(contents)
+            }
+        }
+    }
+
+    fn download(&self) -> Result<String, KawsError> {
+        let url = self.url();
+        let client = default_tls_client().expect("failed to create HTTP client with TLS");
+        let failed = |error: String| {
+            KawsError::new(format!("Failed to download Container Linux release manifest {}: {}", url, error))
+        };
+
+        debug!("Downloading {}", url);
+
+        let mut response = client.get(&url).send().map_err(|error| failed(error.to_string()))?;
+
+        if response.status != StatusCode::Ok {
+            return Err(failed(response.status.to_string()));
+        }
+
+        let mut contents = String::new();
+
+        response.read_to_string(&mut contents)?;
+
+        Ok(contents)
+    }
+
+    /// Reads a manifest in either of the formats published for Container Linux: the one for a
+    /// channel's latest release, which maps each region to its AMIs and names the release, or the
+    /// one for a specific release, which lists the AMIs of every region.
+    fn parse(&self, contents: &str, region: &str) -> Result<OsRelease, String> {
+        let manifest: Value = from_str(contents).map_err(|error| error.to_string())?;
+
+        let (ami, version) = match manifest.get("amis").and_then(|amis| amis.as_array()) {
+            Some(amis) => (
+                amis.iter().find(|ami| {
+                    ami.get("name").and_then(|name| name.as_str()) == Some(region)
+                }).and_then(|ami| ami.get("hvm")).and_then(|hvm| hvm.as_str()),
+                None,
+            ),
+            None => (
+                manifest.get(region).and_then(|ami| ami.get("hvm")).and_then(|hvm| hvm.as_str()),
+                manifest.get("release_info")
+                    .and_then(|release_info| release_info.get("version"))
+                    .and_then(|version| version.as_str()),
+            ),
+        };
+
+        let ami = ami.ok_or_else(|| format!("no HVM AMI is listed for region {}", region))?;
+
+        let version = match (version, self.version) {
+            (Some(version), Some(wanted)) if version != wanted => return Err(format!(
+                "it is for release {}, not {}",
+                version,
+                wanted,
+            )),
+            (Some(version), _) | (None, Some(version)) => version,
+            (None, None) => return Err(
+                "it does not name its release, so give the release version as well".to_owned()
+            ),
+        };
+
+        Ok(OsRelease {
+            ami: ami.to_owned(),
+            version: version.to_owned(),
+        })
+    }
+
+    fn url(&self) -> String {
+        match self.version {
+            Some(version) => format!(
+                "https://{}.release.core-os.net/amd64-usr/{}/coreos_production_ami_all.json",
+                self.channel,
+                version,
+            ),
+            None => format!("https://coreos.com/dist/aws/aws-{}.json", self.channel),
+        }
+    }
+}
+
+impl<'a> AmiUpdate<'a> {
+    pub fn new(matches: &'a ArgMatches) -> Self {
+        AmiUpdate {
+            channel: matches.value_of("os-channel"),
+            cluster: matches.value_of("cluster").expect("clap should have required cluster"),
+            manifest: matches.value_of("os-manifest"),
+            version: matches.value_of("os-version"),
+        }
+    }
+
+    pub fn update(&self) -> KawsResult {
+        let config = ClusterConfig::load(self.cluster)?;
+
+        let channel = match self.channel {
+            Some(channel) => channel,
+            None if !config.os_channel.is_empty() => &config.os_channel,
+            None => return Err(KawsError::new(format!(
+                "Cluster \"{}\" does not set os_channel. Give the channel to follow with \
+                --os-channel.",
+                self.cluster,
+            ))),
+        };
+
+        let release = AmiResolver::new(channel, self.version, self.manifest).resolve(&config.region)?;
+
+        if release.ami == config.coreos_ami && channel == config.os_channel {
+            return Ok(Some(format!(
+                "Cluster \"{}\" already uses {}, the AMI of Container Linux {} {}.",
+                self.cluster,
+                release.ami,
+                channel,
+                release.version,
+            )));
+        }
+
+        let path = config_path(self.cluster);
+        let mut contents = String::new();
+
+        File::open(&path)?.read_to_string(&mut contents)?;
+
+        for &(key, value) in [
+            ("coreos_ami", &release.ami),
+            ("os_channel", &channel.to_owned()),
+            ("os_version", &release.version),
+        ].iter() {
+            contents = edit(&contents, key, &toml_string(value))?.0;
+        }
+
+        let updated = ClusterConfig::parse(&contents, &path)?;
+
+        updated.validate(self.cluster)?;
+
+        log_wrap!("Writing kaws.toml", {
+            let mut file = File::create(&path)?;
+
+            file.write_all(contents.as_bytes())?;
+        });
+
+        updated.write_tfvars(self.cluster)?;
+
+        Ok(Some(format!(
+            "Cluster \"{}\" now uses {}, the AMI of Container Linux {} {}. Run `kaws cluster plan` \
+            and `kaws cluster apply`, and then replace the existing instances with `kaws cluster \
+            roll`. Commit clusters/{} to Git.",
+            self.cluster,
+            release.ami,
+            channel,
+            release.version,
+            self.cluster,
+        )))
+    }
+}
+
+/// Checks that a Container Linux release version is in the form "1632.3.0".
+pub fn validate_os_version(version: &str) -> Result<(), String> {
+    let parts: Vec<&str> = version.split('.').collect();
+
+    if parts.len() == 3 && parts.iter().all(|part| {
+        !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())
+    }) {
+        Ok(())
+    } else {
+        Err(format!(
+            "\"{}\" is not a valid Container Linux version. Give the full version, e.g. \"1632.3.0\".",
+            version,
+        ))
+    }
+}
@@ -0,0 +1,212 @@
+use std::process::Command;
+
+use rusoto_core::ProvideAwsCredentials;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, from_slice};
+
+use aws::{CredentialsProvider, set_credentials_env};
+use error::KawsError;
+
+/// Reads and changes a cluster's autoscaling groups, and reads the health of their instances in
+/// the groups' ELBs, through the AWS CLI.
+pub struct AutoScaling<'a> {
+    credentials_provider: &'a CredentialsProvider,
+    region: &'a str,
+}
+
+/// An autoscaling group, as described by `aws autoscaling describe-auto-scaling-groups`.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Group {
+    pub desired_capacity: u32,
+    pub instances: Vec<GroupInstance>,
+    pub launch_configuration_name: Option<String>,
+    pub load_balancer_names: Vec<String>,
+    pub max_size: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GroupInstance {
+    pub health_status: String,
+    pub instance_id: String,
+    /// Missing when the instance's launch configuration has been deleted.
+    pub launch_configuration_name: Option<String>,
+    pub lifecycle_state: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeAutoScalingGroups {
+    auto_scaling_groups: Vec<Group>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeInstanceHealth {
+    instance_states: Vec<InstanceState>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeInstances {
+    reservations: Vec<Reservation>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InstanceState {
+    instance_id: String,
+    state: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Reservation {
+    instances: Vec<Instance>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Instance {
+    instance_id: String,
+    private_ip_address: Option<String>,
+}
+
+impl<'a> AutoScaling<'a> {
+    pub fn new(credentials_provider: &'a CredentialsProvider, region: &'a str) -> Self {
+        AutoScaling {
+            credentials_provider: credentials_provider,
+            region: region,
+        }
+    }
+
+    pub fn group(&self, name: &str) -> Result<Group, KawsError> {
+        let output: DescribeAutoScalingGroups = self.run(&[
+            "autoscaling",
+            "describe-auto-scaling-groups",
+            "--auto-scaling-group-names",
+            name,
+        ])?;
+
+        output.auto_scaling_groups.into_iter().next().ok_or_else(|| {
+            KawsError::new(format!("Autoscaling group {} does not exist.", name))
+        })
+    }
+
+    /// The IDs of the instances that pass an ELB's health check.
+    pub fn in_service_instances(&self, load_balancer_name: &str) -> Result<Vec<String>, KawsError> {
+        let output: DescribeInstanceHealth = self.run(&[
+            "elb",
+            "describe-instance-health",
+            "--load-balancer-name",
+            load_balancer_name,
+        ])?;
+
+        Ok(output.instance_states.into_iter().filter(|instance| {
+            instance.state == "InService"
+        }).map(|instance| instance.instance_id).collect())
+    }
+
+    /// The private IP address of an instance, which is also the name of its Kubernetes node.
+    pub fn private_ip(&self, instance_id: &str) -> Result<String, KawsError> {
+        let output: DescribeInstances = self.run(&[
+            "ec2",
+            "describe-instances",
+            "--instance-ids",
+            instance_id,
+        ])?;
+
+        output.reservations.into_iter().flat_map(|reservation| {
+            reservation.instances.into_iter()
+        }).find(|instance| instance.instance_id == instance_id).and_then(|instance| {
+            instance.private_ip_address
+        }).ok_or_else(|| {
+            KawsError::new(format!("Instance {} has no private IP address.", instance_id))
+        })
+    }
+
+    /// Sets a group's desired capacity and maximum size.
+    pub fn resize(&self, name: &str, desired_capacity: u32, max_size: u32) -> Result<(), KawsError> {
+        self.output(&[
+            "autoscaling",
+            "update-auto-scaling-group",
+            "--auto-scaling-group-name",
+            name,
+            "--desired-capacity",
+            &desired_capacity.to_string(),
+            "--max-size",
+            &max_size.to_string(),
+        ])?;
+
+        Ok(())
+    }
+
+    /// Terminates an instance. Unless `decrement` is set, the group's desired capacity stays the
+    /// same, so the group launches a replacement from its current launch configuration.
+    pub fn terminate(&self, instance_id: &str, decrement: bool) -> Result<(), KawsError> {
+        let _: Value = self.run(&[
+            "autoscaling",
+            "terminate-instance-in-auto-scaling-group",
+            "--instance-id",
+            instance_id,
+            if decrement {
+                "--should-decrement-desired-capacity"
+            } else {
+                "--no-should-decrement-desired-capacity"
+            },
+        ])?;
+
+        Ok(())
+    }
+
+    // Private
+
+    /// Runs the AWS CLI and returns its standard output, which is empty for some commands.
+    fn output(&self, args: &[&str]) -> Result<Vec<u8>, KawsError> {
+        let mut command = Command::new("aws");
+
+        command.args(["--output", "json", "--region", self.region]).args(args);
+
+        set_credentials_env(&mut command, &self.credentials_provider.credentials()?);
+
+        debug!("Running `aws {}`", args.join(" "));
+
+        let output = command.output()?;
+
+        if !output.status.success() {
+            return Err(KawsError::with_std_streams(
+                format!("`aws {} {}` failed.", args[0], args[1]),
+                String::from_utf8_lossy(&output.stdout).to_string(),
+                String::from_utf8_lossy(&output.stderr).to_string(),
+            ));
+        }
+
+        Ok(output.stdout)
+    }
+
+    fn run<T>(&self, args: &[&str]) -> Result<T, KawsError> where T: DeserializeOwned {
+        Ok(from_slice(&self.output(args)?)?)
+    }
+}
+
+impl Group {
+    /// Whether the group has exactly its desired number of instances, all in service and healthy.
+    pub fn is_settled(&self) -> bool {
+        self.instances.len() == self.desired_capacity as usize &&
+            self.instances.iter().all(|instance| {
+                instance.lifecycle_state == "InService" && instance.health_status == "Healthy"
+            })
+    }
+
+    /// Whether an instance was launched from an older launch configuration than the group's
+    /// current one.
+    pub fn is_stale(&self, instance: &GroupInstance) -> bool {
+        instance.launch_configuration_name.is_none() ||
+            instance.launch_configuration_name != self.launch_configuration_name
+    }
+
+    pub fn stale_instances(&self) -> Vec<&GroupInstance> {
+        self.instances.iter().filter(|instance| self.is_stale(instance)).collect()
+    }
+}
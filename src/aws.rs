@@ -1,6 +1,192 @@
-use rusoto_core::{ChainProvider, ProfileProvider};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::ErrorKind;
+use std::process::Command;
+use std::rc::Rc;
 
-pub fn credentials_provider(path: Option<&str>, profile: Option<&str>) -> ChainProvider {
+use rusoto_core::{
+    AwsCredentials,
+    ChainProvider,
+    CredentialsError,
+    ProfileProvider,
+    ProvideAwsCredentials,
+    Region,
+    default_tls_client,
+};
+use rusoto_sts::{
+    StsAssumeRoleSessionCredentialsProvider,
+    StsClient,
+    StsSessionCredentialsProvider,
+};
+use serde_json::from_reader;
+
+use config::ClusterConfig;
+use prompt::read_line;
+
+/// Provides the AWS credentials for a cluster.
+///
+/// Base credentials come from the usual chain of environment variables, credentials file, and
+/// instance profile. If the cluster's `aws.json` names an IAM role or an MFA device, those
+/// credentials are exchanged for temporary ones with STS. Credentials are resolved at most once
+/// per command, so the user is only asked for an MFA code once, and every Terraform and KMS call
+/// made by the command uses the same session.
+#[derive(Clone)]
+pub struct CredentialsProvider {
+    chain: ChainProvider,
+    cluster: Option<String>,
+    region: Option<String>,
+    resolved: Rc<RefCell<Option<AwsCredentials>>>,
+}
+
+/// Provides credentials that have already been resolved, so they can be shared across threads.
+pub struct ResolvedCredentials(pub AwsCredentials);
+
+/// The AWS settings for a cluster, read from `clusters/CLUSTER/aws.json`.
+#[derive(Default, Deserialize)]
+struct ClusterAwsSettings {
+    external_id: Option<String>,
+    mfa_serial: Option<String>,
+    role_arn: Option<String>,
+}
+
+impl CredentialsProvider {
+    // Private
+
+    fn resolve(&self) -> Result<AwsCredentials, CredentialsError> {
+        let settings = match self.cluster {
+            Some(ref cluster) => ClusterAwsSettings::load(cluster)?,
+            None => ClusterAwsSettings::default(),
+        };
+
+        let mfa_code = match settings.mfa_serial {
+            Some(ref mfa_serial) => Some(
+                read_line(&format!("Enter the MFA code for {}: ", mfa_serial)).map_err(|error| {
+                    CredentialsError::new(format!("Failed to read the MFA code: {}", error))
+                })?
+            ),
+            None => None,
+        };
+
+        let sts_client = || -> Result<StsClient<ChainProvider, _>, CredentialsError> {
+            Ok(StsClient::new(
+                default_tls_client().map_err(|error| CredentialsError::new(format!(
+                    "Failed to create HTTP client with TLS: {}",
+                    error,
+                )))?,
+                self.chain.clone(),
+                self.sts_region()?,
+            ))
+        };
+
+        match settings.role_arn {
+            Some(role_arn) => {
+                let session_name = format!(
+                    "kaws-{}",
+                    self.cluster.as_ref().map_or("session", |cluster| cluster.as_str()),
+                );
+
+                let mut provider = StsAssumeRoleSessionCredentialsProvider::new(
+                    sts_client()?,
+                    role_arn,
+                    session_name,
+                    settings.external_id,
+                    None,
+                    None,
+                    settings.mfa_serial,
+                );
+
+                if let Some(mfa_code) = mfa_code {
+                    provider.set_mfa_code(mfa_code);
+                }
+
+                provider.assume_role()
+            }
+            None => match settings.mfa_serial {
+                Some(mfa_serial) => {
+                    let mut provider = StsSessionCredentialsProvider::new(
+                        sts_client()?,
+                        None,
+                        Some(mfa_serial),
+                    );
+
+                    if let Some(mfa_code) = mfa_code {
+                        provider.set_mfa_code(mfa_code);
+                    }
+
+                    provider.get_session_token()
+                }
+                None => self.chain.credentials(),
+            },
+        }
+    }
+
+    /// The region of the STS endpoint that issues temporary credentials: the one given to the
+    /// command, or else the cluster's. The global endpoint in us-east-1 is only the fallback, since
+    /// it can't be reached from other partitions or from networks that only allow VPC endpoints.
+    fn sts_region(&self) -> Result<Region, CredentialsError> {
+        let region = match self.region {
+            Some(ref region) => region.clone(),
+            None => match self.cluster {
+                Some(ref cluster) if ClusterConfig::exists(cluster) => {
+                    ClusterConfig::load(cluster).map_err(|error| {
+                        CredentialsError::new(format!("{}", error))
+                    })?.region
+                }
+                _ => String::new(),
+            },
+        };
+
+        if region.is_empty() {
+            return Ok(Region::UsEast1);
+        }
+
+        region.parse().map_err(|error| CredentialsError::new(format!("{}", error)))
+    }
+}
+
+impl ProvideAwsCredentials for CredentialsProvider {
+    fn credentials(&self) -> Result<AwsCredentials, CredentialsError> {
+        if let Some(ref credentials) = *self.resolved.borrow() {
+            return Ok(credentials.clone());
+        }
+
+        let credentials = self.resolve()?;
+
+        *self.resolved.borrow_mut() = Some(credentials.clone());
+
+        Ok(credentials)
+    }
+}
+
+impl ProvideAwsCredentials for ResolvedCredentials {
+    fn credentials(&self) -> Result<AwsCredentials, CredentialsError> {
+        Ok(self.0.clone())
+    }
+}
+
+impl ClusterAwsSettings {
+    fn load(cluster: &str) -> Result<Self, CredentialsError> {
+        let path = format!("clusters/{}/aws.json", cluster);
+
+        match File::open(&path) {
+            Ok(file) => from_reader(file).map_err(|error| {
+                CredentialsError::new(format!("Failed to parse {}: {}", path, error))
+            }),
+            Err(ref error) if error.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(CredentialsError::new(format!("Failed to read {}: {}", path, error))),
+        }
+    }
+}
+
+/// Creates the credentials provider for a command. `cluster` should be the cluster the command
+/// operates on, if any, so that the cluster's IAM role and MFA settings are applied. `region` is
+/// the region given to the command, if any.
+pub fn credentials_provider(
+    cluster: Option<&str>,
+    region: Option<&str>,
+    path: Option<&str>,
+    profile: Option<&str>,
+) -> CredentialsProvider {
     let mut profile_provider = ProfileProvider::new().expect(
         "Failed to create AWS credentials provider."
     );
@@ -13,5 +199,28 @@ pub fn credentials_provider(path: Option<&str>, profile: Option<&str>) -> ChainP
         profile_provider.set_profile(profile);
     }
 
-    ChainProvider::with_profile_provider(profile_provider)
+    CredentialsProvider {
+        chain: ChainProvider::with_profile_provider(profile_provider),
+        cluster: cluster.map(|cluster| cluster.to_owned()),
+        region: region.map(|region| region.to_owned()),
+        resolved: Rc::new(RefCell::new(None)),
+    }
+}
+
+/// Passes a full set of AWS credentials to a child process through the environment variables
+/// that Terraform and the AWS CLI read. A session token inherited from the parent environment is
+/// removed when the credentials don't have one, since it would not match the other values.
+pub fn set_credentials_env(command: &mut Command, credentials: &AwsCredentials) {
+    command
+        .env("AWS_ACCESS_KEY_ID", credentials.aws_access_key_id())
+        .env("AWS_SECRET_ACCESS_KEY", credentials.aws_secret_access_key());
+
+    match *credentials.token() {
+        Some(ref token) => {
+            command.env("AWS_SESSION_TOKEN", token).env_remove("AWS_SECURITY_TOKEN");
+        }
+        None => {
+            command.env_remove("AWS_SESSION_TOKEN").env_remove("AWS_SECURITY_TOKEN");
+        }
+    }
 }
@@ -1,9 +1,21 @@
-use std::cmp::Ordering;
-
-use bitstring::BitString;
-use cidr::Ipv4Cidr;
 use clap::{App, AppSettings, Arg, SubCommand};
 
+use ami::{OS_CHANNELS, validate_os_version};
+use cloudconfig::CLOUD_CONFIG_ROLES;
+use config::{
+    TOPOLOGIES,
+    VOLUME_TYPES,
+    parse_cidr,
+    validate_instance_type,
+    validate_kubernetes_version,
+    validate_label,
+    validate_node_pool_name,
+    validate_spot_price,
+    validate_tag,
+    validate_taint,
+};
+use resources::ROLES;
+
 pub fn app<'a, 'b>() -> App<'a, 'b> {
     App::new("kaws")
         .version(env!("CARGO_PKG_VERSION"))
@@ -107,13 +119,59 @@ fn cluster<'a, 'b>() -> App<'a, 'b> {
     SubCommand::with_name("cluster")
         .about("Commands for managing a cluster's infrastructure")
         .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(cluster_ami_update())
         .subcommand(cluster_apply())
+        .subcommand(cluster_cloud_config())
         .subcommand(cluster_destroy())
+        .subcommand(cluster_drift())
         .subcommand(cluster_generate_pki())
+        .subcommand(cluster_get())
+        .subcommand(cluster_import())
         .subcommand(cluster_init())
+        .subcommand(cluster_migrate_config())
+        .subcommand(cluster_nodepool())
         .subcommand(cluster_output())
         .subcommand(cluster_plan())
+        .subcommand(cluster_protect())
         .subcommand(cluster_refresh())
+        .subcommand(cluster_roll())
+        .subcommand(cluster_set())
+        .subcommand(cluster_state())
+        .subcommand(cluster_taint())
+        .subcommand(cluster_unprotect())
+        .subcommand(cluster_upgrade())
+}
+
+fn cluster_ami_update<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("ami-update")
+        .about("Moves the target cluster to the AMI of the latest Container Linux release in its channel")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster to update")
+        )
+        .arg(
+            Arg::with_name("os-channel")
+                .long("os-channel")
+                .takes_value(true)
+                .possible_values(OS_CHANNELS)
+                .help("Container Linux channel to move the cluster to, defaults to the cluster's os_channel")
+        )
+        .arg(
+            Arg::with_name("os-manifest")
+                .long("os-manifest")
+                .takes_value(true)
+                .help("Path to a Container Linux release manifest to find the AMI in, instead of downloading it")
+        )
+        .arg(
+            Arg::with_name("os-version")
+                .long("os-version")
+                .takes_value(true)
+                .validator(|version| validate_os_version(&version))
+                .help("Container Linux release to move the cluster to, e.g. \"1632.3.0\", defaults to the latest release in the channel")
+        )
+        .after_help("\nChanges coreos_ami, os_channel, and os_version in clusters/CLUSTER/kaws.toml. Release manifests are downloaded from the Container Linux release servers and cached in .os-manifests, where the manifest of a channel's latest release is used for up to a day. The new AMI only takes effect once the change is applied and the existing instances are replaced, e.g. with `kaws cluster roll`.")
 }
 
 fn cluster_apply<'a, 'b>() -> App<'a, 'b> {
@@ -138,6 +196,21 @@ fn cluster_apply<'a, 'b>() -> App<'a, 'b> {
                 .takes_value(true)
                 .help("Name of the AWS credentials profile to use, defaults to \"default\"")
         )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Prints the Terraform command that would be run, with secrets redacted, without running it")
+        )
+        .arg(
+            Arg::with_name("only")
+                .long("only")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .use_delimiter(true)
+                .possible_values(ROLES)
+                .help("Limits the operation to the resources of the given roles, separated by commas")
+        )
         .arg(
             Arg::with_name("terraform-args")
                 .index(2)
@@ -148,6 +221,25 @@ fn cluster_apply<'a, 'b>() -> App<'a, 'b> {
         .after_help("\nAny arguments following a literal -- will be passed directly as options to `terraform apply`.")
 }
 
+fn cluster_cloud_config<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("cloud-config")
+        .about("Displays the cloud-config of a role with the cluster's cloud-config fragment merged in")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster whose cloud-config should be displayed")
+        )
+        .arg(
+            Arg::with_name("role")
+                .index(2)
+                .required(true)
+                .possible_values(CLOUD_CONFIG_ROLES)
+                .help("The role whose cloud-config should be displayed")
+        )
+        .after_help("\nFragments are read from clusters/CLUSTER/cloud-config/ROLE.toml and can add units, files, and SSH keys to the cloud-config of a role. The fragment for nodes also applies to every node pool. Values that are only known to Terraform, such as certificates, are displayed as ${...} placeholders.\n\nThe templates are read from the kaws Terraform module in .terraform/modules. If Terraform hasn't been initialized yet, `terraform init` is run first, which downloads the module and needs network access.")
+}
+
 fn cluster_destroy<'a, 'b>() -> App<'a, 'b> {
     SubCommand::with_name("destroy")
         .about("Destroys resources defined by the Terraform plan for the target cluster")
@@ -170,6 +262,11 @@ fn cluster_destroy<'a, 'b>() -> App<'a, 'b> {
                 .takes_value(true)
                 .help("Name of the AWS credentials profile to use, defaults to \"default\"")
         )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Prints the Terraform command that would be run, with secrets redacted, without running it")
+        )
         .arg(
             Arg::with_name("terraform-args")
                 .index(2)
@@ -177,7 +274,139 @@ fn cluster_destroy<'a, 'b>() -> App<'a, 'b> {
                 .hidden(true)
                 .help("Additional arguments to be passed on to `terraform destroy`")
         )
-        .after_help("\nAny arguments following a literal -- will be passed directly as options to `terraform destroy`.")
+        .after_help(
+            "\nAny arguments following a literal -- will be passed directly as options to `terraform destroy`, \
+            except for -force. Before destroying anything, kaws lists the etcd volumes and S3 buckets that will be \
+            lost, offers to save a final etcd snapshot to clusters/CLUSTER/backups, and asks for the cluster's name \
+            to be typed as confirmation. It then saves a snapshot of the Terraform state to \
+            clusters/CLUSTER/state-history. Protected clusters cannot be destroyed."
+        )
+}
+
+fn cluster_drift<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("drift")
+        .about("Reports which clusters' AWS resources differ from their Terraform configuration")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .multiple(true)
+                .required_unless("all")
+                .conflicts_with("all")
+                .help("The clusters to check")
+        )
+        .arg(
+            Arg::with_name("all")
+                .long("all")
+                .help("Checks every cluster in the clusters directory")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-path")
+                .long("aws-credentials-path")
+                .takes_value(true)
+                .help("Path to the AWS credentials file, defaults to ~/.aws/credentials")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-profile")
+                .long("aws-credentials-profile")
+                .takes_value(true)
+                .help("Name of the AWS credentials profile to use, defaults to \"default\"")
+        )
+        .arg(
+            Arg::with_name("concurrency")
+                .short("j")
+                .long("concurrency")
+                .takes_value(true)
+                .validator(|concurrency| {
+                    match concurrency.parse::<usize>() {
+                        Ok(concurrency) if concurrency > 0 => Ok(()),
+                        _ => Err("Concurrency must be a positive integer.".to_string()),
+                    }
+                })
+                .help("The maximum number of clusters to check at the same time, defaults to 4")
+        )
+        .arg(
+            Arg::with_name("detailed-exitcode")
+                .long("detailed-exitcode")
+                .help("Exits with 2 if any cluster has drifted")
+        )
+        .arg(
+            Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["json", "markdown", "table"])
+                .help("The format of the report, defaults to \"table\"")
+        )
+        .after_help(
+            "\nRuns `terraform plan -detailed-exitcode` for each cluster, which refreshes the state from AWS \
+            without changing anything, and summarizes the resources each plan would change."
+        )
+}
+
+fn cluster_get<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("get")
+        .about("Displays the settings in the target cluster's kaws.toml")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster whose settings to display")
+        )
+        .arg(
+            Arg::with_name("key")
+                .index(2)
+                .help("The setting to display, e.g. \"instance_size\" or \"nodes.max_size\"; all settings are displayed if omitted")
+        )
+        .after_help("\nWhen a single setting is displayed, only its value is printed, with each item of a list on its own line, so it can be used in scripts.")
+}
+
+fn cluster_import<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("import")
+        .about("Imports an existing AWS resource into the target cluster's Terraform state")
+        .setting(AppSettings::TrailingVarArg)
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster to import the resource into")
+        )
+        .arg(
+            Arg::with_name("address")
+                .index(2)
+                .required(true)
+                .help("A resource address, e.g. \"module.kaws.aws_instance.etcd_01\", \"aws_instance.etcd_01\", or \"etcd_01\"")
+        )
+        .arg(
+            Arg::with_name("id")
+                .index(3)
+                .required(true)
+                .help("The AWS ID of the resource to import, e.g. \"i-0123456789abcdef0\"")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-path")
+                .long("aws-credentials-path")
+                .takes_value(true)
+                .help("Path to the AWS credentials file, defaults to ~/.aws/credentials")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-profile")
+                .long("aws-credentials-profile")
+                .takes_value(true)
+                .help("Name of the AWS credentials profile to use, defaults to \"default\"")
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Prints the Terraform command that would be run, with secrets redacted, without running it")
+        )
+        .arg(
+            Arg::with_name("terraform-args")
+                .index(4)
+                .multiple(true)
+                .hidden(true)
+                .help("Additional arguments to be passed on to `terraform import`")
+        )
+        .after_help("\nResource addresses without a module are resolved within the kaws module. A bare resource name, such as etcd_01 or k8s_nodes, refers to the instance or autoscaling group with that name, or to the only kaws resource with that name. A snapshot of the state is saved beforehand. Any arguments following a literal -- will be passed directly as options to `terraform import`.")
 }
 
 fn cluster_init<'a, 'b>() -> App<'a, 'b> {
@@ -194,7 +423,6 @@ fn cluster_init<'a, 'b>() -> App<'a, 'b> {
                 .short("A")
                 .long("aws-account-id")
                 .takes_value(true)
-                .required(true)
                 .help("The numeric ID of the AWS account, e.g. \"123456789012\"")
         )
         .arg(
@@ -202,64 +430,133 @@ fn cluster_init<'a, 'b>() -> App<'a, 'b> {
                 .short("a")
                 .long("ami")
                 .takes_value(true)
-                .required(true)
                 .help("EC2 AMI ID to use for all CoreOS instances, e.g. \"ami-1234\"")
         )
         .arg(
             Arg::with_name("availability-zone")
                 .long("availability-zone")
                 .takes_value(true)
-                .required(true)
-                .help("Availability Zone for etcd instances and EBS volumes, e.g. \"us-east-1a\"")
+                .multiple(true)
+                .number_of_values(1)
+                .help("An Availability Zone to run the cluster in, e.g. \"us-east-1a\"; this option can be specified more than once to spread the cluster across several zones")
         )
         .arg(
             Arg::with_name("cidr")
                 .short("C")
                 .long("cidr")
                 .takes_value(true)
-                .required(true)
                 .help("IPv4 network range of the subnet where Kubernetes nodes will run, e.g. \"10.0.2.0/24\"")
-                .validator(|cidr| {
-                    let cidr: Ipv4Cidr = match cidr.parse() {
-                        Ok(cidr) => cidr,
-                        Err(_) => return Err("Invalid CIDR provided.".to_string()),
-                    };
-
-                    let vpc_cidr: Ipv4Cidr = "10.0.0.0/16".parse().unwrap();
-                    let elb_cidr: Ipv4Cidr = "10.0.0.0/24".parse().unwrap();
-                    let etcd_cidr: Ipv4Cidr = "10.0.1.0/24".parse().unwrap();
-
-                    match cidr.subset_cmp(&vpc_cidr) {
-                        Some(Ordering::Less) => {}
-                        _ => return Err("Provided CIDR must be a subset of 10.0.0.0/16.".to_string()),
-                    }
-
-                    match cidr.subset_cmp(&elb_cidr) {
-                        Some(_) => return Err("Provided CIDR cannot overlap with 10.0.0.0/24, which is used for ELBs.".to_string()),
-                        None => {}
-                    }
-
-                    match cidr.subset_cmp(&etcd_cidr) {
-                        Some(_) => return Err("Provided CIDR cannot overlap with 10.0.1.0/24, which is used for etcd.".to_string()),
-                        None => {}
-                    }
-
-                    Ok(())
-                })
+                .validator(|cidr| parse_cidr(&cidr).map(|_| ()))
+        )
+        .arg(
+            Arg::with_name("balancers-cidr")
+                .long("balancers-cidr")
+                .takes_value(true)
+                .help("IPv4 network range of the subnet for ELBs, defaults to \"10.0.0.0/24\"")
+                .validator(|cidr| parse_cidr(&cidr).map(|_| ()))
+        )
+        .arg(
+            Arg::with_name("bastion-instance-type")
+                .long("bastion-instance-type")
+                .takes_value(true)
+                .help("EC2 instance type for the bastion, defaults to \"t2.micro\"")
+                .validator(|instance_type| validate_instance_type(&instance_type))
+        )
+        .arg(
+            Arg::with_name("bastion-root-volume-size")
+                .long("bastion-root-volume-size")
+                .takes_value(true)
+                .validator(validate_size)
+                .help("Size in GiB of the bastion's root volume, defaults to 8")
+        )
+        .arg(
+            Arg::with_name("bastion-root-volume-type")
+                .long("bastion-root-volume-type")
+                .takes_value(true)
+                .possible_values(VOLUME_TYPES)
+                .help("EBS volume type of the bastion's root volume, defaults to \"gp2\"")
+        )
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .takes_value(true)
+                .help("Path to a kaws.toml file to start from; other options override its settings")
+        )
+        .arg(
+            Arg::with_name("dns-service-ip")
+                .long("dns-service-ip")
+                .takes_value(true)
+                .help("IP address of the cluster DNS service, within the service network, defaults to \"10.3.0.10\"")
         )
         .arg(
             Arg::with_name("domain")
                 .short("d")
                 .long("domain")
                 .takes_value(true)
-                .required(true)
                 .help("The base domain name for the cluster, e.g. \"example.com\"")
         )
+        .arg(
+            Arg::with_name("etcd-cidr")
+                .long("etcd-cidr")
+                .takes_value(true)
+                .help("IPv4 network range of the subnet for etcd instances, defaults to \"10.0.1.0/24\"")
+                .validator(|cidr| parse_cidr(&cidr).map(|_| ()))
+        )
+        .arg(
+            Arg::with_name("etcd-members")
+                .long("etcd-members")
+                .takes_value(true)
+                .possible_values(&["1", "3", "5"])
+                .help("The number of etcd instances, defaults to 3")
+        )
+        .arg(
+            Arg::with_name("etcd-instance-type")
+                .long("etcd-instance-type")
+                .takes_value(true)
+                .help("EC2 instance type for etcd instances, defaults to the instance size")
+                .validator(|instance_type| validate_instance_type(&instance_type))
+        )
+        .arg(
+            Arg::with_name("etcd-root-volume-size")
+                .long("etcd-root-volume-size")
+                .takes_value(true)
+                .validator(validate_size)
+                .help("Size in GiB of the etcd instances' root volumes, defaults to 8")
+        )
+        .arg(
+            Arg::with_name("etcd-root-volume-type")
+                .long("etcd-root-volume-type")
+                .takes_value(true)
+                .possible_values(VOLUME_TYPES)
+                .help("EBS volume type of the etcd instances' root volumes, defaults to \"gp2\"")
+        )
+        .arg(
+            Arg::with_name("etcd-volume-size")
+                .long("etcd-volume-size")
+                .takes_value(true)
+                .validator(validate_size)
+                .help("Size in GiB of the EBS volumes for etcd's data, defaults to 10")
+        )
+        .arg(
+            Arg::with_name("etcd-volume-type")
+                .long("etcd-volume-type")
+                .takes_value(true)
+                .possible_values(VOLUME_TYPES)
+                .help("EBS volume type of the volumes for etcd's data, defaults to \"gp2\"")
+        )
+        .arg(
+            Arg::with_name("masters-instance-type")
+                .long("masters-instance-type")
+                .takes_value(true)
+                .help("EC2 instance type for Kubernetes masters, defaults to the instance size")
+                .validator(|instance_type| validate_instance_type(&instance_type))
+        )
         .arg(
             Arg::with_name("masters-max-size")
                 .long("masters-max-size")
                 .takes_value(true)
-                .required(true)
+                .validator(validate_size)
                 .help(
                     "The maximum number of EC2 instances the Kubernetes masters may autoscale to"
                 )
@@ -268,16 +565,37 @@ fn cluster_init<'a, 'b>() -> App<'a, 'b> {
             Arg::with_name("masters-min-size")
                 .long("masters-min-size")
                 .takes_value(true)
-                .required(true)
+                .validator(validate_size)
                 .help(
                     "The minimum number of EC2 instances the Kubernetes masters may autoscale to"
                 )
         )
+        .arg(
+            Arg::with_name("masters-root-volume-size")
+                .long("masters-root-volume-size")
+                .takes_value(true)
+                .validator(validate_size)
+                .help("Size in GiB of the Kubernetes masters' root volumes, defaults to 30")
+        )
+        .arg(
+            Arg::with_name("masters-root-volume-type")
+                .long("masters-root-volume-type")
+                .takes_value(true)
+                .possible_values(VOLUME_TYPES)
+                .help("EBS volume type of the Kubernetes masters' root volumes, defaults to \"gp2\"")
+        )
+        .arg(
+            Arg::with_name("nodes-instance-type")
+                .long("nodes-instance-type")
+                .takes_value(true)
+                .help("EC2 instance type for Kubernetes nodes, defaults to the instance size")
+                .validator(|instance_type| validate_instance_type(&instance_type))
+        )
         .arg(
             Arg::with_name("nodes-max-size")
                 .long("nodes-max-size")
                 .takes_value(true)
-                .required(true)
+                .validator(validate_size)
                 .help(
                     "The maximum number of EC2 instances the Kubernetes nodes may autoscale to"
                 )
@@ -286,26 +604,75 @@ fn cluster_init<'a, 'b>() -> App<'a, 'b> {
             Arg::with_name("nodes-min-size")
                 .long("nodes-min-size")
                 .takes_value(true)
-                .required(true)
+                .validator(validate_size)
                 .help(
                     "The minimum number of EC2 instances the Kubernetes nodes may autoscale to"
                 )
         )
+        .arg(
+            Arg::with_name("nodes-root-volume-size")
+                .long("nodes-root-volume-size")
+                .takes_value(true)
+                .validator(validate_size)
+                .help("Size in GiB of the Kubernetes nodes' root volumes, defaults to 30")
+        )
+        .arg(
+            Arg::with_name("nodes-root-volume-type")
+                .long("nodes-root-volume-type")
+                .takes_value(true)
+                .possible_values(VOLUME_TYPES)
+                .help("EBS volume type of the Kubernetes nodes' root volumes, defaults to \"gp2\"")
+        )
+        .arg(
+            Arg::with_name("os-channel")
+                .long("os-channel")
+                .takes_value(true)
+                .possible_values(OS_CHANNELS)
+                .conflicts_with("ami")
+                .help("Container Linux channel to find the AMI in, as an alternative to --ami")
+        )
+        .arg(
+            Arg::with_name("os-manifest")
+                .long("os-manifest")
+                .takes_value(true)
+                .requires("os-channel")
+                .help("Path to a Container Linux release manifest to find the AMI in, instead of downloading it")
+        )
+        .arg(
+            Arg::with_name("os-version")
+                .long("os-version")
+                .takes_value(true)
+                .requires("os-channel")
+                .validator(|version| validate_os_version(&version))
+                .help("Container Linux release to find the AMI for, e.g. \"1632.3.0\", defaults to the latest release in the channel")
+        )
+        .arg(
+            Arg::with_name("pod-cidr")
+                .long("pod-cidr")
+                .takes_value(true)
+                .help("IPv4 network range pods get their IP addresses from, defaults to \"10.2.0.0/16\"")
+                .validator(|cidr| parse_cidr(&cidr).map(|_| ()))
+        )
         .arg(
             Arg::with_name("region")
                 .short("r")
                 .long("region")
                 .takes_value(true)
-                .required(true)
                 .help("AWS Region to create the resources in, e.g. \"us-east-1\"")
         )
+        .arg(
+            Arg::with_name("service-cidr")
+                .long("service-cidr")
+                .takes_value(true)
+                .help("IPv4 network range Kubernetes services get their IP addresses from, defaults to \"10.3.0.0/24\"")
+                .validator(|cidr| parse_cidr(&cidr).map(|_| ()))
+        )
         .arg(
             Arg::with_name("iam-user")
                 .short("i")
                 .long("iam-user")
                 .takes_value(true)
                 .multiple(true)
-                .required(true)
                 .number_of_values(1)
                 .help("An IAM user name who will have access to cluster PKI secrets, e.g. \"alice\"; this option can be specified more than once")
         )
@@ -314,8 +681,8 @@ fn cluster_init<'a, 'b>() -> App<'a, 'b> {
                 .short("s")
                 .long("instance-size")
                 .takes_value(true)
-                .required(true)
-                .help("EC2 instance size to use for all instances, e.g. \"m3.medium\"")
+                .help("EC2 instance size to use for etcd, masters, and nodes unless they are given their own instance types, e.g. \"m3.medium\"")
+                .validator(|instance_type| validate_instance_type(&instance_type))
         )
         .arg(
             Arg::with_name("ssh-key")
@@ -323,7 +690,6 @@ fn cluster_init<'a, 'b>() -> App<'a, 'b> {
                 .long("ssh-key")
                 .takes_value(true)
                 .multiple(true)
-                .required(true)
                 .number_of_values(1)
                 .help("SSH public key to add to ~/.ssh/authorized_keys on each server; this option can be specified more than once")
         )
@@ -332,30 +698,40 @@ fn cluster_init<'a, 'b>() -> App<'a, 'b> {
                 .short("v")
                 .long("kubernetes-version")
                 .takes_value(true)
-                .required(true)
-                .help("Version of Kubernetes to use, e.g. \"1.0.0\"")
-                .validator(|version| {
-                    let version = version.as_str();
-
-                    if version.starts_with('v') {
-                        return Err("Kubernetes version should be specified without the leading 'v'".to_string());
-                    }
-
-                    if version >= "1.7" {
-                        return Ok(());
-                    } else {
-                        return Err("This version of kaws supports only Kubernetes 1.7.0 or greater".to_string());
-                    }
-                })
+                .help("Version of Kubernetes to use, e.g. \"1.8.4\"; kaws supports 1.7.0 through 1.10.x")
+                .validator(|version| validate_kubernetes_version(&version))
+        )
+        .arg(
+            Arg::with_name("tag")
+                .long("tag")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .validator(|tag| validate_tag(&tag))
+                .help("A tag for every AWS resource that supports tags, as key=value; may be given more than once")
+        )
+        .arg(
+            Arg::with_name("topology")
+                .long("topology")
+                .takes_value(true)
+                .possible_values(TOPOLOGIES)
+                .help("\"public\" to give every server a public IP address, or \"private\" to put etcd and the Kubernetes masters and nodes in private subnets behind NAT gateways, defaults to \"public\"")
+        )
+        .arg(
+            Arg::with_name("vpc-cidr")
+                .long("vpc-cidr")
+                .takes_value(true)
+                .help("IPv4 network range of the cluster's VPC, defaults to \"10.0.0.0/16\"")
+                .validator(|cidr| parse_cidr(&cidr).map(|_| ()))
         )
         .arg(
             Arg::with_name("zone-id")
                 .short("z")
                 .long("zone-id")
                 .takes_value(true)
-                .required(true)
                 .help("Route 53 hosted zone ID")
         )
+        .after_help("\nThe settings are written to clusters/CLUSTER/kaws.toml, from which terraform.tfvars is generated. With --os-channel, the AMI for the region is found in the channel's release manifest, which is downloaded from the Container Linux release servers and cached in .os-manifests. All settings without defaults are required, either as options or in the file given with --config. The subnets must be within the VPC's network range and must not overlap, and each is divided evenly between the Availability Zones.")
 }
 
 fn cluster_generate_pki<'a, 'b>() -> App<'a, 'b> {
@@ -513,43 +889,211 @@ fn cluster_generate_pki_kubernetes<'a, 'b>() -> App<'a, 'b> {
         )
 }
 
-fn cluster_output<'a, 'b>() -> App<'a, 'b> {
-    SubCommand::with_name("output")
-        .about("Displays the Terraform outputs for the target cluster")
+fn cluster_migrate_config<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("migrate-config")
+        .about("Creates kaws.toml for a cluster initialized by an older version of kaws")
         .arg(
             Arg::with_name("cluster")
                 .index(1)
                 .required(true)
-                .help("The cluster whose plan should be displayed")
-        )
-        .arg(
-            Arg::with_name("output")
-                .index(2)
-                .help("The name of an individual output to display")
+                .help("The cluster to migrate")
         )
+        .after_help("\nReads the settings from clusters/CLUSTER/terraform.tfvars and writes them to clusters/CLUSTER/kaws.toml. From then on, terraform.tfvars is generated from kaws.toml before every Terraform command and should not be edited by hand.")
 }
 
-fn cluster_plan<'a, 'b>() -> App<'a, 'b> {
-    SubCommand::with_name("plan")
-        .about("Displays the Terraform plan for the target cluster")
-        .setting(AppSettings::TrailingVarArg)
+fn cluster_nodepool<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("nodepool")
+        .about("Commands for managing a cluster's pools of Kubernetes nodes")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(cluster_nodepool_add())
+        .subcommand(cluster_nodepool_list())
+        .subcommand(cluster_nodepool_remove())
+}
+
+fn cluster_nodepool_add<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("add")
+        .about("Adds a pool of Kubernetes nodes to the target cluster's kaws.toml")
         .arg(
             Arg::with_name("cluster")
                 .index(1)
                 .required(true)
-                .help("The cluster whose plan should be displayed")
+                .help("The cluster to add the node pool to")
         )
         .arg(
-            Arg::with_name("aws-credentials-path")
-                .long("aws-credentials-path")
-                .takes_value(true)
-                .help("Path to the AWS credentials file, defaults to ~/.aws/credentials")
+            Arg::with_name("pool")
+                .index(2)
+                .required(true)
+                .validator(|name| validate_node_pool_name(&name))
+                .help("The name of the node pool, e.g. \"gpu\"")
         )
         .arg(
-            Arg::with_name("aws-credentials-profile")
-                .long("aws-credentials-profile")
+            Arg::with_name("instance-type")
+                .short("t")
+                .long("instance-type")
                 .takes_value(true)
-                .help("Name of the AWS credentials profile to use, defaults to \"default\"")
+                .required(true)
+                .validator(|instance_type| validate_instance_type(&instance_type))
+                .help("EC2 instance type to use for the pool's nodes, e.g. \"p2.xlarge\"")
+        )
+        .arg(
+            Arg::with_name("label")
+                .short("l")
+                .long("label")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .validator(|label| validate_label(&label))
+                .help("A Kubernetes label for the pool's nodes, as key=value; may be given more than once")
+        )
+        .arg(
+            Arg::with_name("max-size")
+                .long("max-size")
+                .takes_value(true)
+                .required(true)
+                .validator(validate_size)
+                .help("The maximum number of EC2 instances the pool may autoscale to")
+        )
+        .arg(
+            Arg::with_name("min-size")
+                .long("min-size")
+                .takes_value(true)
+                .required(true)
+                .validator(validate_size)
+                .help("The minimum number of EC2 instances the pool may autoscale to")
+        )
+        .arg(
+            Arg::with_name("no-on-demand-fallback")
+                .long("no-on-demand-fallback")
+                .requires("spot")
+                .help("Leaves out the spot pool's autoscaling group of on-demand instances")
+        )
+        .arg(
+            Arg::with_name("spot")
+                .long("spot")
+                .requires("spot-max-price")
+                .help("Runs the pool's nodes on spot instances")
+        )
+        .arg(
+            Arg::with_name("spot-instance-type")
+                .long("spot-instance-type")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .requires("spot")
+                .validator(|instance_type| validate_instance_type(&instance_type))
+                .help("An EC2 instance type to request spot instances of, defaults to --instance-type; may be given more than once to diversify across instance types")
+        )
+        .arg(
+            Arg::with_name("spot-max-price")
+                .long("spot-max-price")
+                .takes_value(true)
+                .requires("spot")
+                .validator(|price| validate_spot_price(&price))
+                .help("The maximum hourly price in US dollars to pay for each spot instance, e.g. \"0.05\"")
+        )
+        .arg(
+            Arg::with_name("taint")
+                .long("taint")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .validator(|taint| validate_taint(&taint))
+                .help("A Kubernetes taint for the pool's nodes, as key=value:Effect or key:Effect; may be given more than once")
+        )
+        .after_help("\nThe pool gets its own launch configuration, autoscaling group, and cloud-config, and its nodes register with Kubernetes with the given labels and taints. Every node is also labeled kaws/node-pool=POOL, and the nodes of the [nodes] group are labeled kaws/node-pool=default.\n\nA spot pool has an autoscaling group of spot instances for each spot instance type, which divide the pool's minimum and maximum sizes between them, and an autoscaling group of on-demand instances of --instance-type with a minimum size of 0 and the pool's maximum size. kaws does not deploy the Kubernetes cluster autoscaler, so the on-demand group stays empty unless you run the autoscaler to scale it up when spot instances aren't available, or resize it yourself. The pool's nodes are labeled kaws/lifecycle=spot or kaws/lifecycle=on-demand and tainted kaws/spot=true:NoSchedule, so only pods that tolerate that taint are scheduled onto them.")
+}
+
+fn cluster_nodepool_list<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("list")
+        .about("Lists the pools of Kubernetes nodes in the target cluster's kaws.toml")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster whose node pools to list")
+        )
+}
+
+fn cluster_nodepool_remove<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("remove")
+        .about("Removes a pool of Kubernetes nodes from the target cluster's kaws.toml")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster to remove the node pool from")
+        )
+        .arg(
+            Arg::with_name("pool")
+                .index(2)
+                .required(true)
+                .help("The name of the node pool to remove")
+        )
+        .after_help("\nThe pool's instances are terminated when the change is applied. Drain its nodes with `kubectl drain` first so their pods are rescheduled gracefully. The autoscaling groups of the pools listed after it in kaws.toml are replaced by the same apply, which terminates their instances without draining them too, so kaws asks for confirmation before removing any pool but the last.")
+}
+
+fn cluster_output<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("output")
+        .about("Displays the Terraform outputs for the target cluster")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster whose plan should be displayed")
+        )
+        .arg(
+            Arg::with_name("output")
+                .index(2)
+                .help("The name of an individual output to display")
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .help("Displays the outputs as JSON")
+        )
+}
+
+fn cluster_plan<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("plan")
+        .about("Displays the Terraform plan for the target cluster")
+        .setting(AppSettings::TrailingVarArg)
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster whose plan should be displayed")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-path")
+                .long("aws-credentials-path")
+                .takes_value(true)
+                .help("Path to the AWS credentials file, defaults to ~/.aws/credentials")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-profile")
+                .long("aws-credentials-profile")
+                .takes_value(true)
+                .help("Name of the AWS credentials profile to use, defaults to \"default\"")
+        )
+        .arg(
+            Arg::with_name("detailed-exitcode")
+                .long("detailed-exitcode")
+                .help("Exits with 0 if there are no changes, 1 on error, and 2 if there are changes")
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Prints the Terraform command that would be run, with secrets redacted, without running it")
+        )
+        .arg(
+            Arg::with_name("only")
+                .long("only")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .use_delimiter(true)
+                .possible_values(ROLES)
+                .help("Limits the operation to the resources of the given roles, separated by commas")
         )
         .arg(
             Arg::with_name("terraform-args")
@@ -561,6 +1105,18 @@ fn cluster_plan<'a, 'b>() -> App<'a, 'b> {
         .after_help("\nAny arguments following a literal -- will be passed directly as options to `terraform plan`.")
 }
 
+fn cluster_protect<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("protect")
+        .about("Prevents the target cluster from being destroyed")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster to protect")
+        )
+        .after_help("\nCreates the file clusters/CLUSTER/protected, which should be committed to Git.")
+}
+
 fn cluster_refresh<'a, 'b>() -> App<'a, 'b> {
     SubCommand::with_name("refresh")
         .about("Refreshes the Terraform state for the target cluster")
@@ -583,6 +1139,11 @@ fn cluster_refresh<'a, 'b>() -> App<'a, 'b> {
                 .takes_value(true)
                 .help("Name of the AWS credentials profile to use, defaults to \"default\"")
         )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Prints the Terraform command that would be run, with secrets redacted, without running it")
+        )
         .arg(
             Arg::with_name("terraform-args")
                 .index(2)
@@ -593,6 +1154,305 @@ fn cluster_refresh<'a, 'b>() -> App<'a, 'b> {
         .after_help("\nAny arguments following a literal -- will be passed directly as options to `terraform refresh`.")
 }
 
+fn cluster_roll<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("roll")
+        .about("Replaces the target cluster's instances that were launched from outdated launch configurations")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster whose instances to replace")
+        )
+        .arg(
+            Arg::with_name("role")
+                .index(2)
+                .required(true)
+                .help("The instances to replace: \"masters\", \"nodes\" for the nodes in [nodes], or the name of a node pool")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-path")
+                .long("aws-credentials-path")
+                .takes_value(true)
+                .help("Path to the AWS credentials file, defaults to ~/.aws/credentials")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-profile")
+                .long("aws-credentials-profile")
+                .takes_value(true)
+                .help("Name of the AWS credentials profile to use, defaults to \"default\"")
+        )
+        .arg(
+            Arg::with_name("batch-size")
+                .short("b")
+                .long("batch-size")
+                .takes_value(true)
+                .validator(|batch_size| {
+                    match batch_size.parse::<usize>() {
+                        Ok(batch_size) if batch_size > 0 => Ok(()),
+                        _ => Err("The batch size must be a positive integer.".to_string()),
+                    }
+                })
+                .help("The number of instances to replace at the same time, defaults to 1")
+        )
+        .arg(
+            Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .help("Replaces the instances without asking for confirmation")
+        )
+        .after_help("\nAn instance is stale when it was launched from an older launch configuration than the one in the cluster's Terraform state, so apply changes with `kaws cluster apply` first. Nodes are cordoned and drained with kubectl, using the context kaws-CLUSTER, before their instances are terminated. Each batch is replaced once the previous one's replacements pass the ELB health check, http:8080/healthz for masters and http:10256/healthz for nodes, and their nodes are ready. When a batch is every instance in its autoscaling group, such as a cluster's only master, the group gets an extra instance first, so one stays in service. If the command is interrupted, run it again to continue. The AWS CLI must be installed.")
+}
+
+fn cluster_set<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("set")
+        .about("Changes settings in the target cluster's kaws.toml")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster whose settings to change")
+        )
+        .arg(
+            Arg::with_name("assignments")
+                .index(2)
+                .required(true)
+                .multiple(true)
+                .help("Settings to change, as key=value, e.g. \"nodes.max_size=5\"")
+        )
+        .arg(
+            Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .help("Writes the changes without asking for confirmation")
+        )
+        .after_help("\nLists are given as comma-separated values, e.g. \"iam_users=alice,bob\", and an empty value clears the list. The changed settings are validated in the same way as `kaws cluster init`, and the changes are displayed before they are written. Only the lines for the changed settings are rewritten, so comments in kaws.toml are preserved.")
+}
+
+fn cluster_state<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("state")
+        .about("Commands for managing a cluster's Terraform state")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(cluster_state_history())
+        .subcommand(cluster_state_mv())
+        .subcommand(cluster_state_restore())
+        .subcommand(cluster_state_rm())
+}
+
+fn cluster_state_history<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("history")
+        .about("Lists the saved snapshots of the target cluster's Terraform state")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster whose state snapshots should be listed")
+        )
+        .after_help(
+            "\nA snapshot of the Terraform state is saved to clusters/CLUSTER/state-history before every \
+            apply, destroy, import, refresh, taint, state mv, and state rm. The number of snapshots kept, and \
+            whether they are encrypted with the cluster's KMS key, are set in the [state_history] table of the \
+            cluster's kaws.toml."
+        )
+}
+
+fn cluster_state_mv<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("mv")
+        .about("Moves a resource to a new address in the target cluster's Terraform state")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster whose state should be changed")
+        )
+        .arg(
+            Arg::with_name("source")
+                .index(2)
+                .required(true)
+                .help("A resource address, e.g. \"module.kaws.aws_instance.etcd_01\", \"aws_instance.etcd_01\", or \"etcd_01\"")
+        )
+        .arg(
+            Arg::with_name("destination")
+                .index(3)
+                .required(true)
+                .help("The new address for the resource")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-path")
+                .long("aws-credentials-path")
+                .takes_value(true)
+                .help("Path to the AWS credentials file, defaults to ~/.aws/credentials")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-profile")
+                .long("aws-credentials-profile")
+                .takes_value(true)
+                .help("Name of the AWS credentials profile to use, defaults to \"default\"")
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Prints the Terraform command that would be run, with secrets redacted, without running it")
+        )
+        .after_help("\nResource addresses without a module are resolved within the kaws module. A bare resource name, such as etcd_01 or k8s_nodes, refers to the instance or autoscaling group with that name, or to the only kaws resource with that name. A snapshot of the state is saved beforehand.")
+}
+
+fn cluster_state_restore<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("restore")
+        .about("Replaces the target cluster's Terraform state with a saved snapshot")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster whose state should be restored")
+        )
+        .arg(
+            Arg::with_name("snapshot")
+                .index(2)
+                .required(true)
+                .help("The snapshot to restore, as listed by `kaws cluster state history`")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-path")
+                .long("aws-credentials-path")
+                .takes_value(true)
+                .help("Path to the AWS credentials file, defaults to ~/.aws/credentials")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-profile")
+                .long("aws-credentials-profile")
+                .takes_value(true)
+                .help("Name of the AWS credentials profile to use, defaults to \"default\"")
+        )
+        .arg(
+            Arg::with_name("region")
+                .short("r")
+                .long("region")
+                .takes_value(true)
+                .help("AWS Region where the KMS key lives, defaults to the region in the current state")
+        )
+        .after_help("\nThe current state is saved as a new snapshot before it is replaced.")
+}
+
+fn cluster_state_rm<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("rm")
+        .about("Removes resources from the target cluster's Terraform state without destroying them")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster whose state should be changed")
+        )
+        .arg(
+            Arg::with_name("address")
+                .index(2)
+                .required(true)
+                .multiple(true)
+                .help("A resource address, e.g. \"module.kaws.aws_instance.etcd_01\", \"aws_instance.etcd_01\", or \"etcd_01\"")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-path")
+                .long("aws-credentials-path")
+                .takes_value(true)
+                .help("Path to the AWS credentials file, defaults to ~/.aws/credentials")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-profile")
+                .long("aws-credentials-profile")
+                .takes_value(true)
+                .help("Name of the AWS credentials profile to use, defaults to \"default\"")
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Prints the Terraform command that would be run, with secrets redacted, without running it")
+        )
+        .after_help("\nResource addresses without a module are resolved within the kaws module. A bare resource name, such as etcd_01 or k8s_nodes, refers to the instance or autoscaling group with that name, or to the only kaws resource with that name. A snapshot of the state is saved beforehand.")
+}
+
+fn cluster_taint<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("taint")
+        .about("Marks a resource in the target cluster to be replaced on the next apply")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster whose resource should be tainted")
+        )
+        .arg(
+            Arg::with_name("address")
+                .index(2)
+                .required(true)
+                .help("A resource address, e.g. \"module.kaws.aws_instance.etcd_01\", \"aws_instance.etcd_01\", or \"etcd_01\"")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-path")
+                .long("aws-credentials-path")
+                .takes_value(true)
+                .help("Path to the AWS credentials file, defaults to ~/.aws/credentials")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-profile")
+                .long("aws-credentials-profile")
+                .takes_value(true)
+                .help("Name of the AWS credentials profile to use, defaults to \"default\"")
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Prints the Terraform command that would be run, with secrets redacted, without running it")
+        )
+        .after_help("\nResource addresses without a module are resolved within the kaws module. A bare resource name, such as etcd_01 or k8s_nodes, refers to the instance or autoscaling group with that name, or to the only kaws resource with that name. A snapshot of the state is saved beforehand.")
+}
+
+fn cluster_unprotect<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("unprotect")
+        .about("Allows the target cluster to be destroyed again")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster to unprotect")
+        )
+        .after_help("\nRemoves the file clusters/CLUSTER/protected.")
+}
+
+fn cluster_upgrade<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("upgrade")
+        .about("Upgrades the target cluster to a new version of Kubernetes, replacing its masters and nodes")
+        .arg(
+            Arg::with_name("cluster")
+                .index(1)
+                .required(true)
+                .help("The cluster to upgrade")
+        )
+        .arg(
+            Arg::with_name("version")
+                .index(2)
+                .required(true)
+                .validator(|version| validate_kubernetes_version(&version))
+                .help("The version of Kubernetes to upgrade to, e.g. \"1.9.2\"")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-path")
+                .long("aws-credentials-path")
+                .takes_value(true)
+                .help("Path to the AWS credentials file, defaults to ~/.aws/credentials")
+        )
+        .arg(
+            Arg::with_name("aws-credentials-profile")
+                .long("aws-credentials-profile")
+                .takes_value(true)
+                .help("Name of the AWS credentials profile to use, defaults to \"default\"")
+        )
+        .arg(
+            Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .help("Upgrades the cluster without asking for confirmation")
+        )
+        .after_help("\nThe version may move to a newer patch release or to the next minor release; downgrades and skipping minor releases are not supported. kubernetes_version is changed in kaws.toml and applied, and then the instances launched from the old launch configurations are replaced one at a time, the masters first and then the nodes. Each node is cordoned and drained with kubectl, using the context kaws-CLUSTER, before it is terminated, and the next instance is replaced once the replacement is healthy and its node is ready. A cluster with a single master gets a second one before its master is terminated, so the Kubernetes API stays available. If the upgrade is interrupted, or its apply fails or is declined, run the same command again to continue it. The AWS CLI must be installed.")
+}
+
 fn init<'a, 'b>() -> App<'a, 'b> {
     SubCommand::with_name("init")
         .about("Initializes a new repository for managing Kubernetes clusters")
@@ -610,3 +1470,10 @@ fn init<'a, 'b>() -> App<'a, 'b> {
                 .help("Custom source value for the Terraform module to use")
         )
 }
+
+fn validate_size(size: String) -> Result<(), String> {
+    match size.parse::<u32>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Sizes must be non-negative integers.".to_string()),
+    }
+}
@@ -0,0 +1,576 @@
+use std::fs::{File, read_dir};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use clap::ArgMatches;
+use serde_json::to_string as to_json_string;
+use toml::from_str;
+
+use config::ClusterConfig;
+use error::{KawsError, KawsResult};
+use terraform::Terraform;
+
+/// The roles whose cloud-configs can be extended with fragments. The nodes' fragment also applies
+/// to every node pool.
+pub const CLOUD_CONFIG_ROLES: &'static [&'static str] = &["bastion", "etcd", "masters", "nodes"];
+
+/// The systemd unit types a fragment's units may have.
+const UNIT_TYPES: &'static [&'static str] = &[
+    "automount",
+    "device",
+    "mount",
+    "path",
+    "scope",
+    "service",
+    "slice",
+    "socket",
+    "swap",
+    "target",
+    "timer",
+];
+
+/// The commands coreos-cloudinit can run for a unit.
+const UNIT_COMMANDS: &'static [&'static str] = &[
+    "reload",
+    "reload-or-restart",
+    "reload-or-try-restart",
+    "restart",
+    "start",
+    "stop",
+    "try-restart",
+];
+
+/// The units kaws defines for each role, which fragments can't replace.
+const RESERVED_UNITS: &'static [(&'static str, &'static [&'static str])] = &[
+    ("bastion", &[]),
+    ("etcd", &[
+        "create-etcd2-data-dir.service",
+        "decrypt-pki.service",
+        "docker.service",
+        "etcd2.service",
+        "format-etcd-ebs.service",
+        "var-lib-etcd2.mount",
+    ]),
+    ("masters", &["docker.service", "flanneld.service", "kubelet.service"]),
+    ("nodes", &["docker.service", "flanneld.service", "kubelet.service"]),
+];
+
+/// The files kaws writes for each role, which fragments can't replace. Paths ending in "/" cover
+/// everything in the directory.
+const RESERVED_PATHS: &'static [(&'static str, &'static [&'static str])] = &[
+    ("bastion", &["/etc/environment"]),
+    ("etcd", &["/etc/environment", "/etc/etcd2/ssl/", "/opt/kaws/"]),
+    ("masters", &[
+        "/etc/environment",
+        "/etc/etcd2/ssl/",
+        "/etc/kubernetes/manifests/kaws-rbac.yml",
+        "/etc/kubernetes/manifests/kube-apiserver.yml",
+        "/etc/kubernetes/manifests/kube-controller-manager.yml",
+        "/etc/kubernetes/manifests/kube-proxy.yml",
+        "/etc/kubernetes/manifests/kube-scheduler.yml",
+        "/etc/kubernetes/master-kubeconfig.yml",
+        "/etc/kubernetes/ssl/",
+        "/opt/kaws/",
+    ]),
+    ("nodes", &[
+        "/etc/environment",
+        "/etc/etcd2/ssl/",
+        "/etc/kubernetes/manifests/kube-proxy.yml",
+        "/etc/kubernetes/node-kubeconfig.yml",
+        "/etc/kubernetes/ssl/",
+        "/opt/kaws/",
+    ]),
+];
+
+/// Extra settings for one role's cloud-config, read from `clusters/CLUSTER/cloud-config/ROLE.toml`.
+///
+/// kaws merges them into the cloud-config rendered by the Terraform module: the units are started
+/// after kaws's own, the files are written alongside kaws's own, and the SSH keys are authorized in
+/// addition to the cluster's `ssh_keys`.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CloudConfigFragment {
+    pub ssh_authorized_keys: Vec<String>,
+    pub units: Vec<Unit>,
+    pub write_files: Vec<WriteFile>,
+}
+
+/// Displays the cloud-config of one of a cluster's roles with its fragment merged in.
+pub struct CloudConfigPreview<'a> {
+    cluster: &'a str,
+    role: &'a str,
+}
+
+/// A systemd drop-in for a unit.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DropIn {
+    pub content: String,
+    pub name: String,
+}
+
+/// A systemd unit, with the same settings as the units of a cloud-config.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Unit {
+    pub command: Option<String>,
+    pub content: Option<String>,
+    #[serde(default)]
+    pub drop_ins: Vec<DropIn>,
+    pub enable: Option<bool>,
+    pub mask: Option<bool>,
+    pub name: String,
+}
+
+/// A file to write, with the same settings as the `write_files` of a cloud-config.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WriteFile {
+    pub content: String,
+    pub owner: Option<String>,
+    pub path: String,
+    /// The file's mode in octal, e.g. "0644". A string, since TOML has no octal numbers.
+    pub permissions: Option<String>,
+}
+
+impl CloudConfigFragment {
+    /// Reads a role's fragment. Roles without a fragment file get an empty one.
+    pub fn load(cluster: &str, role: &str) -> Result<Self, KawsError> {
+        let path = fragment_path(cluster, role);
+
+        if !Path::new(&path).exists() {
+            return Ok(CloudConfigFragment::default());
+        }
+
+        let mut contents = String::new();
+
+        File::open(&path)?.read_to_string(&mut contents)?;
+
+        from_str(&contents).map_err(|error| KawsError::new(format!("Failed to parse {}: {}", path, error)))
+    }
+
+    /// Reads every role's fragment, checking each of them and reporting all of the problems at
+    /// once. Files in the fragments directory that don't belong to a role are reported too, since
+    /// they would otherwise be silently ignored.
+    pub fn load_all(cluster: &str) -> Result<Vec<(&'static str, Self)>, KawsError> {
+        let mut fragments = vec![];
+        let mut problems = vec![];
+
+        for &role in CLOUD_CONFIG_ROLES.iter() {
+            match CloudConfigFragment::load(cluster, role) {
+                Ok(fragment) => {
+                    for problem in fragment.problems(role) {
+                        problems.push(format!("{}: {}", fragment_path(cluster, role), problem));
+                    }
+
+                    fragments.push((role, fragment));
+                }
+                Err(error) => problems.push(error.to_string()),
+            }
+        }
+
+        if Path::new(&fragments_dir(cluster)).is_dir() {
+            for entry in read_dir(fragments_dir(cluster))? {
+                let path: PathBuf = entry?.path();
+                let known = path.extension().is_some_and(|extension| extension == "toml") &&
+                    path.file_stem().and_then(|stem| stem.to_str()).is_some_and(|stem| {
+                        CLOUD_CONFIG_ROLES.contains(&stem)
+                    });
+
+                if !known {
+                    problems.push(format!(
+                        "{}: unknown file, fragments must be named after a role: {}",
+                        path.display(),
+                        CLOUD_CONFIG_ROLES.iter().map(|role| format!("{}.toml", role))
+                            .collect::<Vec<String>>().join(", "),
+                    ));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(fragments)
+        } else {
+            Err(KawsError::new(format!(
+                "The cloud-config fragments for cluster \"{}\" are invalid:\n* {}",
+                cluster,
+                problems.join("\n* "),
+            )))
+        }
+    }
+
+    /// The SSH keys as extra items of a YAML flow sequence, each preceded by ", ", to follow the
+    /// cluster's own keys.
+    pub fn ssh_keys_yaml(&self) -> String {
+        self.ssh_authorized_keys.iter().map(|key| format!(", {}", yaml_string(key))).collect()
+    }
+
+    /// The units as extra items of the block sequence `coreos.units`, whose items are indented by
+    /// four spaces. The indentation of the first item comes from the template. The bastion has no
+    /// units of its own, so its items are preceded by the `units:` key itself.
+    pub fn units_yaml(&self, role: &str) -> String {
+        let items = self.units.iter().map(|unit| format!("- {}", unit.to_yaml()))
+            .collect::<Vec<String>>().join("\n    ");
+
+        with_key(role, "units", "\n    ", items)
+    }
+
+    /// The files as extra items of the block sequence `write_files`, whose items are indented by
+    /// two spaces. The indentation of the first item comes from the template. The bastion has no
+    /// files of its own, so its items are preceded by the `write_files:` key itself.
+    pub fn write_files_yaml(&self, role: &str) -> String {
+        let items = self.write_files.iter().map(|file| format!("- {}", file.to_yaml()))
+            .collect::<Vec<String>>().join("\n  ");
+
+        with_key(role, "write_files", "\n  ", items)
+    }
+
+    // Private
+
+    fn problems(&self, role: &str) -> Vec<String> {
+        let mut problems = vec![];
+
+        let reserved_units = reserved(RESERVED_UNITS, role);
+        let reserved_paths = reserved(RESERVED_PATHS, role);
+
+        for key in self.ssh_authorized_keys.iter() {
+            if !(key.starts_with("ssh-") || key.starts_with("ecdsa-sha2-")) || key.contains('\n') {
+                problems.push(format!("\"{}\" is not an SSH public key", key));
+            }
+        }
+
+        for (index, unit) in self.units.iter().enumerate() {
+            let valid_type = unit.name.rfind('.').is_some_and(|dot| {
+                dot > 0 && UNIT_TYPES.contains(&&unit.name[dot + 1..])
+            });
+
+            if !valid_type || unit.name.contains('/') {
+                problems.push(format!(
+                    "unit \"{}\" must be named after its type, one of {}",
+                    unit.name,
+                    UNIT_TYPES.join(", "),
+                ));
+            }
+
+            if reserved_units.contains(&unit.name.as_str()) {
+                problems.push(format!("unit {} is defined by kaws and can't be replaced", unit.name));
+            }
+
+            if self.units[..index].iter().any(|other| other.name == unit.name) {
+                problems.push(format!("unit {} is defined more than once", unit.name));
+            }
+
+            if let Some(ref command) = unit.command {
+                if !UNIT_COMMANDS.contains(&command.as_str()) {
+                    problems.push(format!(
+                        "unit {}: command must be one of {}",
+                        unit.name,
+                        UNIT_COMMANDS.join(", "),
+                    ));
+                }
+            }
+
+            for drop_in in unit.drop_ins.iter() {
+                if !drop_in.name.ends_with(".conf") || drop_in.name.contains('/') {
+                    problems.push(format!(
+                        "unit {}: drop-in \"{}\" must be a file name ending in \".conf\"",
+                        unit.name,
+                        drop_in.name,
+                    ));
+                }
+            }
+        }
+
+        for (index, file) in self.write_files.iter().enumerate() {
+            if !file.path.starts_with('/') {
+                problems.push(format!("write_files: \"{}\" must be an absolute path", file.path));
+            }
+
+            if reserved_paths.iter().any(|reserved| {
+                file.path == *reserved || (reserved.ends_with('/') && file.path.starts_with(reserved))
+            }) {
+                problems.push(format!(
+                    "write_files: {} is written by kaws and can't be replaced",
+                    file.path,
+                ));
+            }
+
+            if self.write_files[..index].iter().any(|other| other.path == file.path) {
+                problems.push(format!("write_files: {} is written more than once", file.path));
+            }
+
+            if let Some(ref permissions) = file.permissions {
+                let valid = (permissions.len() == 3 || permissions.len() == 4) &&
+                    permissions.chars().all(|c| c.is_digit(8));
+
+                if !valid {
+                    problems.push(format!(
+                        "write_files: {}: permissions must be an octal mode, e.g. \"0644\"",
+                        file.path,
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
+}
+
+impl<'a> CloudConfigPreview<'a> {
+    pub fn new(matches: &'a ArgMatches) -> Self {
+        CloudConfigPreview {
+            cluster: matches.value_of("cluster").expect("clap should have required cluster"),
+            role: matches.value_of("role").expect("clap should have required role"),
+        }
+    }
+
+    /// Prints the role's cloud-config template from the kaws Terraform module with the fragment
+    /// merged in. Values that only Terraform knows, such as the certificates, are left as
+    /// `${...}` placeholders.
+    pub fn preview(&self) -> KawsResult {
+        let config = ClusterConfig::load(self.cluster)?;
+        let fragments = CloudConfigFragment::load_all(self.cluster)?;
+        let fragment = fragments.iter().find(|&&(role, _)| role == self.role).map(|(_, fragment)| {
+            fragment
+        }).expect("every role should have a fragment");
+
+        let template_name = format!("{}_cloud_config.yml", match self.role {
+            "masters" => "master",
+            "nodes" => "node",
+            role => role,
+        });
+
+        let modules_dir = Path::new(".terraform/modules");
+
+        // The module is only downloaded if Terraform hasn't been initialized yet.
+        let template_path = match find_file(modules_dir, &template_name)? {
+            Some(template_path) => Some(template_path),
+            None => {
+                Terraform::init()?;
+
+                find_file(modules_dir, &template_name)?
+            }
+        }.ok_or_else(|| KawsError::new(format!(
+            "{} was not found in the kaws Terraform module in .terraform/modules.",
+            template_name,
+        )))?;
+
+        let mut template = String::new();
+
+        File::open(&template_path)?.read_to_string(&mut template)?;
+
+        let ssh_keys = config.ssh_keys.join(", ") + &fragment.ssh_keys_yaml();
+
+        print!(
+            "{}",
+            template
+                .replace("${extra_units}", &fragment.units_yaml(self.role))
+                .replace("${extra_write_files}", &fragment.write_files_yaml(self.role))
+                .replace("${ssh_public_keys}", &ssh_keys),
+        );
+
+        Ok(None)
+    }
+}
+
+impl Unit {
+    fn to_yaml(&self) -> String {
+        let mut fields = vec![("name", yaml_string(&self.name))];
+
+        if let Some(ref command) = self.command {
+            fields.push(("command", yaml_string(command)));
+        }
+
+        if let Some(enable) = self.enable {
+            fields.push(("enable", enable.to_string()));
+        }
+
+        if let Some(mask) = self.mask {
+            fields.push(("mask", mask.to_string()));
+        }
+
+        if !self.drop_ins.is_empty() {
+            fields.push(("drop-ins", format!("[{}]", self.drop_ins.iter().map(|drop_in| {
+                yaml_mapping(&[
+                    ("name", yaml_string(&drop_in.name)),
+                    ("content", yaml_string(&drop_in.content)),
+                ])
+            }).collect::<Vec<String>>().join(", "))));
+        }
+
+        if let Some(ref content) = self.content {
+            fields.push(("content", yaml_string(content)));
+        }
+
+        yaml_mapping(&fields)
+    }
+}
+
+impl WriteFile {
+    fn to_yaml(&self) -> String {
+        let mut fields = vec![("path", yaml_string(&self.path))];
+
+        if let Some(ref owner) = self.owner {
+            fields.push(("owner", yaml_string(owner)));
+        }
+
+        if let Some(ref permissions) = self.permissions {
+            fields.push(("permissions", yaml_string(permissions)));
+        }
+
+        fields.push(("content", yaml_string(&self.content)));
+
+        yaml_mapping(&fields)
+    }
+}
+
+fn fragments_dir(cluster: &str) -> String {
+    format!("clusters/{}/cloud-config", cluster)
+}
+
+fn fragment_path(cluster: &str, role: &str) -> String {
+    format!("{}/{}.toml", fragments_dir(cluster), role)
+}
+
+/// Searches a directory tree for a file in a `templates` directory, as Terraform downloads
+/// modules into directories named after hashes.
+fn find_file(dir: &Path, name: &str) -> Result<Option<PathBuf>, KawsError> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            if let Some(found) = find_file(&path, name)? {
+                return Ok(Some(found));
+            }
+        } else if path.file_name().is_some_and(|file_name| file_name == name) &&
+            dir.file_name().is_some_and(|dir_name| dir_name == "templates")
+        {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
+fn reserved(
+    reserved: &'static [(&'static str, &'static [&'static str])],
+    role: &str,
+) -> &'static [&'static str] {
+    reserved.iter().find(|&&(reserved_role, _)| reserved_role == role).map_or(&[], |&(_, names)| names)
+}
+
+/// Precedes the bastion's non-empty sequence items with the sequence's key, so the key is left out
+/// of its cloud-config when there are no items, rather than being null.
+fn with_key(role: &str, key: &str, indentation: &str, items: String) -> String {
+    if role == "bastion" && !items.is_empty() {
+        format!("{}:{}{}", key, indentation, items)
+    } else {
+        items
+    }
+}
+
+/// A YAML flow mapping. JSON is mostly valid YAML, but YAML 1.1 parsers need a space after each
+/// colon, so the mapping is written out by hand.
+fn yaml_mapping(fields: &[(&str, String)]) -> String {
+    format!("{{{}}}", fields.iter().map(|&(key, ref value)| format!("{}: {}", key, value))
+        .collect::<Vec<String>>().join(", "))
+}
+
+/// A YAML double-quoted string, which accepts the same escapes as JSON.
+fn yaml_string(value: &str) -> String {
+    to_json_string(value).expect("strings should always serialize to JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use toml::from_str;
+
+    use super::CloudConfigFragment;
+
+    fn problems(role: &str, fragment: &str) -> Vec<String> {
+        from_str::<CloudConfigFragment>(fragment).unwrap().problems(role)
+    }
+
+    #[test]
+    fn problems_accepts_valid_fragments() {
+        let fragment = r#"
+            ssh_authorized_keys = ["ssh-ed25519 AAAA alice"]
+
+            [[units]]
+            name = "backup.timer"
+            command = "start"
+
+            [[units.drop_ins]]
+            name = "10-schedule.conf"
+            content = "[Timer]\nOnCalendar=daily\n"
+
+            [[write_files]]
+            path = "/etc/backup.conf"
+            permissions = "0600"
+            content = "bucket = example\n"
+        "#;
+
+        assert!(problems("masters", fragment).is_empty());
+    }
+
+    #[test]
+    fn problems_rejects_units_and_files_kaws_defines() {
+        let fragment = r#"
+            [[units]]
+            name = "kubelet.service"
+
+            [[write_files]]
+            path = "/etc/kubernetes/ssl/extra.pem"
+            content = ""
+        "#;
+
+        assert_eq!(problems("nodes", fragment), vec![
+            "unit kubelet.service is defined by kaws and can't be replaced".to_owned(),
+            "write_files: /etc/kubernetes/ssl/extra.pem is written by kaws and can't be replaced"
+                .to_owned(),
+        ]);
+        assert!(problems("bastion", fragment).is_empty());
+    }
+
+    #[test]
+    fn problems_rejects_invalid_settings() {
+        let fragment = r#"
+            ssh_authorized_keys = ["not a key"]
+
+            [[units]]
+            name = "backup"
+            command = "run"
+
+            [[units]]
+            name = "backup"
+
+            [[units.drop_ins]]
+            name = "override"
+            content = ""
+
+            [[write_files]]
+            path = "etc/backup.conf"
+            permissions = "0800"
+            content = ""
+        "#;
+
+        let problems = problems("etcd", fragment);
+        let expected = [
+            "\"not a key\" is not an SSH public key",
+            "unit \"backup\" must be named after its type",
+            "unit backup: command must be one of",
+            "unit backup is defined more than once",
+            "unit backup: drop-in \"override\" must be a file name ending in \".conf\"",
+            "write_files: \"etc/backup.conf\" must be an absolute path",
+            "write_files: etc/backup.conf: permissions must be an octal mode",
+        ];
+
+        for expected in expected.iter() {
+            assert!(problems.iter().any(|problem| problem.starts_with(expected)), "{}", expected);
+        }
+    }
+}
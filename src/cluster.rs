@@ -1,21 +1,29 @@
-use std::fs::{create_dir_all, File};
-use std::io::Write;
+use std::fs::{create_dir_all, remove_file, File};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
 
 use clap::ArgMatches;
-use rusoto_core::ChainProvider;
+use hyper::Client;
 
-use aws::credentials_provider;
+use ami::AmiResolver;
+use aws::{CredentialsProvider, credentials_provider};
+use config::ClusterConfig;
 use encryption::Encryptor;
-use error::KawsResult;
-use pki::CertificateAuthority;
+use error::{KawsError, KawsResult};
+use pki::{Certificate, CertificateAuthority};
+use terraform::Outputs;
 
 pub struct Cluster<'a> {
     name: &'a str,
     region: &'a str,
 }
 
+pub struct ClusterProtection<'a> {
+    cluster: &'a str,
+}
+
 pub struct ExistingCluster<'a> {
-    aws_credentials_provider: ChainProvider,
+    aws_credentials_provider: CredentialsProvider,
     cluster: Cluster<'a>,
     domain: Option<&'a str>,
     kms_master_key_id: &'a str,
@@ -23,21 +31,49 @@ pub struct ExistingCluster<'a> {
 }
 
 pub struct NewCluster<'a> {
-    availability_zone: &'a str,
-    aws_account_id: &'a str,
-    cidr: &'a str,
+    availability_zones: Option<Vec<&'a str>>,
+    aws_account_id: Option<&'a str>,
+    balancers_cidr: Option<&'a str>,
+    bastion_instance_type: Option<&'a str>,
+    bastion_root_volume_size: Option<&'a str>,
+    bastion_root_volume_type: Option<&'a str>,
+    cidr: Option<&'a str>,
     cluster: Cluster<'a>,
-    coreos_ami: &'a str,
-    domain: &'a str,
-    iam_users: Vec<&'a str>,
-    instance_size: &'a str,
-    kubernetes_version: &'a str,
-    masters_max_size: &'a str,
-    masters_min_size: &'a str,
-    nodes_max_size: &'a str,
-    nodes_min_size: &'a str,
-    ssh_keys: Vec<&'a str>,
-    zone_id: &'a str,
+    config_file: Option<&'a str>,
+    coreos_ami: Option<&'a str>,
+    dns_service_ip: Option<&'a str>,
+    domain: Option<&'a str>,
+    etcd_cidr: Option<&'a str>,
+    etcd_instance_type: Option<&'a str>,
+    etcd_members: Option<&'a str>,
+    etcd_root_volume_size: Option<&'a str>,
+    etcd_root_volume_type: Option<&'a str>,
+    etcd_volume_size: Option<&'a str>,
+    etcd_volume_type: Option<&'a str>,
+    iam_users: Option<Vec<&'a str>>,
+    instance_size: Option<&'a str>,
+    kubernetes_version: Option<&'a str>,
+    masters_instance_type: Option<&'a str>,
+    masters_max_size: Option<&'a str>,
+    masters_min_size: Option<&'a str>,
+    masters_root_volume_size: Option<&'a str>,
+    masters_root_volume_type: Option<&'a str>,
+    nodes_instance_type: Option<&'a str>,
+    nodes_max_size: Option<&'a str>,
+    nodes_min_size: Option<&'a str>,
+    nodes_root_volume_size: Option<&'a str>,
+    nodes_root_volume_type: Option<&'a str>,
+    os_channel: Option<&'a str>,
+    os_manifest: Option<&'a str>,
+    os_version: Option<&'a str>,
+    pod_cidr: Option<&'a str>,
+    region: Option<&'a str>,
+    service_cidr: Option<&'a str>,
+    ssh_keys: Option<Vec<&'a str>>,
+    tags: Option<Vec<&'a str>>,
+    topology: Option<&'a str>,
+    vpc_cidr: Option<&'a str>,
+    zone_id: Option<&'a str>,
 }
 
 impl<'a> Cluster<'a> {
@@ -48,6 +84,104 @@ impl<'a> Cluster<'a> {
         }
     }
 
+    /// The address of the Kubernetes API service if the Kubernetes masters' certificate doesn't
+    /// include it, which happens when `service_cidr` changes. Clusters whose PKI hasn't been
+    /// generated yet have no stale certificate.
+    pub fn stale_master_cert(&self, config: &ClusterConfig) -> Result<Option<String>, KawsError> {
+        let path = self.k8s_master_cert_path();
+
+        if !Path::new(&path).exists() {
+            return Ok(None);
+        }
+
+        let cert = Certificate::from_file(&path)?;
+
+        if cert.as_bytes().is_empty() {
+            return Ok(None);
+        }
+
+        let service_ip = config.kubernetes_service_ip()?;
+
+        if cert.ip_addresses()?.contains(&service_ip) {
+            Ok(None)
+        } else {
+            Ok(Some(service_ip))
+        }
+    }
+
+    /// Regenerates the Kubernetes masters' certificate if it doesn't include the address of the
+    /// Kubernetes API service, so the certificate and the API server's service network can't
+    /// drift apart.
+    pub fn sync_master_cert(
+        &self,
+        config: &ClusterConfig,
+        credentials_provider: &CredentialsProvider,
+    ) -> KawsResult {
+        let service_ip = match self.stale_master_cert(config)? {
+            Some(service_ip) => service_ip,
+            None => return Ok(None),
+        };
+
+        let kms_key = Outputs::load(self.name).map_err(|_| KawsError::new(format!(
+            "The Kubernetes masters' certificate does not include {}, the address of the \
+            Kubernetes API service in service_cidr, and the cluster's KMS key could not be read \
+            from its Terraform outputs to regenerate it. Regenerate it with `kaws cluster \
+            generate-pki kubernetes {} masters --domain {} --region {} --kms-key KEY`.",
+            service_ip,
+            self.name,
+            config.domain,
+            self.region(),
+        )))?.pki_kms_key;
+
+        println!(
+            "Regenerating the Kubernetes masters' certificate to include {}, the address of the \
+            Kubernetes API service.",
+            service_ip,
+        );
+
+        let mut encryptor = Encryptor::new(
+            credentials_provider.clone(),
+            self.region().parse()?,
+            Some(&kms_key),
+        );
+
+        let ca = CertificateAuthority::from_files(
+            &mut encryptor,
+            &self.k8s_ca_cert_path(),
+            &self.k8s_encrypted_ca_key_path(),
+        )?;
+
+        self.generate_master_cert(&ca, &mut encryptor, &config.domain, &service_ip)
+    }
+
+    // Private
+
+    fn generate_master_cert(
+        &self,
+        ca: &CertificateAuthority,
+        encryptor: &mut Encryptor<CredentialsProvider, Client>,
+        domain: &str,
+        service_ip: &str,
+    ) -> KawsResult {
+        let (master_cert, master_key) = ca.generate_cert(
+            &format!("kaws-k8s-master-{}", self.name),
+            Some(&[
+                "kubernetes",
+                "kubernetes.default",
+                "kubernetes.default.svc",
+                "kubernetes.default.svc.cluster.local",
+                &format!("kubernetes.{}", domain),
+                service_ip,
+            ]),
+            None,
+        )?;
+
+        master_cert.write_to_file(&self.k8s_master_cert_path())?;
+        master_key.write_to_file(encryptor, &self.k8s_encrypted_master_key_path())?;
+
+        Ok(None)
+    }
+
     fn etcd_ca_cert_path(&self) -> String {
         format!("clusters/{}/etcd-ca.pem", self.name)
     }
@@ -104,16 +238,32 @@ impl<'a> Cluster<'a> {
         format!("clusters/{}/k8s-node-key-encrypted.base64", self.name)
     }
 
+    /// The etcd instances' IP addresses, for their certificates. Clusters without a `kaws.toml`
+    /// use the default etcd subnet.
+    fn etcd_ips(&self) -> Result<Vec<String>, KawsError> {
+        if ClusterConfig::exists(self.name) {
+            ClusterConfig::load(self.name)?.etcd_ips()
+        } else {
+            ClusterConfig::default().etcd_ips()
+        }
+    }
+
     fn gitignore_path(&self) -> String {
         format!("clusters/{}/.gitignore", self.name)
     }
 
-    fn k8s_master_cert_path(&self) -> String {
-        format!("clusters/{}/k8s-master.pem", self.name)
+    /// The address of the Kubernetes API service. Clusters without a `kaws.toml` use the default
+    /// service network.
+    fn kubernetes_service_ip(&self) -> Result<String, KawsError> {
+        if ClusterConfig::exists(self.name) {
+            ClusterConfig::load(self.name)?.kubernetes_service_ip()
+        } else {
+            ClusterConfig::default().kubernetes_service_ip()
+        }
     }
 
-    fn name(&self) -> &str {
-        self.name
+    fn k8s_master_cert_path(&self) -> String {
+        format!("clusters/{}/k8s-master.pem", self.name)
     }
 
     fn k8s_node_cert_path(&self) -> String {
@@ -123,9 +273,49 @@ impl<'a> Cluster<'a> {
     fn region(&self) -> &str {
         self.region
     }
+}
+
+impl<'a> ClusterProtection<'a> {
+    pub fn new(cluster: &'a str) -> Self {
+        ClusterProtection {
+            cluster: cluster,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        Path::new(&self.path()).exists()
+    }
+
+    pub fn enable(&self) -> KawsResult {
+        log_wrap!("Creating protection marker file", {
+            File::create(self.path())?;
+        });
+
+        Ok(Some(format!(
+            "Cluster \"{name}\" is now protected from `kaws cluster destroy`. Commit clusters/{name} to Git.",
+            name = self.cluster,
+        )))
+    }
+
+    pub fn disable(&self) -> KawsResult {
+        log_wrap!("Removing protection marker file", {
+            if let Err(error) = remove_file(self.path()) {
+                if error.kind() != ErrorKind::NotFound {
+                    return Err(error.into());
+                }
+            }
+        });
+
+        Ok(Some(format!(
+            "Cluster \"{name}\" is no longer protected from `kaws cluster destroy`. Commit clusters/{name} to Git.",
+            name = self.cluster,
+        )))
+    }
+
+    // Private
 
-    fn tfvars_path(&self) -> String {
-        format!("clusters/{}/terraform.tfvars", self.name)
+    fn path(&self) -> String {
+        format!("clusters/{}/protected", self.cluster)
     }
 }
 
@@ -133,6 +323,8 @@ impl<'a> ExistingCluster<'a> {
     pub fn new(matches: &'a ArgMatches) -> Self {
         ExistingCluster {
             aws_credentials_provider: credentials_provider(
+                matches.value_of("cluster"),
+                matches.value_of("region"),
                 matches.value_of("aws-credentials-path"),
                 matches.value_of("aws-credentials-profile"),
             ),
@@ -182,13 +374,11 @@ impl<'a> ExistingCluster<'a> {
         };
 
         if self.subject == "ca" || self.subject == "server" {
+            let etcd_ips = self.cluster.etcd_ips()?;
+
             let (server_cert, server_key) = ca.generate_cert(
                 &format!("kaws-etcd-server-{}", self.cluster.name),
-                Some(&[
-                    "10.0.1.4",
-                    "10.0.1.5",
-                    "10.0.1.6",
-                ]),
+                Some(&etcd_ips.iter().map(|ip| ip.as_str()).collect::<Vec<&str>>()),
                 None,
             )?;
 
@@ -243,13 +433,11 @@ impl<'a> ExistingCluster<'a> {
             )?
         };
 
+        let etcd_ips = self.cluster.etcd_ips()?;
+
         let (peer_cert, peer_key) = ca.generate_cert(
             &format!("kaws-etcd-peer-{}", self.cluster.name),
-            Some(&[
-                "10.0.1.4",
-                "10.0.1.5",
-                "10.0.1.6",
-            ]),
+            Some(&etcd_ips.iter().map(|ip| ip.as_str()).collect::<Vec<&str>>()),
             None,
         )?;
 
@@ -290,23 +478,11 @@ impl<'a> ExistingCluster<'a> {
         };
 
         if self.subject == "ca" || self.subject == "masters" {
-            let (master_cert, master_key) = ca.generate_cert(
-                &format!("kaws-k8s-master-{}", self.cluster.name),
-                Some(&[
-                    "kubernetes",
-                    "kubernetes.default",
-                    "kubernetes.default.svc",
-                    "kubernetes.default.svc.cluster.local",
-                    &format!("kubernetes.{}", self.domain.expect("missing domain")),
-                    "10.3.0.1",
-                ]),
-                None,
-            )?;
-
-            master_cert.write_to_file(&self.cluster.k8s_master_cert_path())?;
-            master_key.write_to_file(
+            self.cluster.generate_master_cert(
+                &ca,
                 &mut encryptor,
-                &self.cluster.k8s_encrypted_master_key_path(),
+                self.domain.expect("missing domain"),
+                &self.cluster.kubernetes_service_ip()?,
             )?;
         }
 
@@ -331,44 +507,64 @@ impl<'a> ExistingCluster<'a> {
 impl<'a> NewCluster<'a> {
     pub fn new(matches: &'a ArgMatches) -> Self {
         NewCluster {
-            availability_zone: matches
-                .value_of("availability-zone")
-                .expect("missing availability-zone"),
-            aws_account_id: matches.value_of("aws-account-id").expect("missing aws-account-id"),
-            cidr: matches.value_of("cidr").expect("missing cidr"),
+            availability_zones: matches.values_of("availability-zone").map(|values| values.collect()),
+            aws_account_id: matches.value_of("aws-account-id"),
+            balancers_cidr: matches.value_of("balancers-cidr"),
+            bastion_instance_type: matches.value_of("bastion-instance-type"),
+            bastion_root_volume_size: matches.value_of("bastion-root-volume-size"),
+            bastion_root_volume_type: matches.value_of("bastion-root-volume-type"),
+            cidr: matches.value_of("cidr"),
             cluster: Cluster::new(
                 matches.value_of("cluster").expect("missing cluster name"),
-                matches.value_of("region").expect("missing region"),
+                matches.value_of("region").unwrap_or(""),
             ),
-            coreos_ami: matches.value_of("ami").expect("missing ami"),
-            domain: matches.value_of("domain").expect("missing domain"),
-            iam_users: matches
-                .values_of("iam-user")
-                .expect("missing iam-users")
-                .collect(),
-            instance_size: matches.value_of("size").expect("missing instance size"),
-            kubernetes_version: matches.value_of("k8s-version").expect("missing k8s-version"),
-            masters_max_size: matches
-                .value_of("masters-max-size")
-                .expect("missing masters-max-size"),
-            masters_min_size: matches
-                .value_of("masters-min-size")
-                .expect("missing masters-min-size"),
-            nodes_max_size: matches
-                .value_of("nodes-max-size")
-                .expect("missing nodes-max-size"),
-            nodes_min_size: matches
-                .value_of("nodes-min-size")
-                .expect("missing nodes-min-size"),
-            ssh_keys: matches.values_of("ssh-key").expect("missing ssh-keys").collect(),
-            zone_id: matches.value_of("zone-id").expect("missing zone-id"),
+            config_file: matches.value_of("config"),
+            coreos_ami: matches.value_of("ami"),
+            dns_service_ip: matches.value_of("dns-service-ip"),
+            domain: matches.value_of("domain"),
+            etcd_cidr: matches.value_of("etcd-cidr"),
+            etcd_instance_type: matches.value_of("etcd-instance-type"),
+            etcd_members: matches.value_of("etcd-members"),
+            etcd_root_volume_size: matches.value_of("etcd-root-volume-size"),
+            etcd_root_volume_type: matches.value_of("etcd-root-volume-type"),
+            etcd_volume_size: matches.value_of("etcd-volume-size"),
+            etcd_volume_type: matches.value_of("etcd-volume-type"),
+            iam_users: matches.values_of("iam-user").map(|values| values.collect()),
+            instance_size: matches.value_of("size"),
+            kubernetes_version: matches.value_of("k8s-version"),
+            masters_instance_type: matches.value_of("masters-instance-type"),
+            masters_max_size: matches.value_of("masters-max-size"),
+            masters_min_size: matches.value_of("masters-min-size"),
+            masters_root_volume_size: matches.value_of("masters-root-volume-size"),
+            masters_root_volume_type: matches.value_of("masters-root-volume-type"),
+            nodes_instance_type: matches.value_of("nodes-instance-type"),
+            nodes_max_size: matches.value_of("nodes-max-size"),
+            nodes_min_size: matches.value_of("nodes-min-size"),
+            nodes_root_volume_size: matches.value_of("nodes-root-volume-size"),
+            nodes_root_volume_type: matches.value_of("nodes-root-volume-type"),
+            os_channel: matches.value_of("os-channel"),
+            os_manifest: matches.value_of("os-manifest"),
+            os_version: matches.value_of("os-version"),
+            pod_cidr: matches.value_of("pod-cidr"),
+            region: matches.value_of("region"),
+            service_cidr: matches.value_of("service-cidr"),
+            ssh_keys: matches.values_of("ssh-key").map(|values| values.collect()),
+            tags: matches.values_of("tag").map(|values| values.collect()),
+            topology: matches.value_of("topology"),
+            vpc_cidr: matches.value_of("vpc-cidr"),
+            zone_id: matches.value_of("zone-id"),
         }
     }
 
     pub fn init(&mut self) -> KawsResult {
+        let config = self.config()?;
+
+        config.validate(self.cluster.name)?;
+
         self.create_directories()?;
         self.create_gitignore()?;
-        self.create_tfvars()?;
+        config.write(self.cluster.name)?;
+        config.write_tfvars(self.cluster.name)?;
         self.create_pki_stubs()?;
 
         Ok(Some(format!(
@@ -377,6 +573,196 @@ impl<'a> NewCluster<'a> {
         )))
     }
 
+    /// Builds the cluster's configuration from the file given with `--config`, if any, with the
+    /// values of any other options taking precedence.
+    fn config(&self) -> Result<ClusterConfig, KawsError> {
+        let mut config = match self.config_file {
+            Some(path) => ClusterConfig::from_file(path)?,
+            None => ClusterConfig::default(),
+        };
+
+        if let Some(ref availability_zones) = self.availability_zones {
+            config.availability_zones = availability_zones.iter().map(|zone| zone.to_string()).collect();
+        }
+
+        if let Some(aws_account_id) = self.aws_account_id {
+            config.aws_account_id = aws_account_id.to_owned();
+        }
+
+        if let Some(balancers_cidr) = self.balancers_cidr {
+            config.balancers_cidr = balancers_cidr.to_owned();
+        }
+
+        if let Some(bastion_instance_type) = self.bastion_instance_type {
+            config.bastion.instance_type = bastion_instance_type.to_owned();
+        }
+
+        if let Some(bastion_root_volume_size) = self.bastion_root_volume_size {
+            config.bastion.root_volume_size = parse_size("bastion-root-volume-size", bastion_root_volume_size)?;
+        }
+
+        if let Some(bastion_root_volume_type) = self.bastion_root_volume_type {
+            config.bastion.root_volume_type = bastion_root_volume_type.to_owned();
+        }
+
+        if let Some(cidr) = self.cidr {
+            config.cidr = cidr.to_owned();
+        }
+
+        if let Some(coreos_ami) = self.coreos_ami {
+            // The AMI no longer comes from the channel, if the file named one.
+            config.coreos_ami = coreos_ami.to_owned();
+            config.os_channel = String::new();
+            config.os_version = String::new();
+        }
+
+        if let Some(dns_service_ip) = self.dns_service_ip {
+            config.dns_service_ip = dns_service_ip.to_owned();
+        }
+
+        if let Some(domain) = self.domain {
+            config.domain = domain.to_owned();
+        }
+
+        if let Some(etcd_cidr) = self.etcd_cidr {
+            config.etcd_cidr = etcd_cidr.to_owned();
+        }
+
+        if let Some(etcd_instance_type) = self.etcd_instance_type {
+            config.etcd.instance_type = etcd_instance_type.to_owned();
+        }
+
+        if let Some(etcd_members) = self.etcd_members {
+            config.etcd.members = parse_size("etcd-members", etcd_members)?;
+        }
+
+        if let Some(etcd_root_volume_size) = self.etcd_root_volume_size {
+            config.etcd.root_volume_size = parse_size("etcd-root-volume-size", etcd_root_volume_size)?;
+        }
+
+        if let Some(etcd_root_volume_type) = self.etcd_root_volume_type {
+            config.etcd.root_volume_type = etcd_root_volume_type.to_owned();
+        }
+
+        if let Some(etcd_volume_size) = self.etcd_volume_size {
+            config.etcd.volume_size = parse_size("etcd-volume-size", etcd_volume_size)?;
+        }
+
+        if let Some(etcd_volume_type) = self.etcd_volume_type {
+            config.etcd.volume_type = etcd_volume_type.to_owned();
+        }
+
+        if let Some(ref iam_users) = self.iam_users {
+            config.iam_users = iam_users.iter().map(|iam_user| iam_user.to_string()).collect();
+        }
+
+        if let Some(instance_size) = self.instance_size {
+            config.instance_size = instance_size.to_owned();
+        }
+
+        if let Some(kubernetes_version) = self.kubernetes_version {
+            config.kubernetes_version = kubernetes_version.to_owned();
+        }
+
+        if let Some(masters_instance_type) = self.masters_instance_type {
+            config.masters.instance_type = masters_instance_type.to_owned();
+        }
+
+        if let Some(masters_max_size) = self.masters_max_size {
+            config.masters.max_size = parse_size("masters-max-size", masters_max_size)?;
+        }
+
+        if let Some(masters_min_size) = self.masters_min_size {
+            config.masters.min_size = parse_size("masters-min-size", masters_min_size)?;
+        }
+
+        if let Some(masters_root_volume_size) = self.masters_root_volume_size {
+            config.masters.root_volume_size = parse_size("masters-root-volume-size", masters_root_volume_size)?;
+        }
+
+        if let Some(masters_root_volume_type) = self.masters_root_volume_type {
+            config.masters.root_volume_type = masters_root_volume_type.to_owned();
+        }
+
+        if let Some(nodes_instance_type) = self.nodes_instance_type {
+            config.nodes.instance_type = nodes_instance_type.to_owned();
+        }
+
+        if let Some(nodes_max_size) = self.nodes_max_size {
+            config.nodes.max_size = parse_size("nodes-max-size", nodes_max_size)?;
+        }
+
+        if let Some(nodes_min_size) = self.nodes_min_size {
+            config.nodes.min_size = parse_size("nodes-min-size", nodes_min_size)?;
+        }
+
+        if let Some(nodes_root_volume_size) = self.nodes_root_volume_size {
+            config.nodes.root_volume_size = parse_size("nodes-root-volume-size", nodes_root_volume_size)?;
+        }
+
+        if let Some(nodes_root_volume_type) = self.nodes_root_volume_type {
+            config.nodes.root_volume_type = nodes_root_volume_type.to_owned();
+        }
+
+        if let Some(pod_cidr) = self.pod_cidr {
+            config.pod_cidr = pod_cidr.to_owned();
+        }
+
+        if let Some(region) = self.region {
+            config.region = region.to_owned();
+        }
+
+        if let Some(service_cidr) = self.service_cidr {
+            config.service_cidr = service_cidr.to_owned();
+        }
+
+        if let Some(ref ssh_keys) = self.ssh_keys {
+            config.ssh_keys = ssh_keys.iter().map(|ssh_key| ssh_key.to_string()).collect();
+        }
+
+        if let Some(ref tags) = self.tags {
+            config.tags = tags.iter().filter_map(|tag| {
+                tag.find('=').map(|index| (tag[..index].to_owned(), tag[index + 1..].to_owned()))
+            }).collect();
+        }
+
+        if let Some(topology) = self.topology {
+            config.topology = topology.to_owned();
+        }
+
+        if let Some(vpc_cidr) = self.vpc_cidr {
+            config.vpc_cidr = vpc_cidr.to_owned();
+        }
+
+        if let Some(zone_id) = self.zone_id {
+            config.zone_id = zone_id.to_owned();
+        }
+
+        if let Some(os_channel) = self.os_channel {
+            config.os_channel = os_channel.to_owned();
+            config.os_version = self.os_version.unwrap_or("").to_owned();
+        }
+
+        // The AMI is found once every other option is applied, since it depends on the region.
+        if self.os_channel.is_some() || (config.coreos_ami.is_empty() && !config.os_channel.is_empty()) {
+            let release = {
+                let os_version = if config.os_version.is_empty() {
+                    None
+                } else {
+                    Some(config.os_version.as_str())
+                };
+
+                AmiResolver::new(&config.os_channel, os_version, self.os_manifest)
+                    .resolve(&config.region)?
+            };
+
+            config.coreos_ami = release.ami;
+            config.os_version = release.version;
+        }
+
+        Ok(config)
+    }
+
     fn create_directories(&self) -> KawsResult {
         log_wrap!("Creating directories for the new cluster", {
             create_dir_all(format!("clusters/{}", self.cluster.name))?;
@@ -387,60 +773,9 @@ impl<'a> NewCluster<'a> {
 
     fn create_gitignore(&self) -> KawsResult {
         log_wrap!("Creating .gitignore file", {
-            let mut file = File::create(&self.cluster.gitignore_path())?;
-
-            write!(file, "*-key.pem")?;
-        });
-
-        Ok(None)
-    }
+            let mut file = File::create(self.cluster.gitignore_path())?;
 
-    fn create_tfvars(&self) -> KawsResult {
-        log_wrap!("Creating tfvars file", {
-            let mut file = File::create(&self.cluster.tfvars_path())?;
-
-            write!(
-                file,
-                "\
-kaws_account_id = \"{}\"
-kaws_availability_zone = \"{}\"
-kaws_cidr = \"{}\"
-kaws_cluster = \"{}\"
-kaws_coreos_ami = \"{}\"
-kaws_domain = \"{}\"
-kaws_iam_users = [{}]
-kaws_instance_size = \"{}\"
-kaws_masters_max_size = \"{}\"
-kaws_masters_min_size = \"{}\"
-kaws_nodes_max_size = \"{}\"
-kaws_nodes_min_size = \"{}\"
-kaws_propagating_vgws = []
-kaws_region = \"{}\"
-kaws_ssh_keys = [{}]
-kaws_version = \"{}\"
-kaws_zone_id = \"{}\"
-",
-                self.aws_account_id,
-                self.availability_zone,
-                self.cidr,
-                self.cluster.name(),
-                self.coreos_ami,
-                self.domain,
-                self.iam_users.iter().map(|iam_user| {
-                    format!("\"{}\"", iam_user)
-                }).collect::<Vec<String>>().join(", "),
-                self.instance_size,
-                self.masters_max_size,
-                self.masters_min_size,
-                self.nodes_max_size,
-                self.nodes_min_size,
-                self.cluster.region(),
-                self.ssh_keys.iter().map(|ssh_key| {
-                    format!("\"{}\"", ssh_key)
-                }).collect::<Vec<String>>().join(", "),
-                self.kubernetes_version,
-                self.zone_id,
-            )?;
+            write!(file, "*-key.pem\nbackups/\n")?;
         });
 
         Ok(None)
@@ -488,3 +823,9 @@ kaws_zone_id = \"{}\"
         Ok(None)
     }
 }
+
+pub fn parse_size(option: &str, value: &str) -> Result<u32, KawsError> {
+    value.parse().map_err(|_| {
+        KawsError::new(format!("--{} must be a non-negative integer.", option))
+    })
+}
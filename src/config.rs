@@ -0,0 +1,2180 @@
+use std::cmp::{Ordering, max};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::net::Ipv4Addr;
+use std::path::Path;
+
+use bitstring::BitString;
+use cidr::{Cidr, Ipv4Cidr};
+use serde_json::to_string as to_json_string;
+use toml::{Value, from_str};
+
+use ami::{OS_CHANNELS, validate_os_version};
+use cloudconfig::CloudConfigFragment;
+use error::{KawsError, KawsResult};
+use version::KubernetesVersion;
+
+/// The settings for a cluster, stored in `clusters/CLUSTER/kaws.toml`.
+///
+/// This file is the source of truth for a cluster's configuration. `terraform.tfvars` is rendered
+/// from it before every command that runs Terraform, so it should not be edited by hand.
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct ClusterConfig {
+    /// Replaced by `availability_zones`, but still read from files written by older versions.
+    #[serde(skip_serializing)]
+    availability_zone: Option<String>,
+    pub availability_zones: Vec<String>,
+    pub aws_account_id: String,
+    pub balancers_cidr: String,
+    pub bastion: BastionConfig,
+    pub cidr: String,
+    pub coreos_ami: String,
+    pub dns_service_ip: String,
+    pub domain: String,
+    pub etcd: EtcdConfig,
+    pub etcd_cidr: String,
+    pub iam_users: Vec<String>,
+    pub instance_size: String,
+    pub kubernetes_version: String,
+    pub masters: GroupConfig,
+    pub node_pools: Vec<NodePoolConfig>,
+    pub nodes: GroupConfig,
+    pub os_channel: String,
+    pub os_version: String,
+    pub pod_cidr: String,
+    pub propagating_vgws: Vec<String>,
+    pub region: String,
+    pub service_cidr: String,
+    pub ssh_keys: Vec<String>,
+    pub state_history: StateHistoryConfig,
+    pub tags: BTreeMap<String, String>,
+    pub topology: String,
+    pub vpc_cidr: String,
+    pub zone_id: String,
+}
+
+/// The settings for the bastion instance.
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct BastionConfig {
+    pub instance_type: String,
+    pub root_volume_size: u32,
+    pub root_volume_type: String,
+}
+
+/// The settings for the etcd cluster.
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct EtcdConfig {
+    /// Falls back to the cluster's `instance_size` if empty.
+    pub instance_type: String,
+    pub members: u32,
+    pub root_volume_size: u32,
+    pub root_volume_type: String,
+    pub volume_size: u32,
+    pub volume_type: String,
+}
+
+/// The settings for the autoscaling group of Kubernetes masters or nodes.
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct GroupConfig {
+    /// Falls back to the cluster's `instance_size` if empty.
+    pub instance_type: String,
+    pub max_size: u32,
+    pub min_size: u32,
+    pub root_volume_size: u32,
+    pub root_volume_type: String,
+}
+
+/// The settings for an additional pool of Kubernetes nodes, which has its own autoscaling group.
+/// Pools are stored as `[[node_pools]]` tables at the end of `kaws.toml`.
+///
+/// A spot pool has an autoscaling group of spot instances for each of its spot instance types, and
+/// unless `on_demand_fallback` is false, an autoscaling group of on-demand instances of
+/// `instance_type` that can be scaled up when spot capacity isn't available.
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct NodePoolConfig {
+    pub instance_type: String,
+    /// Kubernetes labels for the pool's nodes, as `key=value`.
+    pub labels: Vec<String>,
+    pub max_size: u32,
+    pub min_size: u32,
+    pub name: String,
+    pub on_demand_fallback: bool,
+    pub spot: bool,
+    /// Falls back to `instance_type` if empty.
+    pub spot_instance_types: Vec<String>,
+    /// The maximum hourly price in US dollars to pay for each spot instance, e.g. "0.05".
+    pub spot_max_price: String,
+    /// Kubernetes taints for the pool's nodes, as `key=value:Effect` or `key:Effect`.
+    pub taints: Vec<String>,
+}
+
+/// How many snapshots of the Terraform state kaws keeps for the cluster, and whether they are
+/// encrypted with the cluster's KMS key. Every command that changes the state uses the same policy.
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct StateHistoryConfig {
+    pub encrypt: bool,
+    pub limit: u32,
+}
+
+/// One of the autoscaling groups that make up the node pools, as rendered into
+/// `terraform.tfvars`.
+struct NodeGroup {
+    instance_type: String,
+    labels: Vec<String>,
+    max_size: u32,
+    min_size: u32,
+    name: String,
+    /// Empty for on-demand instances.
+    spot_price: String,
+    taints: Vec<String>,
+}
+
+/// The numbers of etcd instances a cluster can have. An odd number of members tolerates as many
+/// failures as the next even number, and the Terraform module defines at most five instances.
+const ETCD_MEMBERS: &'static [u32] = &[1, 3, 5];
+
+/// The number of etcd instances in clusters created before it was configurable.
+const DEFAULT_ETCD_MEMBERS: u32 = 3;
+
+/// The instance types and volumes used by clusters created before they were configurable.
+const DEFAULT_BASTION_INSTANCE_TYPE: &'static str = "t2.micro";
+const DEFAULT_ETCD_VOLUME_SIZE: u32 = 10;
+const DEFAULT_GROUP_ROOT_VOLUME_SIZE: u32 = 30;
+const DEFAULT_ROOT_VOLUME_SIZE: u32 = 8;
+const DEFAULT_STATE_HISTORY_LIMIT: u32 = 20;
+const DEFAULT_VOLUME_TYPE: &'static str = "gp2";
+
+/// The EBS volume types that can be chosen for instances' volumes. Provisioned IOPS volumes need
+/// settings kaws doesn't manage, and throughput optimized and cold HDD volumes can't be root
+/// volumes.
+pub const VOLUME_TYPES: &'static [&'static str] = &["gp2", "standard"];
+
+/// The size limits of EBS volumes in GiB. Root volumes must be at least as large as the CoreOS
+/// AMI's snapshot.
+const MAX_VOLUME_SIZE: u32 = 16384;
+const MIN_ROOT_VOLUME_SIZE: u32 = 8;
+
+/// The EC2 instance families that can run CoreOS, with the sizes each comes in.
+const INSTANCE_TYPES: &'static [(&'static str, &'static [&'static str])] = &[
+    ("c3", &["large", "xlarge", "2xlarge", "4xlarge", "8xlarge"]),
+    ("c4", &["large", "xlarge", "2xlarge", "4xlarge", "8xlarge"]),
+    ("c5", &["large", "xlarge", "2xlarge", "4xlarge", "9xlarge", "18xlarge"]),
+    ("d2", &["xlarge", "2xlarge", "4xlarge", "8xlarge"]),
+    ("g2", &["2xlarge", "8xlarge"]),
+    ("g3", &["4xlarge", "8xlarge", "16xlarge"]),
+    ("i2", &["xlarge", "2xlarge", "4xlarge", "8xlarge"]),
+    ("i3", &["large", "xlarge", "2xlarge", "4xlarge", "8xlarge", "16xlarge"]),
+    ("m3", &["medium", "large", "xlarge", "2xlarge"]),
+    ("m4", &["large", "xlarge", "2xlarge", "4xlarge", "10xlarge", "16xlarge"]),
+    ("m5", &["large", "xlarge", "2xlarge", "4xlarge", "12xlarge", "24xlarge"]),
+    ("p2", &["xlarge", "8xlarge", "16xlarge"]),
+    ("p3", &["2xlarge", "8xlarge", "16xlarge"]),
+    ("r3", &["large", "xlarge", "2xlarge", "4xlarge", "8xlarge"]),
+    ("r4", &["large", "xlarge", "2xlarge", "4xlarge", "8xlarge", "16xlarge"]),
+    ("t2", &["nano", "micro", "small", "medium", "large", "xlarge", "2xlarge"]),
+    ("x1", &["16xlarge", "32xlarge"]),
+    ("x1e", &["xlarge", "2xlarge", "4xlarge", "8xlarge", "16xlarge", "32xlarge"]),
+];
+
+/// The label kaws gives every node with the name of its node pool. The nodes in the `[nodes]` group
+/// are in the pool "default".
+pub const NODE_POOL_LABEL: &'static str = "kaws/node-pool";
+const DEFAULT_NODE_POOL: &'static str = "default";
+
+/// Labels and taints with this prefix are reserved for the ones kaws sets itself.
+const RESERVED_PREFIX: &'static str = "kaws/";
+
+/// The label kaws gives the nodes of spot pools, with the value "spot" or "on-demand", and the
+/// taint it gives them so that only pods that tolerate losing their nodes are scheduled there.
+pub const LIFECYCLE_LABEL: &'static str = "kaws/lifecycle";
+pub const SPOT_TAINT: &'static str = "kaws/spot=true:NoSchedule";
+
+/// The tags kaws gives every AWS resource itself.
+const RESERVED_TAGS: &'static [&'static str] = &["KubernetesCluster", "Name"];
+
+/// AWS's limits on tags. A resource can have at most 50 tags, two of which are kaws's own, and tags
+/// are limited to the characters every AWS service accepts.
+const MAX_TAGS: usize = 48;
+const MAX_TAG_KEY_LENGTH: usize = 128;
+const MAX_TAG_VALUE_LENGTH: usize = 256;
+const TAG_PUNCTUATION: &'static str = " +-=._:/@";
+
+/// The effects a Kubernetes taint can have.
+const TAINT_EFFECTS: &'static [&'static str] = &["NoExecute", "NoSchedule", "PreferNoSchedule"];
+
+/// The ways a cluster's servers can be connected to the Internet. In the public topology, every
+/// server has a public IP address. In the private topology, only the ELBs and the bastion are in
+/// public subnets, and the other servers reach the Internet through a NAT gateway in each
+/// Availability Zone.
+pub const TOPOLOGIES: &'static [&'static str] = &["private", "public"];
+const DEFAULT_TOPOLOGY: &'static str = "public";
+
+/// The largest prefix length of the balancers' subnets in the private topology. ELBs need a /27
+/// subnet with at least eight free addresses, which the NAT gateway and bastion share.
+const MAX_PRIVATE_BALANCERS_PREFIX: u8 = 27;
+
+/// The network ranges used by clusters created before they were configurable.
+const DEFAULT_BALANCERS_CIDR: &'static str = "10.0.0.0/24";
+const DEFAULT_ETCD_CIDR: &'static str = "10.0.1.0/24";
+const DEFAULT_VPC_CIDR: &'static str = "10.0.0.0/16";
+const DEFAULT_POD_CIDR: &'static str = "10.2.0.0/16";
+const DEFAULT_SERVICE_CIDR: &'static str = "10.3.0.0/24";
+const DEFAULT_DNS_SERVICE_IP: &'static str = "10.3.0.10";
+
+/// The largest prefix length of the pod network. flannel gives each server a /24 from it.
+const MAX_POD_PREFIX: u8 = 23;
+
+/// The range of prefix lengths of the service network. The API server refuses ranges larger than
+/// a /12.
+const MIN_SERVICE_PREFIX: u8 = 12;
+const MAX_SERVICE_PREFIX: u8 = 28;
+
+/// The variables in the `terraform.tfvars` files written by kaws.
+const TFVARS: &'static [&'static str] = &[
+    "kaws_account_id",
+    "kaws_asg_tags",
+    "kaws_availability_zone",
+    "kaws_availability_zones",
+    "kaws_balancers_cidr",
+    "kaws_balancers_subnets",
+    "kaws_bastion_instance_type",
+    "kaws_bastion_root_volume_size",
+    "kaws_bastion_root_volume_type",
+    "kaws_cidr",
+    "kaws_cloud_config_ssh_keys",
+    "kaws_cloud_config_units",
+    "kaws_cloud_config_write_files",
+    "kaws_cluster",
+    "kaws_coreos_ami",
+    "kaws_dns_service_ip",
+    "kaws_domain",
+    "kaws_etcd_cidr",
+    "kaws_etcd_instance_type",
+    "kaws_etcd_ips",
+    "kaws_etcd_root_volume_size",
+    "kaws_etcd_root_volume_type",
+    "kaws_etcd_subnets",
+    "kaws_etcd_volume_size",
+    "kaws_etcd_volume_type",
+    "kaws_iam_users",
+    "kaws_instance_size",
+    "kaws_k8s_subnets",
+    "kaws_kubelet_api_servers",
+    "kaws_masters_instance_type",
+    "kaws_masters_max_size",
+    "kaws_masters_min_size",
+    "kaws_masters_root_volume_size",
+    "kaws_masters_root_volume_type",
+    "kaws_node_pool_instance_types",
+    "kaws_node_pool_labels",
+    "kaws_node_pool_max_sizes",
+    "kaws_node_pool_min_sizes",
+    "kaws_node_pool_spot_prices",
+    "kaws_node_pool_taints",
+    "kaws_node_pools",
+    "kaws_nodes_instance_type",
+    "kaws_nodes_max_size",
+    "kaws_nodes_min_size",
+    "kaws_nodes_root_volume_size",
+    "kaws_nodes_root_volume_type",
+    "kaws_pod_cidr",
+    "kaws_propagating_vgws",
+    "kaws_region",
+    "kaws_service_cidr",
+    "kaws_ssh_keys",
+    "kaws_tags",
+    "kaws_topology",
+    "kaws_version",
+    "kaws_vpc_cidr",
+    "kaws_zone_id",
+];
+
+impl ClusterConfig {
+    /// Whether the cluster has a `kaws.toml`. Clusters created by older versions of kaws only have
+    /// a `terraform.tfvars` until they are migrated with `kaws cluster migrate-config`.
+    pub fn exists(cluster: &str) -> bool {
+        Path::new(&config_path(cluster)).exists()
+    }
+
+    /// The number of autoscaling groups of all the node pools together.
+    pub fn node_pool_group_count(&self) -> usize {
+        self.node_pools.iter().map(|node_pool| node_pool.groups().len()).sum()
+    }
+
+    /// The indexes of a node pool's autoscaling groups among the groups of all the pools, which
+    /// are their indexes in `aws_autoscaling_group.k8s_node_pools`.
+    pub fn node_pool_group_indexes(&self, pool: &str) -> Vec<usize> {
+        self.node_pool_group_names().into_iter().enumerate().filter(|&(_, (name, _))| {
+            name == pool
+        }).map(|(index, _)| index).collect()
+    }
+
+    /// The names of the autoscaling groups of all the pools, in the order of
+    /// `aws_autoscaling_group.k8s_node_pools`, each with the name of its pool. A group is named
+    /// after its pool, with the instance type appended for groups of spot instances.
+    pub fn node_pool_group_names(&self) -> Vec<(&str, String)> {
+        self.node_pools.iter().flat_map(|node_pool| {
+            node_pool.groups().into_iter().map(move |group| (node_pool.name.as_str(), group.name))
+        }).collect()
+    }
+
+    /// The IP address of the Kubernetes API service, which Kubernetes gives the first address in
+    /// the service network. The masters' certificate must include it.
+    pub fn kubernetes_service_ip(&self) -> Result<String, KawsError> {
+        let service_cidr = parse_cidr(&self.service_cidr).map_err(|error| {
+            KawsError::new(format!("service_cidr: {}", error))
+        })?;
+
+        Ok(Ipv4Addr::from(u32::from(service_cidr.first_address()) + 1).to_string())
+    }
+
+    /// The private IP addresses of the etcd instances. The instances are spread across the etcd
+    /// subnets in each availability zone in turn, and use fixed addresses from the fifth address of
+    /// their subnet onwards, since AWS reserves the first four addresses in every subnet.
+    pub fn etcd_ips(&self) -> Result<Vec<String>, KawsError> {
+        let subnets = self.subnets("etcd_cidr", &self.etcd_cidr)?;
+
+        Ok((0..self.etcd.members as usize).map(|index| {
+            let subnet = &subnets[index % subnets.len()];
+            let offset = 4 + (index / subnets.len()) as u32;
+
+            Ipv4Addr::from(u32::from(subnet.first_address()) + offset).to_string()
+        }).collect())
+    }
+
+    pub fn load(cluster: &str) -> Result<Self, KawsError> {
+        if !ClusterConfig::exists(cluster) {
+            return Err(KawsError::new(format!(
+                "{} does not exist. If the cluster was created by an older version of kaws, \
+                run `kaws cluster migrate-config {}` to create it.",
+                config_path(cluster),
+                cluster,
+            )));
+        }
+
+        ClusterConfig::from_file(&config_path(cluster))
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, KawsError> {
+        let mut contents = String::new();
+
+        File::open(path)?.read_to_string(&mut contents)?;
+
+        ClusterConfig::parse(&contents, path)
+    }
+
+    /// Parses the contents of a `kaws.toml` file, which was read from `path`.
+    pub fn parse(contents: &str, path: &str) -> Result<Self, KawsError> {
+        let mut config: ClusterConfig = from_str(contents).map_err(|error| {
+            KawsError::new(format!("Failed to parse {}: {}", path, error))
+        })?;
+
+        if config.availability_zones.is_empty() {
+            if let Some(availability_zone) = config.availability_zone.take() {
+                config.availability_zones.push(availability_zone);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Reads the settings from a `terraform.tfvars` file written by an older version of kaws.
+    pub fn from_tfvars(cluster: &str) -> Result<Self, KawsError> {
+        if !Path::new(&tfvars_path(cluster)).exists() {
+            return Err(KawsError::new(format!("{} does not exist.", tfvars_path(cluster))));
+        }
+
+        let mut contents = String::new();
+
+        File::open(tfvars_path(cluster))?.read_to_string(&mut contents)?;
+
+        ClusterConfig::parse_tfvars(cluster, &contents)
+    }
+
+    /// Reads the settings in the contents of a cluster's `terraform.tfvars`.
+    fn parse_tfvars(cluster: &str, contents: &str) -> Result<Self, KawsError> {
+        // The tfvars files kaws wrote are a subset of HCL that is also valid TOML: one
+        // `key = "string"`, `key = ["list", "of", "strings"]`, or `key = { map = "of strings" }`
+        // assignment per line.
+        let values: Value = from_str(contents).map_err(|error| {
+            KawsError::new(format!("Failed to parse {}: {}", tfvars_path(cluster), error))
+        })?;
+
+        let unknown: Vec<&str> = values.as_table().map_or(vec![], |table| {
+            table.keys().map(|key| key.as_str()).filter(|key| !TFVARS.contains(key)).collect()
+        });
+
+        if !unknown.is_empty() {
+            return Err(KawsError::new(format!(
+                "{} contains variables that kaws does not manage: {}. Move them elsewhere first, \
+                since terraform.tfvars will be generated from kaws.toml from now on.",
+                tfvars_path(cluster),
+                unknown.join(", "),
+            )));
+        }
+
+        let string = |key: &str| -> String {
+            values.get(key).and_then(|value| value.as_str()).unwrap_or("").to_owned()
+        };
+
+        let list = |key: &str| -> Vec<String> {
+            values.get(key).and_then(|value| value.as_array()).map_or(vec![], |values| {
+                values.iter().filter_map(|value| value.as_str()).map(|value| {
+                    value.to_owned()
+                }).collect()
+            })
+        };
+
+        let number = |key: &str| -> u32 { string(key).parse().unwrap_or(0) };
+
+        let number_or = |key: &str, default: u32| -> u32 {
+            values.get(key).and_then(|value| value.as_str()).map_or(default, |value| {
+                value.parse().unwrap_or(0)
+            })
+        };
+
+        let string_or = |key: &str, default: &str| -> String {
+            values.get(key).and_then(|value| value.as_str()).unwrap_or(default).to_owned()
+        };
+
+        // Older versions of kaws wrote each network range, and newer ones write the subnets they
+        // divide it into for the availability zones.
+        let network = |key: &str, subnets_key: &str, default: &str| -> String {
+            values.get(key).and_then(|value| value.as_str()).map(|value| value.to_owned())
+                .or_else(|| uncarve(&list(subnets_key)))
+                .unwrap_or_else(|| default.to_owned())
+        };
+
+        let node_pool_instance_types = list("kaws_node_pool_instance_types");
+        let node_pool_labels = list("kaws_node_pool_labels");
+        let node_pool_max_sizes = list("kaws_node_pool_max_sizes");
+        let node_pool_min_sizes = list("kaws_node_pool_min_sizes");
+        let node_pool_spot_prices = list("kaws_node_pool_spot_prices");
+        let node_pool_taints = list("kaws_node_pool_taints");
+
+        let item = |values: &[String], index: usize| -> String {
+            values.get(index).cloned().unwrap_or_default()
+        };
+
+        let unreserved = |values: String| -> Vec<String> {
+            split_list(&values).into_iter().filter(|value| !value.starts_with(RESERVED_PREFIX)).collect()
+        };
+
+        // Each autoscaling group is named after its pool, with the instance type appended for the
+        // groups of spot instances.
+        let mut node_pools: Vec<NodePoolConfig> = vec![];
+        let mut on_demand_pools = vec![];
+
+        for (index, group) in list("kaws_node_pools").into_iter().enumerate() {
+            let (name, spot_instance_type) = match group.find('.') {
+                Some(dot) => (group[..dot].to_owned(), Some(group[dot + 1..].to_owned())),
+                None => (group.clone(), None),
+            };
+
+            if !node_pools.iter().any(|pool| pool.name == name) {
+                node_pools.push(NodePoolConfig {
+                    labels: unreserved(item(&node_pool_labels, index)),
+                    name: name.clone(),
+                    taints: unreserved(item(&node_pool_taints, index)),
+                    ..NodePoolConfig::default()
+                });
+            }
+
+            let pool = node_pools.iter_mut().find(|pool| pool.name == name).expect(
+                "node pool should have been added",
+            );
+
+            let max_size = item(&node_pool_max_sizes, index).parse().unwrap_or(0);
+            let min_size = item(&node_pool_min_sizes, index).parse().unwrap_or(0);
+
+            match spot_instance_type {
+                Some(spot_instance_type) => {
+                    pool.max_size += max_size;
+                    pool.min_size += min_size;
+                    pool.spot = true;
+                    pool.spot_instance_types.push(spot_instance_type);
+                    pool.spot_max_price = item(&node_pool_spot_prices, index);
+                }
+                None => {
+                    pool.instance_type = item(&node_pool_instance_types, index);
+                    pool.max_size = max_size;
+
+                    if !pool.spot {
+                        pool.min_size = min_size;
+                    }
+
+                    on_demand_pools.push(name);
+                }
+            }
+        }
+
+        for pool in node_pools.iter_mut() {
+            if pool.spot && !on_demand_pools.contains(&pool.name) {
+                pool.instance_type = pool.spot_instance_types[0].clone();
+                pool.on_demand_fallback = false;
+            }
+        }
+
+        Ok(ClusterConfig {
+            availability_zone: None,
+            availability_zones: match list("kaws_availability_zones") {
+                ref zones if zones.is_empty() => vec![string("kaws_availability_zone")],
+                zones => zones,
+            },
+            aws_account_id: string("kaws_account_id"),
+            balancers_cidr: network("kaws_balancers_cidr", "kaws_balancers_subnets", DEFAULT_BALANCERS_CIDR),
+            bastion: BastionConfig {
+                instance_type: string_or("kaws_bastion_instance_type", DEFAULT_BASTION_INSTANCE_TYPE),
+                root_volume_size: number_or("kaws_bastion_root_volume_size", DEFAULT_ROOT_VOLUME_SIZE),
+                root_volume_type: string_or("kaws_bastion_root_volume_type", DEFAULT_VOLUME_TYPE),
+            },
+            cidr: network("kaws_cidr", "kaws_k8s_subnets", ""),
+            coreos_ami: string("kaws_coreos_ami"),
+            dns_service_ip: string_or("kaws_dns_service_ip", DEFAULT_DNS_SERVICE_IP),
+            domain: string("kaws_domain"),
+            etcd: EtcdConfig {
+                instance_type: string("kaws_etcd_instance_type"),
+                members: match list("kaws_etcd_ips") {
+                    ref ips if ips.is_empty() => DEFAULT_ETCD_MEMBERS,
+                    ips => ips.len() as u32,
+                },
+                root_volume_size: number_or("kaws_etcd_root_volume_size", DEFAULT_ROOT_VOLUME_SIZE),
+                root_volume_type: string_or("kaws_etcd_root_volume_type", DEFAULT_VOLUME_TYPE),
+                volume_size: number_or("kaws_etcd_volume_size", DEFAULT_ETCD_VOLUME_SIZE),
+                volume_type: string_or("kaws_etcd_volume_type", DEFAULT_VOLUME_TYPE),
+            },
+            etcd_cidr: network("kaws_etcd_cidr", "kaws_etcd_subnets", DEFAULT_ETCD_CIDR),
+            iam_users: list("kaws_iam_users"),
+            instance_size: string("kaws_instance_size"),
+            kubernetes_version: string("kaws_version"),
+            masters: GroupConfig {
+                instance_type: string("kaws_masters_instance_type"),
+                max_size: number("kaws_masters_max_size"),
+                min_size: number("kaws_masters_min_size"),
+                root_volume_size: number_or("kaws_masters_root_volume_size", DEFAULT_GROUP_ROOT_VOLUME_SIZE),
+                root_volume_type: string_or("kaws_masters_root_volume_type", DEFAULT_VOLUME_TYPE),
+            },
+            node_pools: node_pools,
+            nodes: GroupConfig {
+                instance_type: string("kaws_nodes_instance_type"),
+                max_size: number("kaws_nodes_max_size"),
+                min_size: number("kaws_nodes_min_size"),
+                root_volume_size: number_or("kaws_nodes_root_volume_size", DEFAULT_GROUP_ROOT_VOLUME_SIZE),
+                root_volume_type: string_or("kaws_nodes_root_volume_type", DEFAULT_VOLUME_TYPE),
+            },
+            os_channel: String::new(),
+            os_version: String::new(),
+            pod_cidr: string_or("kaws_pod_cidr", DEFAULT_POD_CIDR),
+            propagating_vgws: list("kaws_propagating_vgws"),
+            region: string("kaws_region"),
+            service_cidr: string_or("kaws_service_cidr", DEFAULT_SERVICE_CIDR),
+            ssh_keys: list("kaws_ssh_keys"),
+            state_history: StateHistoryConfig::default(),
+            tags: values.get("kaws_tags").and_then(|value| value.as_table()).map_or(BTreeMap::new(), |tags| {
+                tags.iter().filter_map(|(key, value)| {
+                    value.as_str().map(|value| (key.clone(), value.to_owned()))
+                }).collect()
+            }),
+            topology: string_or("kaws_topology", DEFAULT_TOPOLOGY),
+            vpc_cidr: string_or("kaws_vpc_cidr", DEFAULT_VPC_CIDR),
+            zone_id: string("kaws_zone_id"),
+        })
+    }
+
+    /// Creates `kaws.toml` for a cluster created by an older version of kaws from its
+    /// `terraform.tfvars`, which is then regenerated.
+    pub fn migrate(cluster: &str) -> KawsResult {
+        if ClusterConfig::exists(cluster) {
+            return Err(KawsError::new(format!("{} already exists.", config_path(cluster))));
+        }
+
+        let config = ClusterConfig::from_tfvars(cluster)?;
+
+        config.validate(cluster)?;
+        config.write(cluster)?;
+        config.write_tfvars(cluster)?;
+
+        Ok(Some(format!(
+            "Created {} from terraform.tfvars. Commit clusters/{} to Git.",
+            config_path(cluster),
+            cluster,
+        )))
+    }
+
+    /// Renders the cluster's `terraform.tfvars` from its `kaws.toml`, after validating it.
+    /// Clusters without a `kaws.toml` keep using their existing `terraform.tfvars`.
+    pub fn render_tfvars(cluster: &str) -> KawsResult {
+        if !ClusterConfig::exists(cluster) {
+            debug!("No kaws.toml for cluster \"{}\", using terraform.tfvars as is", cluster);
+
+            return Ok(None);
+        }
+
+        let config = ClusterConfig::load(cluster)?;
+
+        config.validate(cluster)?;
+        config.write_tfvars(cluster)
+    }
+
+    /// Checks every setting, reporting all of the problems at once. Instance types kaws doesn't
+    /// know about are only warned about, since AWS adds new ones regularly.
+    pub fn validate(&self, cluster: &str) -> Result<(), KawsError> {
+        let mut problems = vec![];
+        let mut unknown_instance_types = vec![];
+
+        let required = [
+            ("aws_account_id", &self.aws_account_id),
+            ("balancers_cidr", &self.balancers_cidr),
+            ("cidr", &self.cidr),
+            ("coreos_ami", &self.coreos_ami),
+            ("dns_service_ip", &self.dns_service_ip),
+            ("domain", &self.domain),
+            ("etcd_cidr", &self.etcd_cidr),
+            ("kubernetes_version", &self.kubernetes_version),
+            ("pod_cidr", &self.pod_cidr),
+            ("region", &self.region),
+            ("service_cidr", &self.service_cidr),
+            ("vpc_cidr", &self.vpc_cidr),
+            ("zone_id", &self.zone_id),
+        ];
+
+        for &(key, value) in required.iter() {
+            if value.is_empty() {
+                problems.push(format!("{} is required", key));
+            }
+        }
+
+        if self.instance_size.is_empty() && (
+            self.etcd.instance_type.is_empty() ||
+            self.masters.instance_type.is_empty() ||
+            self.nodes.instance_type.is_empty()
+        ) {
+            problems.push(
+                "instance_size is required unless etcd, masters, and nodes all set instance_type"
+                    .to_owned()
+            );
+        }
+
+        if self.availability_zone.is_some() {
+            problems.push(
+                "availability_zone has been replaced by availability_zones, so remove it".to_owned()
+            );
+        }
+
+        if self.availability_zones.is_empty() {
+            problems.push("availability_zones must list at least one Availability Zone".to_owned());
+        }
+
+        for (index, zone) in self.availability_zones.iter().enumerate() {
+            if self.availability_zones[..index].contains(zone) {
+                problems.push(format!("availability_zones lists {} more than once", zone));
+            }
+
+            if !self.region.is_empty() && !zone.starts_with(&self.region) {
+                problems.push(format!(
+                    "availability_zones: {} is not in the region {}",
+                    zone,
+                    self.region,
+                ));
+            }
+        }
+
+        if self.iam_users.is_empty() {
+            problems.push("iam_users must list at least one IAM user".to_owned());
+        }
+
+        if !self.os_channel.is_empty() && !OS_CHANNELS.contains(&self.os_channel.as_str()) {
+            problems.push(format!("os_channel must be one of {}", OS_CHANNELS.join(", ")));
+        }
+
+        if !self.os_version.is_empty() {
+            if self.os_channel.is_empty() {
+                problems.push("os_version requires os_channel".to_owned());
+            }
+
+            if let Err(error) = validate_os_version(&self.os_version) {
+                problems.push(format!("os_version: {}", error));
+            }
+        }
+
+        if !TOPOLOGIES.contains(&self.topology.as_str()) {
+            problems.push(format!("topology must be one of {}", TOPOLOGIES.join(", ")));
+        }
+
+        if self.ssh_keys.is_empty() {
+            problems.push("ssh_keys must list at least one SSH public key".to_owned());
+        }
+
+        if self.state_history.limit == 0 {
+            problems.push("state_history.limit must be at least 1".to_owned());
+        }
+
+        self.validate_instances(&mut problems, &mut unknown_instance_types);
+
+        if !ETCD_MEMBERS.contains(&self.etcd.members) {
+            problems.push(format!(
+                "etcd.members must be one of {}",
+                ETCD_MEMBERS.iter().map(|members| {
+                    members.to_string()
+                }).collect::<Vec<String>>().join(", "),
+            ));
+        }
+
+        self.validate_network(&mut problems);
+
+        if !self.kubernetes_version.is_empty() {
+            if let Err(error) = validate_kubernetes_version(&self.kubernetes_version) {
+                problems.push(format!("kubernetes_version: {}", error));
+            }
+        }
+
+        for &(group, size) in [("masters", &self.masters), ("nodes", &self.nodes)].iter() {
+            if size.max_size == 0 {
+                problems.push(format!("{}.max_size must be at least 1", group));
+            } else if size.min_size > size.max_size {
+                problems.push(format!("{}.min_size cannot be greater than max_size", group));
+            }
+        }
+
+        self.validate_node_pools(&mut problems, &mut unknown_instance_types);
+
+        if self.tags.len() > MAX_TAGS {
+            problems.push(format!("tags may have at most {} tags", MAX_TAGS));
+        }
+
+        for (key, value) in self.tags.iter() {
+            if let Err(error) = validate_tag_pair(key, value) {
+                problems.push(format!("tags: {}", error));
+            }
+        }
+
+        if !unknown_instance_types.is_empty() {
+            println!(
+                "Warning: {} uses instance types kaws does not know about. Check that they exist \
+                in {}:\n{}",
+                config_path(cluster),
+                self.region,
+                unknown_instance_types.iter().map(|warning| {
+                    format!("* {}", warning)
+                }).collect::<Vec<String>>().join("\n"),
+            );
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(KawsError::new(format!(
+                "{} is invalid:\n{}",
+                config_path(cluster),
+                problems.iter().map(|problem| {
+                    format!("* {}", problem)
+                }).collect::<Vec<String>>().join("\n"),
+            )))
+        }
+    }
+
+    pub fn write(&self, cluster: &str) -> KawsResult {
+        log_wrap!("Writing kaws.toml", {
+            let mut file = File::create(config_path(cluster))?;
+
+            write!(
+                file,
+                "\
+# Settings for the kaws cluster \"{cluster}\". terraform.tfvars is generated from this file.
+
+# The AWS account and Region the cluster lives in.
+aws_account_id = {aws_account_id}
+region = {region}
+
+# The Availability Zones to spread the cluster across. Each of the subnet ranges below is divided
+# evenly between them, and the etcd instances are spread across them.
+availability_zones = {availability_zones}
+
+# The base domain name for the cluster, and the ID of its Route 53 hosted zone.
+domain = {domain}
+zone_id = {zone_id}
+
+# The EC2 AMI ID to use for all CoreOS instances.
+coreos_ami = {coreos_ami}
+
+# The Container Linux channel (\"stable\", \"beta\", or \"alpha\") and release that coreos_ami was
+# found for, if it was found by kaws. `kaws cluster ami-update` moves coreos_ami to the latest
+# release in the channel.
+os_channel = {os_channel}
+os_version = {os_version}
+
+# The EC2 instance size to use for etcd, the Kubernetes masters, and the Kubernetes nodes, unless
+# their own instance_type is set below.
+instance_size = {instance_size}
+
+# The full version of Kubernetes to run, without a leading \"v\", e.g. \"1.8.4\". kaws supports
+# Kubernetes 1.7.0 through 1.10.x.
+kubernetes_version = {kubernetes_version}
+
+# The IPv4 network range of the cluster's VPC. Give each cluster a different range if it will be
+# peered with other clusters or networks.
+vpc_cidr = {vpc_cidr}
+
+# How the cluster's servers are connected to the Internet. With \"public\", every server is in a
+# public subnet with a public IP address. With \"private\", etcd and the Kubernetes masters and nodes
+# are in private subnets and reach the Internet through a NAT gateway in each Availability Zone, and
+# only the ELBs and the bastion are public. The topology can't be changed once the cluster has been
+# applied.
+topology = {topology}
+
+# The IPv4 network ranges of the subnets for ELBs, etcd, and Kubernetes nodes, which must be within
+# vpc_cidr and must not overlap. In the private topology, the bastion and NAT gateways share the
+# balancers' subnets, which must be /27 or larger in each Availability Zone.
+balancers_cidr = {balancers_cidr}
+etcd_cidr = {etcd_cidr}
+cidr = {cidr}
+
+# The IPv4 network ranges Kubernetes gives pods and services their IP addresses from, which must not
+# overlap each other or vpc_cidr, and the IP address of the cluster DNS service, which must be in
+# service_cidr. The Kubernetes API service gets the first address in service_cidr, which the
+# masters' certificate must include, so changing service_cidr regenerates it on the next `kaws
+# cluster plan` or `kaws cluster apply`. Changing any of them means replacing every master and node.
+pod_cidr = {pod_cidr}
+service_cidr = {service_cidr}
+dns_service_ip = {dns_service_ip}
+
+# IAM user names who will have access to cluster PKI secrets.
+iam_users = {iam_users}
+
+# SSH public keys to add to ~/.ssh/authorized_keys on each server.
+ssh_keys = {ssh_keys}
+
+# Virtual private gateways that should propagate routes to the cluster's route table.
+propagating_vgws = {propagating_vgws}
+
+# Each role below has its own EC2 instance type and root volume. Volume sizes are in GiB, and volume
+# types are \"gp2\" (general purpose SSD) or \"standard\" (magnetic). An empty instance_type uses
+# instance_size. Changing the root volume of the bastion or etcd only affects instances created
+# afterwards, so existing ones are not replaced.
+
+# The bastion instance, the SSH entrypoint to the cluster.
+[bastion]
+instance_type = {bastion_instance_type}
+root_volume_size = {bastion_root_volume_size}
+root_volume_type = {bastion_root_volume_type}
+
+# The etcd instances. members is the number of instances: 1, 3, or 5. A cluster with n members keeps
+# working as long as a majority of them are available. Changing it requires regenerating the etcd
+# server and peer certificates, and etcd does not add or remove members from a running cluster by
+# itself. volume_size and volume_type are for the encrypted EBS volume that holds etcd's data.
+[etcd]
+instance_type = {etcd_instance_type}
+members = {etcd_members}
+root_volume_size = {etcd_root_volume_size}
+root_volume_type = {etcd_root_volume_type}
+volume_size = {etcd_volume_size}
+volume_type = {etcd_volume_type}
+
+# The Kubernetes masters, and the number of EC2 instances they may autoscale between.
+[masters]
+instance_type = {masters_instance_type}
+min_size = {masters_min_size}
+max_size = {masters_max_size}
+root_volume_size = {masters_root_volume_size}
+root_volume_type = {masters_root_volume_type}
+
+# The Kubernetes nodes, and the number of EC2 instances they may autoscale between.
+[nodes]
+instance_type = {nodes_instance_type}
+min_size = {nodes_min_size}
+max_size = {nodes_max_size}
+root_volume_size = {nodes_root_volume_size}
+root_volume_type = {nodes_root_volume_type}
+
+# kaws saves a snapshot of the Terraform state to clusters/{cluster}/state-history before every
+# command that changes it, and keeps the most recent `limit` snapshots. With encrypt = true, the
+# snapshots are encrypted with the cluster's KMS key, so only administrators with access to the key
+# can restore them.
+[state_history]
+encrypt = {state_history_encrypt}
+limit = {state_history_limit}
+
+# Tags to add to every AWS resource that supports them, e.g. for cost allocation, in addition to
+# the Name and KubernetesCluster tags kaws sets itself. The Kubernetes masters' and nodes'
+# autoscaling groups give them to the instances they launch.
+[tags]
+{tags}
+
+# Additional pools of Kubernetes nodes, each with its own instance type and autoscaling group, are
+# added below as [[node_pools]] tables by `kaws cluster nodepool add`. Pools are identified by their
+# position, so removing or reordering them replaces the nodes of every pool after the first change.
+",
+                cluster = cluster,
+                availability_zones = toml_list(&self.availability_zones),
+                aws_account_id = toml_string(&self.aws_account_id),
+                balancers_cidr = toml_string(&self.balancers_cidr),
+                bastion_instance_type = toml_string(&self.bastion.instance_type),
+                bastion_root_volume_size = self.bastion.root_volume_size,
+                bastion_root_volume_type = toml_string(&self.bastion.root_volume_type),
+                cidr = toml_string(&self.cidr),
+                coreos_ami = toml_string(&self.coreos_ami),
+                dns_service_ip = toml_string(&self.dns_service_ip),
+                domain = toml_string(&self.domain),
+                etcd_cidr = toml_string(&self.etcd_cidr),
+                etcd_instance_type = toml_string(&self.etcd.instance_type),
+                etcd_members = self.etcd.members,
+                etcd_root_volume_size = self.etcd.root_volume_size,
+                etcd_root_volume_type = toml_string(&self.etcd.root_volume_type),
+                etcd_volume_size = self.etcd.volume_size,
+                etcd_volume_type = toml_string(&self.etcd.volume_type),
+                iam_users = toml_list(&self.iam_users),
+                instance_size = toml_string(&self.instance_size),
+                kubernetes_version = toml_string(&self.kubernetes_version),
+                masters_instance_type = toml_string(&self.masters.instance_type),
+                masters_max_size = self.masters.max_size,
+                masters_min_size = self.masters.min_size,
+                masters_root_volume_size = self.masters.root_volume_size,
+                masters_root_volume_type = toml_string(&self.masters.root_volume_type),
+                nodes_instance_type = toml_string(&self.nodes.instance_type),
+                nodes_max_size = self.nodes.max_size,
+                nodes_min_size = self.nodes.min_size,
+                nodes_root_volume_size = self.nodes.root_volume_size,
+                nodes_root_volume_type = toml_string(&self.nodes.root_volume_type),
+                os_channel = toml_string(&self.os_channel),
+                os_version = toml_string(&self.os_version),
+                pod_cidr = toml_string(&self.pod_cidr),
+                propagating_vgws = toml_list(&self.propagating_vgws),
+                region = toml_string(&self.region),
+                service_cidr = toml_string(&self.service_cidr),
+                ssh_keys = toml_list(&self.ssh_keys),
+                state_history_encrypt = self.state_history.encrypt,
+                state_history_limit = self.state_history.limit,
+                tags = if self.tags.is_empty() {
+                    "# team = \"platform\"".to_owned()
+                } else {
+                    self.tags.iter().map(|(key, value)| {
+                        format!("{} = {}", bare_or_quoted_key(key), toml_string(value))
+                    }).collect::<Vec<_>>().join("\n")
+                },
+                topology = toml_string(&self.topology),
+                vpc_cidr = toml_string(&self.vpc_cidr),
+                zone_id = toml_string(&self.zone_id),
+            )?;
+
+            for node_pool in self.node_pools.iter() {
+                write!(file, "\n{}", node_pool.to_toml())?;
+            }
+        });
+
+        Ok(None)
+    }
+
+    pub fn write_tfvars(&self, cluster: &str) -> KawsResult {
+        let fragments = CloudConfigFragment::load_all(cluster)?;
+        let contents = self.to_tfvars(cluster, &fragments)?;
+
+        log_wrap!("Rendering terraform.tfvars", {
+            File::create(tfvars_path(cluster))?.write_all(contents.as_bytes())?;
+        });
+
+        Ok(None)
+    }
+
+    // Private
+
+    /// Renders the contents of the cluster's `terraform.tfvars`.
+    fn to_tfvars(
+        &self,
+        cluster: &str,
+        fragments: &[(&'static str, CloudConfigFragment)],
+    ) -> Result<String, KawsError> {
+        let subnets = |key: &str, cidr: &str| -> Result<Vec<String>, KawsError> {
+            Ok(self.subnets(key, cidr)?.iter().map(|subnet| subnet.to_string()).collect())
+        };
+
+        let balancers_subnets = subnets("balancers_cidr", &self.balancers_cidr)?;
+        let etcd_subnets = subnets("etcd_cidr", &self.etcd_cidr)?;
+        let k8s_subnets = subnets("cidr", &self.cidr)?;
+
+        let release = self.kubernetes_version.parse::<KubernetesVersion>().and_then(|version| {
+            version.release()
+        }).map_err(|error| KawsError::new(format!("kubernetes_version: {}", error)))?;
+
+        let node_groups: Vec<NodeGroup> = self.node_pools.iter().flat_map(|pool| pool.groups()).collect();
+
+        let fragment_values = |value: fn(&CloudConfigFragment, &str) -> String| {
+            fragments.iter().map(|&(role, ref fragment)| {
+                (role, value(fragment, role))
+            }).filter(|(_, value)| !value.is_empty()).collect::<Vec<(&str, String)>>()
+        };
+
+        let node_group_values = |value: fn(&NodeGroup) -> String| -> Vec<String> {
+            node_groups.iter().map(value).collect()
+        };
+
+        Ok(format!(
+            "\
+# Generated by kaws from kaws.toml. Do not edit.
+kaws_account_id = {}
+kaws_asg_tags = {}
+kaws_availability_zones = {}
+kaws_balancers_subnets = {}
+kaws_bastion_instance_type = {}
+kaws_bastion_root_volume_size = \"{}\"
+kaws_bastion_root_volume_type = {}
+kaws_cloud_config_ssh_keys = {}
+kaws_cloud_config_units = {}
+kaws_cloud_config_write_files = {}
+kaws_cluster = {}
+kaws_coreos_ami = {}
+kaws_dns_service_ip = {}
+kaws_domain = {}
+kaws_etcd_instance_type = {}
+kaws_etcd_ips = {}
+kaws_etcd_root_volume_size = \"{}\"
+kaws_etcd_root_volume_type = {}
+kaws_etcd_subnets = {}
+kaws_etcd_volume_size = \"{}\"
+kaws_etcd_volume_type = {}
+kaws_iam_users = {}
+kaws_instance_size = {}
+kaws_k8s_subnets = {}
+kaws_kubelet_api_servers = \"{}\"
+kaws_masters_instance_type = {}
+kaws_masters_max_size = \"{}\"
+kaws_masters_min_size = \"{}\"
+kaws_masters_root_volume_size = \"{}\"
+kaws_masters_root_volume_type = {}
+kaws_node_pool_instance_types = {}
+kaws_node_pool_labels = {}
+kaws_node_pool_max_sizes = {}
+kaws_node_pool_min_sizes = {}
+kaws_node_pool_spot_prices = {}
+kaws_node_pool_taints = {}
+kaws_node_pools = {}
+kaws_nodes_instance_type = {}
+kaws_nodes_max_size = \"{}\"
+kaws_nodes_min_size = \"{}\"
+kaws_nodes_root_volume_size = \"{}\"
+kaws_nodes_root_volume_type = {}
+kaws_pod_cidr = {}
+kaws_propagating_vgws = {}
+kaws_region = {}
+kaws_service_cidr = {}
+kaws_ssh_keys = {}
+kaws_tags = {}
+kaws_topology = {}
+kaws_version = {}
+kaws_vpc_cidr = {}
+kaws_zone_id = {}
+",
+            hcl_string(&self.aws_account_id),
+            hcl_asg_tags(&self.tags),
+            hcl_list(&self.availability_zones),
+            hcl_list(&balancers_subnets),
+            hcl_string(&self.bastion.instance_type),
+            self.bastion.root_volume_size,
+            hcl_string(&self.bastion.root_volume_type),
+            hcl_map(&fragment_values(|fragment, _| fragment.ssh_keys_yaml())),
+            hcl_map(&fragment_values(CloudConfigFragment::units_yaml)),
+            hcl_map(&fragment_values(CloudConfigFragment::write_files_yaml)),
+            hcl_string(cluster),
+            hcl_string(&self.coreos_ami),
+            hcl_string(&self.dns_service_ip),
+            hcl_string(&self.domain),
+            hcl_string(self.instance_type(&self.etcd.instance_type)),
+            hcl_list(&self.etcd_ips()?),
+            self.etcd.root_volume_size,
+            hcl_string(&self.etcd.root_volume_type),
+            hcl_list(&etcd_subnets),
+            self.etcd.volume_size,
+            hcl_string(&self.etcd.volume_type),
+            hcl_list(&self.iam_users),
+            hcl_string(&self.instance_size),
+            hcl_list(&k8s_subnets),
+            release.kubelet_api_servers,
+            hcl_string(self.instance_type(&self.masters.instance_type)),
+            self.masters.max_size,
+            self.masters.min_size,
+            self.masters.root_volume_size,
+            hcl_string(&self.masters.root_volume_type),
+            hcl_list(&node_group_values(|group| group.instance_type.clone())),
+            hcl_list(&node_group_values(|group| group.labels.join(","))),
+            hcl_list(&node_group_values(|group| group.max_size.to_string())),
+            hcl_list(&node_group_values(|group| group.min_size.to_string())),
+            hcl_list(&node_group_values(|group| group.spot_price.clone())),
+            hcl_list(&node_group_values(|group| group.taints.join(","))),
+            hcl_list(&node_group_values(|group| group.name.clone())),
+            hcl_string(self.instance_type(&self.nodes.instance_type)),
+            self.nodes.max_size,
+            self.nodes.min_size,
+            self.nodes.root_volume_size,
+            hcl_string(&self.nodes.root_volume_type),
+            hcl_string(&self.pod_cidr),
+            hcl_list(&self.propagating_vgws),
+            hcl_string(&self.region),
+            hcl_string(&self.service_cidr),
+            hcl_list(&self.ssh_keys),
+            hcl_map(&self.tags.iter().map(|(key, value)| (key.as_str(), value.clone())).collect::<Vec<_>>()),
+            hcl_string(&self.topology),
+            hcl_string(&self.kubernetes_version),
+            hcl_string(&self.vpc_cidr),
+            hcl_string(&self.zone_id),
+        ))
+    }
+
+    /// The instance type for a role, which is the cluster's `instance_size` unless the role sets
+    /// its own.
+    fn instance_type<'b>(&'b self, instance_type: &'b str) -> &'b str {
+        if instance_type.is_empty() {
+            &self.instance_size
+        } else {
+            instance_type
+        }
+    }
+
+    /// Divides one of the subnet ranges between the availability zones.
+    fn subnets(&self, key: &str, cidr: &str) -> Result<Vec<Ipv4Cidr>, KawsError> {
+        let cidr = parse_cidr(cidr).map_err(|error| KawsError::new(format!("{}: {}", key, error)))?;
+
+        carve(&cidr, max(self.availability_zones.len(), 1)).map_err(|error| {
+            KawsError::new(format!("{}: {}", key, error))
+        })
+    }
+
+    /// Checks the instance types and volumes of each role.
+    fn validate_instances(
+        &self,
+        problems: &mut Vec<String>,
+        unknown_instance_types: &mut Vec<String>,
+    ) {
+        let roles = [
+            (
+                "bastion",
+                &self.bastion.instance_type,
+                self.bastion.root_volume_size,
+                &self.bastion.root_volume_type,
+            ),
+            (
+                "etcd",
+                &self.etcd.instance_type,
+                self.etcd.root_volume_size,
+                &self.etcd.root_volume_type,
+            ),
+            (
+                "masters",
+                &self.masters.instance_type,
+                self.masters.root_volume_size,
+                &self.masters.root_volume_type,
+            ),
+            (
+                "nodes",
+                &self.nodes.instance_type,
+                self.nodes.root_volume_size,
+                &self.nodes.root_volume_type,
+            ),
+        ];
+
+        if !self.instance_size.is_empty() {
+            if let Err(error) = validate_instance_type(&self.instance_size) {
+                unknown_instance_types.push(format!("instance_size: {}", error));
+            }
+        }
+
+        for &(role, instance_type, root_volume_size, root_volume_type) in roles.iter() {
+            if role == "bastion" && instance_type.is_empty() {
+                problems.push("bastion.instance_type is required".to_owned());
+            } else if !instance_type.is_empty() {
+                if let Err(error) = validate_instance_type(instance_type) {
+                    unknown_instance_types.push(format!("{}.instance_type: {}", role, error));
+                }
+            }
+
+            if !(MIN_ROOT_VOLUME_SIZE..=MAX_VOLUME_SIZE).contains(&root_volume_size) {
+                problems.push(format!(
+                    "{}.root_volume_size must be between {} and {} GiB",
+                    role,
+                    MIN_ROOT_VOLUME_SIZE,
+                    MAX_VOLUME_SIZE,
+                ));
+            }
+
+            if let Err(error) = validate_volume_type(root_volume_type) {
+                problems.push(format!("{}.root_volume_type: {}", role, error));
+            }
+        }
+
+        if self.etcd.volume_size == 0 || self.etcd.volume_size > MAX_VOLUME_SIZE {
+            problems.push(format!("etcd.volume_size must be between 1 and {} GiB", MAX_VOLUME_SIZE));
+        }
+
+        if let Err(error) = validate_volume_type(&self.etcd.volume_type) {
+            problems.push(format!("etcd.volume_type: {}", error));
+        }
+    }
+
+    /// Checks the node pools' names, sizes, labels, and taints.
+    fn validate_node_pools(
+        &self,
+        problems: &mut Vec<String>,
+        unknown_instance_types: &mut Vec<String>,
+    ) {
+        for (index, pool) in self.node_pools.iter().enumerate() {
+            if let Err(error) = validate_node_pool_name(&pool.name) {
+                problems.push(format!("node_pools: {}", error));
+
+                continue;
+            }
+
+            if self.node_pools[..index].iter().any(|other| other.name == pool.name) {
+                problems.push(format!("node_pools: {} is defined more than once", pool.name));
+            }
+
+            if pool.instance_type.is_empty() {
+                problems.push(format!("node_pools.{}.instance_type is required", pool.name));
+            } else if let Err(error) = validate_instance_type(&pool.instance_type) {
+                unknown_instance_types.push(format!(
+                    "node_pools.{}.instance_type: {}",
+                    pool.name,
+                    error,
+                ));
+            }
+
+            if pool.max_size == 0 {
+                problems.push(format!("node_pools.{}.max_size must be at least 1", pool.name));
+            } else if pool.min_size > pool.max_size {
+                problems.push(format!(
+                    "node_pools.{}.min_size cannot be greater than max_size",
+                    pool.name,
+                ));
+            }
+
+            for label in pool.labels.iter() {
+                if let Err(error) = validate_label(label) {
+                    problems.push(format!("node_pools.{}.labels: {}", pool.name, error));
+                }
+            }
+
+            for taint in pool.taints.iter() {
+                if let Err(error) = validate_taint(taint) {
+                    problems.push(format!("node_pools.{}.taints: {}", pool.name, error));
+                }
+            }
+
+            if !pool.spot {
+                if !pool.spot_instance_types.is_empty() || !pool.spot_max_price.is_empty() {
+                    problems.push(format!(
+                        "node_pools.{}: spot_instance_types and spot_max_price are only used when \
+                        spot is true",
+                        pool.name,
+                    ));
+                }
+
+                continue;
+            }
+
+            if let Err(error) = validate_spot_price(&pool.spot_max_price) {
+                problems.push(format!("node_pools.{}.spot_max_price: {}", pool.name, error));
+            }
+
+            if (pool.max_size as usize) < pool.spot_instance_types.len() {
+                problems.push(format!(
+                    "node_pools.{}.max_size must be at least the number of spot_instance_types, \
+                    since it is divided between them",
+                    pool.name,
+                ));
+            }
+
+            for (index, instance_type) in pool.spot_instance_types.iter().enumerate() {
+                if pool.spot_instance_types[..index].contains(instance_type) {
+                    problems.push(format!(
+                        "node_pools.{}.spot_instance_types lists {} more than once",
+                        pool.name,
+                        instance_type,
+                    ));
+                } else if let Err(error) = validate_instance_type(instance_type) {
+                    unknown_instance_types.push(format!(
+                        "node_pools.{}.spot_instance_types: {}",
+                        pool.name,
+                        error,
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Checks that the subnets fit within the VPC without overlapping each other, and that the pod
+    /// and service networks are separate from each other and from the VPC.
+    fn validate_network(&self, problems: &mut Vec<String>) {
+        let mut parse = |key: &str, value: &str| -> Option<Ipv4Cidr> {
+            if value.is_empty() {
+                return None;
+            }
+
+            match parse_cidr(value) {
+                Ok(cidr) => Some(cidr),
+                Err(error) => {
+                    problems.push(format!("{}: {}", key, error));
+
+                    None
+                }
+            }
+        };
+
+        let vpc_cidr = parse("vpc_cidr", &self.vpc_cidr);
+        let pod_cidr = parse("pod_cidr", &self.pod_cidr);
+        let service_cidr = parse("service_cidr", &self.service_cidr);
+
+        let subnets = [
+            ("balancers_cidr", parse("balancers_cidr", &self.balancers_cidr)),
+            ("etcd_cidr", parse("etcd_cidr", &self.etcd_cidr)),
+            ("cidr", parse("cidr", &self.cidr)),
+        ];
+
+        if let Some(ref vpc_cidr) = vpc_cidr {
+            if vpc_cidr.network_length() < 16 || vpc_cidr.network_length() > 28 {
+                problems.push(format!(
+                    "vpc_cidr: {} must have a prefix length between /16 and /28.",
+                    vpc_cidr,
+                ));
+            }
+
+            for &(key, ref subnet) in subnets.iter() {
+                if let Some(ref subnet) = *subnet {
+                    if subnet.subset_cmp(vpc_cidr) != Some(Ordering::Less) {
+                        problems.push(format!(
+                            "{}: {} must be a subset of the VPC's range, {}.",
+                            key,
+                            subnet,
+                            vpc_cidr,
+                        ));
+                    }
+                }
+            }
+        }
+
+        for (index, &(key, ref subnet)) in subnets.iter().enumerate() {
+            let subnet = match *subnet {
+                Some(ref subnet) => subnet,
+                None => continue,
+            };
+
+            match carve(subnet, max(self.availability_zones.len(), 1)) {
+                Ok(ref zone_subnets) if key == "balancers_cidr" && self.topology == "private" => {
+                    if zone_subnets[0].network_length() > MAX_PRIVATE_BALANCERS_PREFIX {
+                        problems.push(format!(
+                            "{}: {} must give each Availability Zone a /{} subnet or larger in the \
+                            private topology, for the ELBs, NAT gateway, and bastion.",
+                            key,
+                            subnet,
+                            MAX_PRIVATE_BALANCERS_PREFIX,
+                        ));
+                    }
+                }
+                Ok(_) => {}
+                Err(error) => problems.push(format!("{}: {}", key, error)),
+            }
+
+            for &(other_key, ref other_subnet) in subnets[index + 1..].iter() {
+                if let Some(ref other_subnet) = *other_subnet {
+                    if subnet.subset_cmp(other_subnet).is_some() {
+                        problems.push(format!(
+                            "{}: {} overlaps with {} ({}).",
+                            key,
+                            subnet,
+                            other_key,
+                            other_subnet,
+                        ));
+                    }
+                }
+            }
+        }
+
+        if let Some(ref pod_cidr) = pod_cidr {
+            if pod_cidr.network_length() > MAX_POD_PREFIX {
+                problems.push(format!(
+                    "pod_cidr: {} must be a /{} or larger, since each server gets a /24 from it.",
+                    pod_cidr,
+                    MAX_POD_PREFIX,
+                ));
+            }
+        }
+
+        if let Some(ref service_cidr) = service_cidr {
+            if service_cidr.network_length() < MIN_SERVICE_PREFIX ||
+                service_cidr.network_length() > MAX_SERVICE_PREFIX
+            {
+                problems.push(format!(
+                    "service_cidr: {} must have a prefix length between /{} and /{}.",
+                    service_cidr,
+                    MIN_SERVICE_PREFIX,
+                    MAX_SERVICE_PREFIX,
+                ));
+            }
+        }
+
+        let networks = [
+            ("vpc_cidr", vpc_cidr.as_ref()),
+            ("pod_cidr", pod_cidr.as_ref()),
+            ("service_cidr", service_cidr.as_ref()),
+        ];
+
+        for (index, &(key, network)) in networks.iter().enumerate() {
+            for &(other_key, other_network) in networks[index + 1..].iter() {
+                if let (Some(network), Some(other_network)) = (network, other_network) {
+                    if network.subset_cmp(other_network).is_some() {
+                        problems.push(format!(
+                            "{}: {} overlaps with {} ({}).",
+                            other_key,
+                            other_network,
+                            key,
+                            network,
+                        ));
+                    }
+                }
+            }
+        }
+
+        if self.dns_service_ip.is_empty() {
+            return;
+        }
+
+        let dns_service_ip: Ipv4Addr = match self.dns_service_ip.parse() {
+            Ok(ip) => ip,
+            Err(_) => {
+                problems.push(format!(
+                    "dns_service_ip: \"{}\" is not a valid IPv4 address.",
+                    self.dns_service_ip,
+                ));
+
+                return;
+            }
+        };
+
+        if let Some(ref service_cidr) = service_cidr {
+            let ip = u32::from(dns_service_ip);
+            let first = u32::from(service_cidr.first_address());
+            let last = u32::from(service_cidr.last_address());
+
+            if ip <= first + 1 || ip >= last {
+                problems.push(format!(
+                    "dns_service_ip: {} must be in service_cidr ({}), and must not be its first \
+                    two or last address, which are its network address, the Kubernetes API \
+                    service's address, and its broadcast address.",
+                    dns_service_ip,
+                    service_cidr,
+                ));
+            }
+        }
+    }
+}
+
+impl NodePoolConfig {
+    /// Renders the pool as a `[[node_pools]]` table for `kaws.toml`.
+    pub fn to_toml(&self) -> String {
+        format!(
+            "\
+[[node_pools]]
+name = {name}
+instance_type = {instance_type}
+min_size = {min_size}
+max_size = {max_size}
+labels = {labels}
+taints = {taints}
+spot = {spot}
+spot_instance_types = {spot_instance_types}
+spot_max_price = {spot_max_price}
+on_demand_fallback = {on_demand_fallback}
+",
+            instance_type = toml_string(&self.instance_type),
+            labels = toml_list(&self.labels),
+            max_size = self.max_size,
+            min_size = self.min_size,
+            name = toml_string(&self.name),
+            on_demand_fallback = self.on_demand_fallback,
+            spot = self.spot,
+            spot_instance_types = toml_list(&self.spot_instance_types),
+            spot_max_price = toml_string(&self.spot_max_price),
+            taints = toml_list(&self.taints),
+        )
+    }
+
+    /// The pool's autoscaling groups. A spot pool's minimum and maximum sizes are divided between
+    /// its groups of spot instances, and its on-demand group, which may autoscale up to the pool's
+    /// maximum size, only has instances when they are asked for.
+    fn groups(&self) -> Vec<NodeGroup> {
+        let mut labels = vec![format!("{}={}", NODE_POOL_LABEL, self.name)];
+
+        labels.extend(self.labels.iter().cloned());
+
+        if !self.spot {
+            return vec![NodeGroup {
+                instance_type: self.instance_type.clone(),
+                labels: labels,
+                max_size: self.max_size,
+                min_size: self.min_size,
+                name: self.name.clone(),
+                spot_price: String::new(),
+                taints: self.taints.clone(),
+            }];
+        }
+
+        let mut taints = vec![SPOT_TAINT.to_owned()];
+
+        taints.extend(self.taints.iter().cloned());
+
+        let spot_instance_types = if self.spot_instance_types.is_empty() {
+            vec![self.instance_type.clone()]
+        } else {
+            self.spot_instance_types.clone()
+        };
+
+        let count = spot_instance_types.len() as u32;
+        let share = |size: u32, index: usize| {
+            size / count + if (index as u32) < size % count { 1 } else { 0 }
+        };
+
+        let mut groups: Vec<NodeGroup> = spot_instance_types.into_iter().enumerate().map(|(index, instance_type)| {
+            let mut labels = labels.clone();
+
+            labels.push(format!("{}=spot", LIFECYCLE_LABEL));
+
+            NodeGroup {
+                labels: labels,
+                max_size: share(self.max_size, index),
+                min_size: share(self.min_size, index),
+                name: format!("{}.{}", self.name, instance_type),
+                instance_type: instance_type,
+                spot_price: self.spot_max_price.clone(),
+                taints: taints.clone(),
+            }
+        }).collect();
+
+        if self.on_demand_fallback {
+            labels.push(format!("{}=on-demand", LIFECYCLE_LABEL));
+
+            groups.push(NodeGroup {
+                instance_type: self.instance_type.clone(),
+                labels: labels,
+                max_size: self.max_size,
+                min_size: 0,
+                name: self.name.clone(),
+                spot_price: String::new(),
+                taints: taints,
+            });
+        }
+
+        groups
+    }
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        ClusterConfig {
+            availability_zone: None,
+            availability_zones: vec![],
+            aws_account_id: String::new(),
+            balancers_cidr: DEFAULT_BALANCERS_CIDR.to_owned(),
+            bastion: BastionConfig::default(),
+            cidr: String::new(),
+            coreos_ami: String::new(),
+            dns_service_ip: DEFAULT_DNS_SERVICE_IP.to_owned(),
+            domain: String::new(),
+            etcd: EtcdConfig::default(),
+            etcd_cidr: DEFAULT_ETCD_CIDR.to_owned(),
+            iam_users: vec![],
+            instance_size: String::new(),
+            kubernetes_version: String::new(),
+            masters: GroupConfig::default(),
+            node_pools: vec![],
+            nodes: GroupConfig::default(),
+            os_channel: String::new(),
+            os_version: String::new(),
+            pod_cidr: DEFAULT_POD_CIDR.to_owned(),
+            propagating_vgws: vec![],
+            region: String::new(),
+            service_cidr: DEFAULT_SERVICE_CIDR.to_owned(),
+            ssh_keys: vec![],
+            state_history: StateHistoryConfig::default(),
+            tags: BTreeMap::new(),
+            topology: DEFAULT_TOPOLOGY.to_owned(),
+            vpc_cidr: DEFAULT_VPC_CIDR.to_owned(),
+            zone_id: String::new(),
+        }
+    }
+}
+
+impl Default for BastionConfig {
+    fn default() -> Self {
+        BastionConfig {
+            instance_type: DEFAULT_BASTION_INSTANCE_TYPE.to_owned(),
+            root_volume_size: DEFAULT_ROOT_VOLUME_SIZE,
+            root_volume_type: DEFAULT_VOLUME_TYPE.to_owned(),
+        }
+    }
+}
+
+impl Default for EtcdConfig {
+    fn default() -> Self {
+        EtcdConfig {
+            instance_type: String::new(),
+            members: DEFAULT_ETCD_MEMBERS,
+            root_volume_size: DEFAULT_ROOT_VOLUME_SIZE,
+            root_volume_type: DEFAULT_VOLUME_TYPE.to_owned(),
+            volume_size: DEFAULT_ETCD_VOLUME_SIZE,
+            volume_type: DEFAULT_VOLUME_TYPE.to_owned(),
+        }
+    }
+}
+
+impl Default for GroupConfig {
+    fn default() -> Self {
+        GroupConfig {
+            instance_type: String::new(),
+            max_size: 0,
+            min_size: 0,
+            root_volume_size: DEFAULT_GROUP_ROOT_VOLUME_SIZE,
+            root_volume_type: DEFAULT_VOLUME_TYPE.to_owned(),
+        }
+    }
+}
+
+impl Default for NodePoolConfig {
+    fn default() -> Self {
+        NodePoolConfig {
+            instance_type: String::new(),
+            labels: vec![],
+            max_size: 0,
+            min_size: 0,
+            name: String::new(),
+            on_demand_fallback: true,
+            spot: false,
+            spot_instance_types: vec![],
+            spot_max_price: String::new(),
+            taints: vec![],
+        }
+    }
+}
+
+impl Default for StateHistoryConfig {
+    fn default() -> Self {
+        StateHistoryConfig {
+            encrypt: false,
+            limit: DEFAULT_STATE_HISTORY_LIMIT,
+        }
+    }
+}
+
+pub fn config_path(cluster: &str) -> String {
+    format!("clusters/{}/kaws.toml", cluster)
+}
+
+pub fn tfvars_path(cluster: &str) -> String {
+    format!("clusters/{}/terraform.tfvars", cluster)
+}
+
+/// Finds the network range that `carve` divided into `subnets`.
+fn uncarve(subnets: &[String]) -> Option<String> {
+    let first = parse_cidr(subnets.first()?).ok()?;
+    let mut new_bits = 0;
+
+    while 1 << new_bits < subnets.len() {
+        new_bits += 1;
+    }
+
+    let length = first.network_length().checked_sub(new_bits)?;
+
+    Ipv4Cidr::new(first.first_address(), length).ok().map(|cidr| cidr.to_string())
+}
+
+/// Divides a network range into `count` equal subnets, rounding up to a power of two, so the last
+/// few may go unused. Subnets must be /28 or larger, the smallest size AWS allows.
+fn carve(cidr: &Ipv4Cidr, count: usize) -> Result<Vec<Ipv4Cidr>, String> {
+    let mut new_bits = 0;
+
+    while 1 << new_bits < count {
+        new_bits += 1;
+    }
+
+    let length = cidr.network_length() + new_bits;
+
+    if length > 28 {
+        return Err(if count == 1 {
+            format!("{} must be /28 or larger.", cidr)
+        } else {
+            format!(
+                "{} is too small to divide between {} availability zones, since each zone's \
+                subnet must be /28 or larger.",
+                cidr,
+                count,
+            )
+        });
+    }
+
+    let first = u32::from(cidr.first_address());
+    let size = 1u32 << (32 - length);
+
+    Ok((0..count as u32).map(|index| {
+        Ipv4Cidr::new(Ipv4Addr::from(first + index * size), length)
+            .expect("subnets of a valid network range should be valid")
+    }).collect())
+}
+
+/// Parses an IPv4 network range, e.g. "10.0.2.0/24".
+pub fn parse_cidr(cidr: &str) -> Result<Ipv4Cidr, String> {
+    cidr.parse().map_err(|_| format!("\"{}\" is not a valid IPv4 CIDR block.", cidr))
+}
+
+/// Checks that a Kubernetes version is a full version, e.g. "1.8.4", of a supported minor release.
+pub fn validate_kubernetes_version(version: &str) -> Result<(), String> {
+    version.parse::<KubernetesVersion>()?.release().map(|_| ())
+}
+
+/// Checks that an instance type is one of the known sizes of a known EC2 instance family.
+pub fn validate_instance_type(instance_type: &str) -> Result<(), String> {
+    let mut parts = instance_type.splitn(2, '.');
+    let family = parts.next().unwrap_or("");
+    let size = parts.next().unwrap_or("");
+
+    match INSTANCE_TYPES.iter().find(|&&(known_family, _)| known_family == family) {
+        Some(&(_, sizes)) => if sizes.contains(&size) {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} is not a valid instance type. {} instances come in the sizes {}.",
+                instance_type,
+                family,
+                sizes.join(", "),
+            ))
+        },
+        None => Err(format!(
+            "{} is not a valid instance type. Known instance families are {}.",
+            instance_type,
+            INSTANCE_TYPES.iter().map(|&(family, _)| family).collect::<Vec<&str>>().join(", "),
+        )),
+    }
+}
+
+/// Checks that a node pool's name can be used in the names of its AWS resources and as the value
+/// of its nodes' `kaws/node-pool` label.
+pub fn validate_node_pool_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty() &&
+        name.len() <= 32 &&
+        name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') &&
+        !name.starts_with('-') &&
+        !name.ends_with('-');
+
+    if !valid {
+        Err(format!(
+            "\"{}\" is not a valid node pool name. Names must be at most 32 lowercase letters, \
+            digits, and hyphens, and must start and end with a letter or digit.",
+            name,
+        ))
+    } else if name == DEFAULT_NODE_POOL || name == "nodes" {
+        Err(format!("\"{}\" is the name of the pool of nodes in [nodes].", name))
+    } else if name == "masters" {
+        Err(format!("\"{}\" is reserved for the Kubernetes masters.", name))
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that a Kubernetes label is given as `key=value`. The labels kaws sets itself, such as the
+/// name of the node pool, can't be overridden.
+pub fn validate_label(label: &str) -> Result<(), String> {
+    let (key, value) = match label.find('=') {
+        Some(index) => (&label[..index], &label[index + 1..]),
+        None => return Err(format!("\"{}\" is not a valid label. Use key=value.", label)),
+    };
+
+    if key.is_empty() || !valid_label_part(key, true) || !valid_label_part(value, false) {
+        Err(format!(
+            "\"{}\" is not a valid label. Keys and values may contain letters, digits, \"-\", \
+            \"_\", and \".\", and keys may have a prefix ending in \"/\".",
+            label,
+        ))
+    } else if key.starts_with(RESERVED_PREFIX) {
+        Err(format!("Labels with the prefix {} are reserved for kaws.", RESERVED_PREFIX))
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that a Kubernetes taint is given as `key=value:Effect` or `key:Effect`.
+pub fn validate_taint(taint: &str) -> Result<(), String> {
+    let invalid = || format!(
+        "\"{}\" is not a valid taint. Use key=value:Effect or key:Effect, where Effect is one of {}.",
+        taint,
+        TAINT_EFFECTS.join(", "),
+    );
+
+    let (key_value, effect) = match taint.rfind(':') {
+        Some(index) => (&taint[..index], &taint[index + 1..]),
+        None => return Err(invalid()),
+    };
+
+    let (key, value) = match key_value.find('=') {
+        Some(index) => (&key_value[..index], &key_value[index + 1..]),
+        None => (key_value, ""),
+    };
+
+    if key.is_empty() || !valid_label_part(key, true) || !valid_label_part(value, false) ||
+        !TAINT_EFFECTS.contains(&effect)
+    {
+        Err(invalid())
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that a spot price is a positive number of US dollars, e.g. "0.05".
+pub fn validate_spot_price(price: &str) -> Result<(), String> {
+    let valid = !price.is_empty() &&
+        price.chars().all(|c| c.is_ascii_digit() || c == '.') &&
+        price.parse::<f64>().map(|price| price > 0.0).unwrap_or(false);
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "\"{}\" is not a valid spot price. Give the maximum hourly price in US dollars, e.g. \"0.05\".",
+            price,
+        ))
+    }
+}
+
+/// Checks that a tag is given as `key=value` and fits AWS's limits on tags.
+pub fn validate_tag(tag: &str) -> Result<(), String> {
+    match tag.find('=') {
+        Some(index) => validate_tag_pair(&tag[..index], &tag[index + 1..]),
+        None => Err(format!("\"{}\" is not a valid tag. Use key=value.", tag)),
+    }
+}
+
+/// Checks a tag's key and value against AWS's limits on tags.
+fn validate_tag_pair(key: &str, value: &str) -> Result<(), String> {
+    let valid_chars = |part: &str| {
+        part.chars().all(|c| c.is_alphanumeric() || TAG_PUNCTUATION.contains(c))
+    };
+
+    if key.is_empty() || key.chars().count() > MAX_TAG_KEY_LENGTH {
+        Err(format!(
+            "\"{}\" is not a valid tag key. Keys must be 1 to {} characters long.",
+            key,
+            MAX_TAG_KEY_LENGTH,
+        ))
+    } else if value.chars().count() > MAX_TAG_VALUE_LENGTH {
+        Err(format!(
+            "The value of tag \"{}\" is too long. Values may be at most {} characters long.",
+            key,
+            MAX_TAG_VALUE_LENGTH,
+        ))
+    } else if !valid_chars(key) || !valid_chars(value) {
+        Err(format!(
+            "Tag \"{}\" is not valid. Keys and values may contain letters, digits, spaces, and \
+            \"{}\".",
+            key,
+            TAG_PUNCTUATION.trim(),
+        ))
+    } else if key.to_lowercase().starts_with("aws:") {
+        Err(format!("Tag \"{}\" is not valid. Keys with the prefix aws: are reserved for AWS.", key))
+    } else if RESERVED_TAGS.contains(&key) {
+        Err(format!("Tag \"{}\" is not valid. kaws sets it on every resource itself.", key))
+    } else {
+        Ok(())
+    }
+}
+
+/// Whether a label or taint key or value uses only the characters Kubernetes allows. Keys may also
+/// contain a "/" separating a DNS prefix from the name.
+fn valid_label_part(part: &str, key: bool) -> bool {
+    part.len() <= 253 && part.chars().all(|c| {
+        c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' || (key && c == '/')
+    })
+}
+
+fn validate_volume_type(volume_type: &str) -> Result<(), String> {
+    if VOLUME_TYPES.contains(&volume_type) {
+        Ok(())
+    } else {
+        Err(format!("{} must be one of {}.", volume_type, VOLUME_TYPES.join(", ")))
+    }
+}
+
+fn hcl_string(value: &str) -> String {
+    to_json_string(value).expect("strings should always serialize to JSON")
+}
+
+fn hcl_list(values: &[String]) -> String {
+    format!("[{}]", values.iter().map(|value| hcl_string(value)).collect::<Vec<String>>().join(", "))
+}
+
+/// A map written on one line, which is valid as both HCL and TOML.
+fn hcl_map(values: &[(&str, String)]) -> String {
+    if values.is_empty() {
+        return "{}".to_owned();
+    }
+
+    format!("{{ {} }}", values.iter().map(|&(key, ref value)| {
+        format!("{} = {}", bare_or_quoted_key(key), hcl_string(value))
+    }).collect::<Vec<String>>().join(", "))
+}
+
+/// Tags as autoscaling group tags, which are also given to the instances the groups launch.
+fn hcl_asg_tags(tags: &BTreeMap<String, String>) -> String {
+    format!("[{}]", tags.iter().map(|(key, value)| {
+        format!(
+            "{{ key = {}, value = {}, propagate_at_launch = \"true\" }}",
+            hcl_string(key),
+            hcl_string(value),
+        )
+    }).collect::<Vec<String>>().join(", "))
+}
+
+/// A key for a map in HCL or TOML, which must be quoted unless it only has letters, digits, "_",
+/// and "-".
+fn bare_or_quoted_key(key: &str) -> String {
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        key.to_owned()
+    } else {
+        hcl_string(key)
+    }
+}
+
+/// Splits a comma-separated list, ignoring empty items.
+fn split_list(values: &str) -> Vec<String> {
+    values.split(',').filter(|value| !value.is_empty()).map(|value| value.to_owned()).collect()
+}
+
+pub fn toml_string(value: &str) -> String {
+    Value::String(value.to_owned()).to_string()
+}
+
+pub fn toml_list(values: &[String]) -> String {
+    Value::Array(values.iter().map(|value| Value::String(value.clone())).collect()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{ClusterConfig, NodePoolConfig, validate_spot_price, validate_tag, validate_taint};
+
+    fn network_problems(config: &ClusterConfig) -> Vec<String> {
+        let mut problems = vec![];
+
+        config.validate_network(&mut problems);
+
+        problems
+    }
+
+    fn network_config() -> ClusterConfig {
+        ClusterConfig {
+            availability_zones: vec!["us-east-1a".to_owned(), "us-east-1b".to_owned()],
+            cidr: "10.0.2.0/24".to_owned(),
+            ..ClusterConfig::default()
+        }
+    }
+
+    #[test]
+    fn validate_taint_accepts_keys_with_and_without_values() {
+        assert!(validate_taint("dedicated=batch:NoSchedule").is_ok());
+        assert!(validate_taint("example.com/gpu:PreferNoSchedule").is_ok());
+        assert!(validate_taint("dedicated=:NoExecute").is_ok());
+    }
+
+    #[test]
+    fn validate_taint_rejects_missing_or_unknown_effects() {
+        assert!(validate_taint("dedicated=batch").is_err());
+        assert!(validate_taint("dedicated=batch:").is_err());
+        assert!(validate_taint("dedicated=batch:NoRun").is_err());
+    }
+
+    #[test]
+    fn validate_taint_rejects_invalid_keys_and_values() {
+        assert!(validate_taint(":NoSchedule").is_err());
+        assert!(validate_taint("=batch:NoSchedule").is_err());
+        assert!(validate_taint("dedi cated=batch:NoSchedule").is_err());
+        assert!(validate_taint("dedicated=a/b:NoSchedule").is_err());
+    }
+
+    #[test]
+    fn validate_spot_price_accepts_positive_dollar_amounts() {
+        assert!(validate_spot_price("0.05").is_ok());
+        assert!(validate_spot_price("1").is_ok());
+        assert!(validate_spot_price(".5").is_ok());
+    }
+
+    #[test]
+    fn validate_spot_price_rejects_other_values() {
+        assert!(validate_spot_price("").is_err());
+        assert!(validate_spot_price("0").is_err());
+        assert!(validate_spot_price("0.00").is_err());
+        assert!(validate_spot_price("-0.05").is_err());
+        assert!(validate_spot_price("$0.05").is_err());
+        assert!(validate_spot_price("1e-2").is_err());
+        assert!(validate_spot_price("0.0.5").is_err());
+    }
+
+    #[test]
+    fn validate_tag_accepts_keys_and_values_within_aws_limits() {
+        assert!(validate_tag("team=platform").is_ok());
+        assert!(validate_tag("cost-center=").is_ok());
+        assert!(validate_tag("example.com/owner=ops@example.com").is_ok());
+        assert!(validate_tag("expr=a=b").is_ok());
+        assert!(validate_tag(&format!("{}=value", "k".repeat(128))).is_ok());
+    }
+
+    #[test]
+    fn validate_tag_rejects_malformed_or_oversized_tags() {
+        assert!(validate_tag("team").is_err());
+        assert!(validate_tag("=platform").is_err());
+        assert!(validate_tag(&format!("{}=value", "k".repeat(129))).is_err());
+        assert!(validate_tag(&format!("key={}", "v".repeat(257))).is_err());
+        assert!(validate_tag("team=plat#form").is_err());
+    }
+
+    #[test]
+    fn validate_tag_rejects_reserved_keys() {
+        assert!(validate_tag("aws:createdBy=kaws").is_err());
+        assert!(validate_tag("AWS:createdBy=kaws").is_err());
+        assert!(validate_tag("Name=cluster").is_err());
+        assert!(validate_tag("KubernetesCluster=cluster").is_err());
+    }
+
+    #[test]
+    fn spot_pools_divide_their_sizes_between_spot_groups() {
+        let pool = NodePoolConfig {
+            instance_type: "m4.large".to_owned(),
+            max_size: 10,
+            min_size: 2,
+            name: "batch".to_owned(),
+            spot: true,
+            spot_instance_types: vec![
+                "c4.large".to_owned(),
+                "m4.large".to_owned(),
+                "r4.large".to_owned(),
+            ],
+            spot_max_price: "0.05".to_owned(),
+            ..NodePoolConfig::default()
+        };
+
+        let sizes: Vec<(u32, u32)> = pool.groups().iter().map(|group| {
+            (group.min_size, group.max_size)
+        }).collect();
+
+        assert_eq!(sizes, vec![(1, 4), (1, 3), (0, 3), (0, 10)]);
+    }
+
+    #[test]
+    fn validate_network_accepts_separate_ranges() {
+        assert!(network_problems(&network_config()).is_empty());
+    }
+
+    #[test]
+    fn validate_network_rejects_overlapping_subnets() {
+        let config = ClusterConfig {
+            etcd_cidr: "10.0.0.128/25".to_owned(),
+            ..network_config()
+        };
+
+        assert_eq!(network_problems(&config), vec![
+            "balancers_cidr: 10.0.0.0/24 overlaps with etcd_cidr (10.0.0.128/25).".to_owned(),
+        ]);
+    }
+
+    #[test]
+    fn validate_network_rejects_pod_and_service_ranges_overlapping_other_networks() {
+        let config = ClusterConfig {
+            pod_cidr: "10.0.0.0/8".to_owned(),
+            service_cidr: "10.2.0.0/24".to_owned(),
+            dns_service_ip: "10.2.0.10".to_owned(),
+            ..network_config()
+        };
+
+        assert_eq!(network_problems(&config), vec![
+            "pod_cidr: 10.0.0.0/8 overlaps with vpc_cidr (10.0.0.0/16).".to_owned(),
+            "service_cidr: 10.2.0.0/24 overlaps with pod_cidr (10.0.0.0/8).".to_owned(),
+        ]);
+    }
+
+    #[test]
+    fn tfvars_are_read_back_as_the_settings_they_were_rendered_from() {
+        let mut tags = BTreeMap::new();
+
+        tags.insert("cost center".to_owned(), "k8s".to_owned());
+        tags.insert("team".to_owned(), "platform".to_owned());
+
+        let config = ClusterConfig {
+            availability_zones: vec![
+                "us-east-1a".to_owned(),
+                "us-east-1b".to_owned(),
+                "us-east-1c".to_owned(),
+            ],
+            aws_account_id: "123456789012".to_owned(),
+            balancers_cidr: "10.0.4.0/22".to_owned(),
+            cidr: "10.0.64.0/18".to_owned(),
+            coreos_ami: "ami-12345678".to_owned(),
+            domain: "example.com".to_owned(),
+            etcd_cidr: "10.0.8.0/22".to_owned(),
+            iam_users: vec!["alice".to_owned()],
+            instance_size: "m4.large".to_owned(),
+            kubernetes_version: "1.9.1".to_owned(),
+            node_pools: vec![
+                NodePoolConfig {
+                    instance_type: "r4.large".to_owned(),
+                    labels: vec!["workload=memory".to_owned()],
+                    max_size: 4,
+                    min_size: 1,
+                    name: "memory".to_owned(),
+                    taints: vec!["dedicated=memory:NoSchedule".to_owned()],
+                    ..NodePoolConfig::default()
+                },
+                NodePoolConfig {
+                    instance_type: "c4.large".to_owned(),
+                    max_size: 10,
+                    min_size: 2,
+                    name: "batch".to_owned(),
+                    spot: true,
+                    spot_instance_types: vec!["c4.large".to_owned(), "m4.large".to_owned()],
+                    spot_max_price: "0.05".to_owned(),
+                    ..NodePoolConfig::default()
+                },
+                NodePoolConfig {
+                    instance_type: "c4.xlarge".to_owned(),
+                    max_size: 5,
+                    min_size: 3,
+                    name: "spot".to_owned(),
+                    on_demand_fallback: false,
+                    spot: true,
+                    spot_instance_types: vec!["c4.xlarge".to_owned(), "m4.xlarge".to_owned()],
+                    spot_max_price: "0.1".to_owned(),
+                    ..NodePoolConfig::default()
+                },
+            ],
+            region: "us-east-1".to_owned(),
+            ssh_keys: vec!["ssh-ed25519 AAAA alice".to_owned()],
+            tags: tags,
+            zone_id: "Z123".to_owned(),
+            ..ClusterConfig::default()
+        };
+
+        let tfvars = config.to_tfvars("test", &[]).expect("tfvars should render");
+        let parsed = ClusterConfig::parse_tfvars("test", &tfvars).expect("tfvars should parse");
+
+        assert_eq!(parsed.availability_zones, config.availability_zones);
+        assert_eq!(parsed.balancers_cidr, config.balancers_cidr);
+        assert_eq!(parsed.cidr, config.cidr);
+        assert_eq!(parsed.etcd_cidr, config.etcd_cidr);
+        assert_eq!(parsed.tags, config.tags);
+
+        let pools = |config: &ClusterConfig| -> Vec<String> {
+            config.node_pools.iter().map(|pool| pool.to_toml()).collect()
+        };
+
+        assert_eq!(pools(&parsed), pools(&config));
+        assert_eq!(parsed.to_tfvars("test", &[]).expect("tfvars should render"), tfvars);
+    }
+}
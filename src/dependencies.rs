@@ -6,6 +6,24 @@ pub fn ensure_dependencies() -> KawsResult {
     ensure_cfssl().and(ensure_kubectl()).and(ensure_terraform())
 }
 
+/// Checks for the AWS CLI, which only the commands that replace instances need.
+pub fn ensure_aws_cli() -> KawsResult {
+    let installed = match Command::new("aws")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status() {
+            Ok(status) => status.success(),
+            Err(_) => false,
+    };
+
+    if installed {
+        Ok(None)
+    } else {
+        Err(KawsError::new("The AWS CLI (aws) must be installed".to_string()))
+    }
+}
+
 fn ensure_cfssl() -> KawsResult {
     let installed = match Command::new("cfssl")
         .arg("version")
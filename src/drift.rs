@@ -0,0 +1,275 @@
+use std::fs::read_dir;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use clap::ArgMatches;
+use rusoto_core::{AwsCredentials, ProvideAwsCredentials};
+use serde_json::to_string_pretty;
+
+use aws::{ResolvedCredentials, credentials_provider};
+use config::{ClusterConfig, tfvars_path};
+use error::{KawsError, KawsExitResult, Success};
+use runner::TerraformCommand;
+use terraform::Terraform;
+
+const DEFAULT_CONCURRENCY: usize = 4;
+
+pub struct Drift<'a> {
+    all: bool,
+    aws_credentials_path: Option<&'a str>,
+    aws_credentials_profile: Option<&'a str>,
+    clusters: Vec<&'a str>,
+    concurrency: usize,
+    detailed_exitcode: bool,
+    format: &'a str,
+}
+
+#[derive(Serialize)]
+struct ClusterDrift {
+    cluster: String,
+    status: &'static str,
+    changes: Vec<ResourceChange>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ResourceChange {
+    action: &'static str,
+    address: String,
+}
+
+impl<'a> Drift<'a> {
+    pub fn new(matches: &'a ArgMatches) -> Self {
+        Drift {
+            all: matches.is_present("all"),
+            aws_credentials_path: matches.value_of("aws-credentials-path"),
+            aws_credentials_profile: matches.value_of("aws-credentials-profile"),
+            clusters: matches.values_of("cluster").map_or(vec![], |values| values.collect()),
+            concurrency: matches.value_of("concurrency").map_or(DEFAULT_CONCURRENCY, |concurrency| {
+                concurrency.parse().expect("clap should have validated concurrency")
+            }),
+            detailed_exitcode: matches.is_present("detailed-exitcode"),
+            format: matches.value_of("format").unwrap_or("table"),
+        }
+    }
+
+    pub fn report(&self) -> KawsExitResult {
+        let clusters = if self.all {
+            all_clusters()?
+        } else {
+            self.clusters.iter().map(|cluster| cluster.to_string()).collect()
+        };
+
+        Terraform::init()?;
+
+        // Credentials are resolved up front, one cluster at a time, since resolving them may
+        // prompt for an MFA code. Each cluster's terraform.tfvars is rendered at the same time.
+        let mut jobs = vec![];
+
+        for cluster in clusters {
+            ClusterConfig::render_tfvars(&cluster)?;
+
+            let credentials = credentials_provider(
+                Some(&cluster),
+                None,
+                self.aws_credentials_path,
+                self.aws_credentials_profile,
+            ).credentials()?;
+
+            jobs.push((cluster, credentials));
+        }
+
+        let queue = Arc::new(Mutex::new(jobs));
+        let reports = Arc::new(Mutex::new(Vec::new()));
+
+        let workers: Vec<_> = (0..self.concurrency).map(|_| {
+            let queue = queue.clone();
+            let reports = reports.clone();
+
+            thread::spawn(move || {
+                loop {
+                    let (cluster, credentials) = match queue.lock().expect(
+                        "drift queue lock was poisoned"
+                    ).pop() {
+                        Some(job) => job,
+                        None => break,
+                    };
+
+                    let report = detect_drift(&cluster, credentials);
+
+                    reports.lock().expect("drift reports lock was poisoned").push(report);
+                }
+            })
+        }).collect();
+
+        for worker in workers {
+            if worker.join().is_err() {
+                return Err(KawsError::new("A drift detection worker panicked.".to_owned()));
+            }
+        }
+
+        let mut reports = match Arc::try_unwrap(reports) {
+            Ok(reports) => reports.into_inner().expect("drift reports lock was poisoned"),
+            Err(_) => return Err(KawsError::new("Drift detection workers are still running.".to_owned())),
+        };
+
+        reports.sort_by(|a, b| a.cluster.cmp(&b.cluster));
+
+        match self.format {
+            "json" => println!("{}", to_string_pretty(&reports)?),
+            "markdown" => print_markdown(&reports),
+            _ => print_table(&reports),
+        }
+
+        let failed = reports.iter().filter(|report| report.status == "error").count();
+        let drifted = reports.iter().filter(|report| report.status == "drifted").count();
+
+        if failed > 0 {
+            Err(KawsError::new(format!("Drift detection failed for {} cluster(s).", failed)))
+        } else if drifted > 0 && self.detailed_exitcode {
+            Ok(Success::with_exit_code(format!("{} cluster(s) have drifted.", drifted), 2))
+        } else {
+            Ok(Success::from(None))
+        }
+    }
+}
+
+fn all_clusters() -> Result<Vec<String>, KawsError> {
+    let mut clusters = vec![];
+
+    for entry in read_dir("clusters")? {
+        let entry = entry?;
+        let cluster = entry.file_name().to_string_lossy().into_owned();
+
+        if ClusterConfig::exists(&cluster) || Path::new(&tfvars_path(&cluster)).exists() {
+            clusters.push(cluster);
+        }
+    }
+
+    if clusters.is_empty() {
+        return Err(KawsError::new("No clusters were found in the clusters directory.".to_owned()));
+    }
+
+    Ok(clusters)
+}
+
+fn detect_drift(cluster: &str, credentials: AwsCredentials) -> ClusterDrift {
+    let credentials = ResolvedCredentials(credentials);
+
+    let output = TerraformCommand::new("plan")
+        .args(&["-detailed-exitcode", "-input=false", "-module-depth=-1", "-no-color"])
+        .state(cluster)
+        .var_file(cluster)
+        .config()
+        .credentials(&credentials)
+        .output();
+
+    let output = match output {
+        Ok(output) => output,
+        Err(error) => return ClusterDrift {
+            cluster: cluster.to_owned(),
+            status: "error",
+            changes: vec![],
+            error: Some(format!("{}", error)),
+        },
+    };
+
+    match output.status.code() {
+        Some(0) => ClusterDrift {
+            cluster: cluster.to_owned(),
+            status: "clean",
+            changes: vec![],
+            error: None,
+        },
+        Some(2) => ClusterDrift {
+            cluster: cluster.to_owned(),
+            status: "drifted",
+            changes: parse_plan(&String::from_utf8_lossy(&output.stdout)),
+            error: None,
+        },
+        _ => ClusterDrift {
+            cluster: cluster.to_owned(),
+            status: "error",
+            changes: vec![],
+            error: Some(String::from_utf8_lossy(&output.stderr).trim().to_owned()),
+        },
+    }
+}
+
+/// Extracts the resources that would change from the output of `terraform plan -no-color`, where
+/// each change is a line such as `~ module.kaws.aws_instance.etcd_01`.
+fn parse_plan(plan: &str) -> Vec<ResourceChange> {
+    let actions = [
+        ("-/+ ", "replace"),
+        ("+ ", "create"),
+        ("- ", "destroy"),
+        ("~ ", "update"),
+    ];
+
+    plan.lines().filter_map(|line| {
+        let line = line.trim();
+
+        actions.iter().find(|&&(prefix, _)| line.starts_with(prefix)).and_then(|&(prefix, action)| {
+            let address = line[prefix.len()..].split_whitespace().next().unwrap_or("");
+
+            if address.starts_with("module.") {
+                Some(ResourceChange {
+                    action: action,
+                    address: address.to_owned(),
+                })
+            } else {
+                None
+            }
+        })
+    }).collect()
+}
+
+fn print_markdown(reports: &[ClusterDrift]) {
+    println!("| Cluster | Status | Changes |");
+    println!("| --- | --- | --- |");
+
+    for report in reports {
+        println!("| {} | {} | {} |", report.cluster, report.status, report.changes.len());
+    }
+
+    for report in reports.iter().filter(|report| report.status != "clean") {
+        println!("\n### {}\n", report.cluster);
+
+        if let Some(ref error) = report.error {
+            println!("```\n{}\n```", error);
+        }
+
+        for change in &report.changes {
+            println!("* `{}` ({})", change.address, change.action);
+        }
+    }
+}
+
+fn print_table(reports: &[ClusterDrift]) {
+    let width = reports.iter().map(|report| report.cluster.len()).max().unwrap_or(0).max(7) + 2;
+
+    println!("{:<width$}{:<10}CHANGES", "CLUSTER", "STATUS", width = width);
+
+    for report in reports {
+        println!(
+            "{:<width$}{:<10}{}",
+            report.cluster,
+            report.status,
+            report.changes.len(),
+            width = width,
+        );
+    }
+
+    for report in reports.iter().filter(|report| report.status != "clean") {
+        println!("\n{}:", report.cluster);
+
+        if let Some(ref error) = report.error {
+            println!("{}", error);
+        }
+
+        for change in &report.changes {
+            println!("  {:<8}{}", change.action, change.address);
+        }
+    }
+}
@@ -2,8 +2,9 @@ use std::fs::{File, remove_file};
 use std::io::{ErrorKind, Read, Write};
 
 use hyper::Client as HyperClient;
+use openssl::rand::rand_bytes;
+use openssl::symm::{Cipher, decrypt, encrypt};
 use rusoto_core::{
-    ChainProvider,
     DispatchSignedRequest,
     ProvideAwsCredentials,
     Region,
@@ -16,11 +17,16 @@ use rusoto_kms::{
     EncryptError,
     EncryptRequest,
     EncryptResponse,
+    GenerateDataKeyError,
+    GenerateDataKeyRequest,
+    GenerateDataKeyResponse,
     Kms,
     KmsClient,
 };
 use rustc_serialize::base64::{FromBase64, STANDARD, ToBase64};
+use serde_json::{from_str, to_vec};
 
+use aws::CredentialsProvider;
 use error::{KawsError, KawsResult};
 
 pub struct Encryptor<'a, P, D> where P: ProvideAwsCredentials, D: DispatchSignedRequest {
@@ -29,12 +35,20 @@ pub struct Encryptor<'a, P, D> where P: ProvideAwsCredentials, D: DispatchSigned
     kms_master_key_id: Option<&'a str>,
 }
 
-impl<'a> Encryptor<'a, ChainProvider, HyperClient> {
+/// Data too large to be encrypted by KMS directly, encrypted locally with a KMS data key.
+#[derive(Deserialize, Serialize)]
+struct Envelope {
+    encrypted_key: String,
+    iv: String,
+    ciphertext: String,
+}
+
+impl<'a> Encryptor<'a, CredentialsProvider, HyperClient> {
     pub fn new(
-        provider: ChainProvider,
+        provider: CredentialsProvider,
         region: Region,
         kms_master_key_id: Option<&'a str>,
-    ) -> Encryptor<'a, ChainProvider, HyperClient> {
+    ) -> Encryptor<'a, CredentialsProvider, HyperClient> {
         Encryptor {
             client: KmsClient::new(
                 default_tls_client().expect("failed to create HTTP client with TLS"),
@@ -57,8 +71,8 @@ impl<'a> Encryptor<'a, ChainProvider, HyperClient> {
         let decrypted_data = self.decrypt(encrypted_data)?;
 
         match decrypted_data.plaintext {
-            Some(plaintext) => return Ok(plaintext),
-            None => return Err(KawsError::new("No plaintext was returned from KMS".to_owned())),
+            Some(plaintext) => Ok(plaintext),
+            None => Err(KawsError::new("No plaintext was returned from KMS".to_owned())),
         }
     }
 
@@ -78,9 +92,58 @@ impl<'a> Encryptor<'a, ChainProvider, HyperClient> {
         Ok(None)
     }
 
+    /// Encrypts data of any size using envelope encryption: the data is encrypted locally with a
+    /// new data key from KMS, and only the KMS-encrypted copy of that key is written alongside it.
+    pub fn envelope_encrypt_and_write_file(&mut self, data: &[u8], file_path: &str) -> KawsResult {
+        let data_key = self.generate_data_key()?;
+
+        let (plaintext_key, encrypted_key) = match (data_key.plaintext, data_key.ciphertext_blob) {
+            (Some(plaintext_key), Some(encrypted_key)) => (plaintext_key, encrypted_key),
+            _ => return Err(KawsError::new("No data key was returned from KMS".to_owned())),
+        };
+
+        let mut iv = vec![0; 16];
+        rand_bytes(&mut iv)?;
+
+        let ciphertext = encrypt(Cipher::aes_256_cbc(), &plaintext_key, Some(&iv), data)?;
+
+        let envelope = Envelope {
+            encrypted_key: encrypted_key.to_base64(STANDARD),
+            iv: iv.to_base64(STANDARD),
+            ciphertext: ciphertext.to_base64(STANDARD),
+        };
+
+        let mut file = File::create(file_path)?;
+        file.write_all(&to_vec(&envelope)?)?;
+
+        Ok(None)
+    }
+
+    pub fn envelope_decrypt_file(&mut self, source: &str) -> Result<Vec<u8>, KawsError> {
+        let mut src = File::open(source)?;
+
+        let mut contents = String::new();
+
+        src.read_to_string(&mut contents)?;
+
+        let envelope: Envelope = from_str(&contents)?;
+
+        let plaintext_key = match self.decrypt(envelope.encrypted_key.from_base64()?)?.plaintext {
+            Some(plaintext_key) => plaintext_key,
+            None => return Err(KawsError::new("No plaintext was returned from KMS".to_owned())),
+        };
+
+        Ok(decrypt(
+            Cipher::aes_256_cbc(),
+            &plaintext_key,
+            Some(&envelope.iv.from_base64()?),
+            &envelope.ciphertext.from_base64()?,
+        )?)
+    }
+
     // Private
 
-    fn decrypt<'b>(&mut self, encrypted_data: Vec<u8>) -> Result<DecryptResponse, DecryptError> {
+    fn decrypt(&mut self, encrypted_data: Vec<u8>) -> Result<DecryptResponse, DecryptError> {
         let request = DecryptRequest {
             encryption_context: None,
             grant_tokens: None,
@@ -90,7 +153,7 @@ impl<'a> Encryptor<'a, ChainProvider, HyperClient> {
         self.client.decrypt(&request)
     }
 
-    fn encrypt<'b>(&mut self, decrypted_data: Vec<u8>) -> Result<EncryptResponse, EncryptError> {
+    fn encrypt(&mut self, decrypted_data: Vec<u8>) -> Result<EncryptResponse, EncryptError> {
         let request = EncryptRequest {
             plaintext: decrypted_data,
             encryption_context: None,
@@ -101,6 +164,18 @@ impl<'a> Encryptor<'a, ChainProvider, HyperClient> {
         self.client.encrypt(&request)
     }
 
+    fn generate_data_key(&mut self) -> Result<GenerateDataKeyResponse, GenerateDataKeyError> {
+        let request = GenerateDataKeyRequest {
+            encryption_context: None,
+            grant_tokens: None,
+            key_id: self.kms_master_key_id.expect("KMS key must be supplied to encrypt").to_owned(),
+            key_spec: Some("AES_256".to_owned()),
+            number_of_bytes: None,
+        };
+
+        self.client.generate_data_key(&request)
+    }
+
 }
 
 impl<'a, P, D> Drop for Encryptor<'a, P, D>
@@ -3,12 +3,14 @@ use std::fmt::{Debug, Display, Formatter};
 use std::fmt::Error as FmtError;
 use std::str::Utf8Error;
 
-use rusoto_core::ParseRegionError;
-use rusoto_kms::{DecryptError, EncryptError};
+use openssl::error::ErrorStack;
+use rusoto_core::{CredentialsError, ParseRegionError};
+use rusoto_kms::{DecryptError, EncryptError, GenerateDataKeyError};
 use rustc_serialize::base64::FromBase64Error;
 use serde_json::Error as SerdeJsonError;
 
 pub struct KawsError {
+    exit_code: i32,
     message: String,
     stderr: Option<String>,
     stdout: Option<String>,
@@ -17,6 +19,7 @@ pub struct KawsError {
 impl KawsError {
     pub fn new(message: String) -> KawsError {
         KawsError {
+            exit_code: 1,
             message: message,
             stderr: None,
             stdout: None,
@@ -25,11 +28,25 @@ impl KawsError {
 
     pub fn with_std_streams(message: String, stdout: String, stderr: String) -> KawsError {
         KawsError {
+            exit_code: 1,
             message: message,
             stderr: Some(stderr),
             stdout: Some(stdout),
         }
     }
+
+    pub fn with_exit_code(message: String, exit_code: i32) -> KawsError {
+        KawsError {
+            exit_code: exit_code,
+            message: message,
+            stderr: None,
+            stdout: None,
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
 }
 
 impl Debug for KawsError {
@@ -40,7 +57,7 @@ impl Debug for KawsError {
 
 impl Display for KawsError {
     fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
-        if self.stdout.is_some() && self.stderr.is_some() {
+        if let (Some(stdout), Some(stderr)) = (&self.stdout, &self.stderr) {
             write!(f,
                 "{}
 
@@ -52,8 +69,8 @@ impl Display for KawsError {
                 stderr:
                 {}",
                 self.message,
-                self.stdout.as_ref().expect("accessing self.stdout"),
-                self.stderr.as_ref().expect("accessing self.stderr")
+                stdout,
+                stderr
             )
         } else {
             write!(f, "{}", self.message)
@@ -79,6 +96,12 @@ impl From<Utf8Error> for KawsError {
     }
 }
 
+impl From<CredentialsError> for KawsError {
+    fn from(error: CredentialsError) -> Self {
+        KawsError::new(format!("{}", error))
+    }
+}
+
 impl From<DecryptError> for KawsError {
     fn from(error: DecryptError) -> Self {
         KawsError::new(format!("{}", error))
@@ -91,12 +114,24 @@ impl From<EncryptError> for KawsError {
     }
 }
 
+impl From<ErrorStack> for KawsError {
+    fn from(error: ErrorStack) -> Self {
+        KawsError::new(format!("{}", error))
+    }
+}
+
 impl From<FromBase64Error> for KawsError {
     fn from(error: FromBase64Error) -> Self {
         KawsError::new(format!("{}", error))
     }
 }
 
+impl From<GenerateDataKeyError> for KawsError {
+    fn from(error: GenerateDataKeyError) -> Self {
+        KawsError::new(format!("{}", error))
+    }
+}
+
 impl From<ParseRegionError> for KawsError {
     fn from(error: ParseRegionError) -> Self {
         KawsError::new(format!("{}", error))
@@ -109,4 +144,39 @@ impl From<SerdeJsonError> for KawsError {
     }
 }
 
+/// The result of a command that succeeded, with the exit code kaws exits with. A non-zero code
+/// reports an outcome that is not a failure, such as a plan that contains changes.
+pub struct Success {
+    exit_code: i32,
+    message: Option<String>,
+}
+
+impl Success {
+    pub fn with_exit_code(message: String, exit_code: i32) -> Success {
+        Success {
+            exit_code: exit_code,
+            message: Some(message),
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+impl From<Option<String>> for Success {
+    fn from(message: Option<String>) -> Self {
+        Success {
+            exit_code: 0,
+            message: message,
+        }
+    }
+}
+
 pub type KawsResult = Result<Option<String>, KawsError>;
+
+pub type KawsExitResult = Result<Success, KawsError>;
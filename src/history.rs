@@ -0,0 +1,301 @@
+use std::fs::{create_dir_all, read_dir, remove_file, File, OpenOptions};
+use std::io::{copy, ErrorKind, Read, Write};
+use std::path::Path;
+
+use clap::ArgMatches;
+
+use aws::{CredentialsProvider, credentials_provider};
+use config::{ClusterConfig, StateHistoryConfig};
+use encryption::Encryptor;
+use error::{KawsError, KawsResult};
+use state::{State, format_timestamp, state_path, timestamp};
+use terraform::Outputs;
+
+const ENCRYPTED_SUFFIX: &'static str = ".tfstate.encrypted.json";
+const PLAINTEXT_SUFFIX: &'static str = ".tfstate";
+
+pub struct StateHistory<'a> {
+    aws_credentials_provider: CredentialsProvider,
+    cluster: &'a str,
+    region: Option<&'a str>,
+    snapshot: Option<&'a str>,
+}
+
+struct Snapshot {
+    encrypted: bool,
+    id: String,
+    operation: String,
+    path: String,
+    /// Distinguishes snapshots taken within the same second, starting from 0.
+    sequence: u32,
+    timestamp: u64,
+}
+
+impl<'a> StateHistory<'a> {
+    pub fn new(matches: &'a ArgMatches) -> Self {
+        Self::with_credentials_provider(matches, credentials_provider(
+            matches.value_of("cluster"),
+            matches.value_of("region"),
+            matches.value_of("aws-credentials-path"),
+            matches.value_of("aws-credentials-profile"),
+        ))
+    }
+
+    /// Creates a state history that shares the given credentials provider, and with it any
+    /// credentials the provider has already resolved, with the command taking the snapshots.
+    pub fn with_credentials_provider(
+        matches: &'a ArgMatches,
+        aws_credentials_provider: CredentialsProvider,
+    ) -> Self {
+        StateHistory {
+            aws_credentials_provider: aws_credentials_provider,
+            cluster: matches.value_of("cluster").expect("clap should have required cluster"),
+            region: matches.value_of("region"),
+            snapshot: matches.value_of("snapshot"),
+        }
+    }
+
+    pub fn history(&self) -> KawsResult {
+        let snapshots = self.snapshots()?;
+
+        if snapshots.is_empty() {
+            return Ok(Some(format!("No state snapshots found for cluster \"{}\".", self.cluster)));
+        }
+
+        println!("{:<28}{:<28}{:<12}ENCRYPTED", "SNAPSHOT", "TAKEN", "BEFORE");
+
+        for snapshot in snapshots.iter().rev() {
+            println!(
+                "{:<28}{:<28}{:<12}{}",
+                snapshot.id,
+                format_timestamp(snapshot.timestamp),
+                snapshot.operation,
+                if snapshot.encrypted { "yes" } else { "no" },
+            );
+        }
+
+        Ok(None)
+    }
+
+    pub fn restore(&self) -> KawsResult {
+        let id = self.snapshot.expect("clap should have required snapshot");
+
+        let snapshot = match self.snapshots()?.into_iter().find(|snapshot| snapshot.id == id) {
+            Some(snapshot) => snapshot,
+            None => return Err(KawsError::new(format!(
+                "No state snapshot \"{}\" exists for cluster \"{}\". \
+                Run `kaws cluster state history {}` to list the available snapshots.",
+                id,
+                self.cluster,
+                self.cluster,
+            ))),
+        };
+
+        let contents = if snapshot.encrypted {
+            let region = match self.region {
+                Some(region) => region.to_owned(),
+                None => Outputs::load(self.cluster).map_err(|error| KawsError::new(format!(
+                    "Unable to determine the cluster's region ({}). Specify it with --region.",
+                    error,
+                )))?.region,
+            };
+
+            let mut encryptor = Encryptor::new(
+                self.aws_credentials_provider.clone(),
+                region.parse()?,
+                None,
+            );
+
+            encryptor.envelope_decrypt_file(&snapshot.path)?
+        } else {
+            let mut contents = Vec::new();
+
+            File::open(&snapshot.path)?.read_to_end(&mut contents)?;
+
+            contents
+        };
+
+        self.save("restore")?;
+
+        log_wrap!("Writing restored Terraform state", {
+            File::create(state_path(self.cluster))?.write_all(&contents)?;
+        });
+
+        Ok(Some(format!(
+            "Restored state snapshot \"{}\" for cluster \"{}\". Commit clusters/{} to Git.",
+            id,
+            self.cluster,
+            self.cluster,
+        )))
+    }
+
+    /// Saves a copy of the cluster's current Terraform state before `operation` changes it, then
+    /// removes the oldest snapshots beyond the retention limit in the cluster's `kaws.toml`.
+    pub fn save(&self, operation: &str) -> KawsResult {
+        let state_path = state_path(self.cluster);
+
+        if !Path::new(&state_path).exists() {
+            return Ok(None);
+        }
+
+        let config = self.config()?;
+
+        create_dir_all(self.path())?;
+
+        let id = self.next_id(operation)?;
+
+        if config.encrypt {
+            // The cluster's KMS key is created by its first apply, so only a state without the
+            // key's output is saved unencrypted.
+            if State::load(self.cluster)?.has_output("pki_kms_key") {
+                let outputs = Outputs::load(self.cluster)?;
+                let mut contents = Vec::new();
+
+                File::open(&state_path)?.read_to_end(&mut contents)?;
+
+                let mut encryptor = Encryptor::new(
+                    self.aws_credentials_provider.clone(),
+                    outputs.region.parse()?,
+                    Some(&outputs.pki_kms_key),
+                );
+
+                log_wrap!("Saving encrypted state snapshot", {
+                    encryptor.envelope_encrypt_and_write_file(
+                        &contents,
+                        &self.new_snapshot_path(&id, ENCRYPTED_SUFFIX)?,
+                    )?;
+                });
+
+                return self.prune(config.limit as usize);
+            }
+
+            println!(
+                "The cluster's KMS key is not in the Terraform state yet, so the state snapshot \
+                taken before this {} will not be encrypted.",
+                operation,
+            );
+        }
+
+        log_wrap!("Saving state snapshot", {
+            let path = self.new_snapshot_path(&id, PLAINTEXT_SUFFIX)?;
+
+            copy(
+                &mut File::open(&state_path)?,
+                &mut OpenOptions::new().write(true).create_new(true).open(path)?,
+            )?;
+        });
+
+        self.prune(config.limit as usize)
+    }
+
+    // Private
+
+    /// The cluster's state history settings. Clusters without a `kaws.toml` use the defaults.
+    fn config(&self) -> Result<StateHistoryConfig, KawsError> {
+        if ClusterConfig::exists(self.cluster) {
+            Ok(ClusterConfig::load(self.cluster)?.state_history)
+        } else {
+            Ok(StateHistoryConfig::default())
+        }
+    }
+
+    /// The ID of a new snapshot taken before `operation`: the current Unix timestamp, followed by
+    /// a sequence number if other snapshots were taken within the same second.
+    fn next_id(&self, operation: &str) -> Result<String, KawsError> {
+        let timestamp = timestamp();
+
+        let sequence = self.snapshots()?.iter().filter(|snapshot| {
+            snapshot.timestamp == timestamp
+        }).map(|snapshot| snapshot.sequence + 1).max().unwrap_or(0);
+
+        if sequence == 0 {
+            Ok(format!("{}-{}", timestamp, operation))
+        } else {
+            Ok(format!("{}.{}-{}", timestamp, sequence, operation))
+        }
+    }
+
+    /// The path for a new snapshot file, which must not exist yet.
+    fn new_snapshot_path(&self, id: &str, suffix: &str) -> Result<String, KawsError> {
+        let path = format!("{}/{}{}", self.path(), id, suffix);
+
+        if Path::new(&path).exists() {
+            return Err(KawsError::new(format!(
+                "State snapshot {} already exists and will not be overwritten.",
+                path,
+            )));
+        }
+
+        Ok(path)
+    }
+
+    fn path(&self) -> String {
+        format!("clusters/{}/state-history", self.cluster)
+    }
+
+    fn prune(&self, limit: usize) -> KawsResult {
+        let snapshots = self.snapshots()?;
+
+        if snapshots.len() > limit {
+            for snapshot in &snapshots[..snapshots.len() - limit] {
+                log_wrap!(&format!("Removing expired state snapshot {}", snapshot.id), {
+                    remove_file(&snapshot.path)?;
+                });
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the cluster's state snapshots, oldest first.
+    fn snapshots(&self) -> Result<Vec<Snapshot>, KawsError> {
+        let entries = match read_dir(self.path()) {
+            Ok(entries) => entries,
+            Err(ref error) if error.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut snapshots = vec![];
+
+        for entry in entries {
+            let file_name = entry?.file_name().to_string_lossy().into_owned();
+
+            let (id, encrypted) = if file_name.ends_with(ENCRYPTED_SUFFIX) {
+                (&file_name[..file_name.len() - ENCRYPTED_SUFFIX.len()], true)
+            } else if file_name.ends_with(PLAINTEXT_SUFFIX) {
+                (&file_name[..file_name.len() - PLAINTEXT_SUFFIX.len()], false)
+            } else {
+                continue;
+            };
+
+            let mut parts = id.splitn(2, '-');
+            let mut taken = parts.next().unwrap_or("").splitn(2, '.');
+
+            let timestamp = match taken.next().and_then(|timestamp| timestamp.parse().ok()) {
+                Some(timestamp) => timestamp,
+                None => continue,
+            };
+
+            let sequence = match taken.next().map(|sequence| sequence.parse()) {
+                Some(Ok(sequence)) => sequence,
+                Some(Err(_)) => continue,
+                None => 0,
+            };
+
+            snapshots.push(Snapshot {
+                encrypted: encrypted,
+                id: id.to_owned(),
+                operation: parts.next().unwrap_or("unknown").to_owned(),
+                path: format!("{}/{}", self.path(), file_name),
+                sequence: sequence,
+                timestamp: timestamp,
+            });
+        }
+
+        snapshots.sort_by(|a, b| {
+            (a.timestamp, a.sequence, &a.id).cmp(&(b.timestamp, b.sequence, &b.id))
+        });
+
+        Ok(snapshots)
+    }
+}
@@ -1,3 +1,7 @@
+// kaws spells out `'static` on constants and names struct fields even when a variable of the same
+// name is assigned to them.
+#![allow(clippy::redundant_field_names, clippy::redundant_static_lifetimes)]
+
 extern crate ansi_term;
 extern crate bitstring;
 extern crate env_logger;
@@ -6,8 +10,10 @@ extern crate clap;
 #[macro_use]
 extern crate log;
 extern crate hyper;
+extern crate openssl;
 extern crate rusoto_core;
 extern crate rusoto_kms;
+extern crate rusoto_sts;
 extern crate rustc_serialize;
 extern crate serde;
 #[macro_use]
@@ -15,6 +21,7 @@ extern crate serde_derive;
 #[macro_use]
 extern crate serde_json;
 extern crate tempdir;
+extern crate toml;
 
 macro_rules! log_wrap {
     ($m:expr, $b:block) => {
@@ -25,56 +32,102 @@ macro_rules! log_wrap {
 }
 
 mod admin;
+mod ami;
+mod autoscaling;
 mod aws;
 mod cli;
+mod cloudconfig;
 mod cluster;
+mod config;
 mod dependencies;
+mod drift;
 mod encryption;
 mod error;
+mod history;
+mod nodepool;
 mod pki;
 mod process;
+mod prompt;
 mod repository;
+mod resources;
+mod rollout;
+mod runner;
+mod settings;
+mod state;
 mod terraform;
+mod upgrade;
+mod version;
 
 use std::process::exit;
 
 use ansi_term::Colour::{Green, Red};
+use clap::ArgMatches;
 
 use admin::Admin;
-use cluster::{ExistingCluster, NewCluster};
-use dependencies::ensure_dependencies;
-use error::KawsResult;
+use ami::AmiUpdate;
+use cloudconfig::CloudConfigPreview;
+use cluster::{ClusterProtection, ExistingCluster, NewCluster};
+use config::ClusterConfig;
+use dependencies::{ensure_aws_cli, ensure_dependencies};
+use drift::Drift;
+use error::{KawsExitResult, KawsResult, Success};
+use history::StateHistory;
+use nodepool::NodePools;
 use repository::Repository;
+use rollout::Roll;
+use settings::ClusterSettings;
 use terraform::Terraform;
+use upgrade::Upgrade;
 
 fn main() {
     env_logger::init().expect("Failed to initialize logger.");
 
-    let mut failed = false;
-
-    match execute_cli() {
+    let exit_code = match execute_cli() {
         Ok(success) => {
-            if let Some(message) = success {
+            if let Some(message) = success.message() {
                 println!("{}", Green.paint(message.to_string()));
             }
+
+            success.exit_code()
         },
         Err(error) => {
             let error_output = format!("Error:\n{}", error);
 
             println!("{}", Red.paint(error_output));
 
-            failed = true;
+            error.exit_code()
         },
-    }
+    };
 
-    if failed {
-        exit(1);
+    if exit_code != 0 {
+        exit(exit_code);
     }
 }
 
-fn execute_cli() -> KawsResult {
+fn execute_cli() -> KawsExitResult {
     let app_matches = cli::app().get_matches();
 
+    // Commands that report their outcome through the exit code even when they succeed.
+    if let ("cluster", Some(cluster_matches)) = app_matches.subcommand() {
+        match cluster_matches.subcommand() {
+            ("drift", Some(matches)) => {
+                ensure_dependencies()?;
+
+                return Drift::new(matches).report();
+            }
+            ("plan", Some(matches)) => {
+                ensure_dependencies()?;
+
+                return Terraform::new(matches).plan();
+            }
+            _ => {}
+        }
+    }
+
+    execute_command(&app_matches).map(Success::from)
+}
+
+fn execute_command(app_matches: &ArgMatches) -> KawsResult {
     match app_matches.subcommand() {
         ("admin", Some(admin_matches)) => {
             ensure_dependencies()?;
@@ -94,8 +147,12 @@ fn execute_cli() -> KawsResult {
             ensure_dependencies()?;
 
             match cluster_matches.subcommand() {
+                ("ami-update", Some(matches)) => AmiUpdate::new(matches).update(),
                 ("apply", Some(matches)) => Terraform::new(matches).apply(),
+                ("cloud-config", Some(matches)) => CloudConfigPreview::new(matches).preview(),
                 ("destroy", Some(matches)) => Terraform::new(matches).destroy(),
+                ("get", Some(matches)) => ClusterSettings::new(matches).get(),
+                ("import", Some(matches)) => Terraform::new(matches).import(),
                 ("init", Some(matches)) => NewCluster::new(matches).init(),
                 ("generate-pki", Some(generate_pki_matches)) => {
                     match generate_pki_matches.subcommand() {
@@ -118,9 +175,54 @@ fn execute_cli() -> KawsResult {
                         }
                     }
                 }
+                ("migrate-config", Some(matches)) => ClusterConfig::migrate(
+                    matches.value_of("cluster").expect("clap should have required cluster"),
+                ),
+                ("nodepool", Some(nodepool_matches)) => {
+                    match nodepool_matches.subcommand() {
+                        ("add", Some(matches)) => NodePools::new(matches).add(),
+                        ("list", Some(matches)) => NodePools::new(matches).list(),
+                        ("remove", Some(matches)) => NodePools::new(matches).remove(),
+                        _ => {
+                            println!("{}", nodepool_matches.usage());
+
+                            Ok(None)
+                        }
+                    }
+                }
                 ("output", Some(matches)) => Terraform::new(matches).output(),
-                ("plan", Some(matches)) => Terraform::new(matches).plan(),
+                ("protect", Some(matches)) => ClusterProtection::new(
+                    matches.value_of("cluster").expect("clap should have required cluster"),
+                ).enable(),
                 ("refresh", Some(matches)) => Terraform::new(matches).refresh(),
+                ("roll", Some(matches)) => {
+                    ensure_aws_cli()?;
+
+                    Roll::new(matches).roll()
+                }
+                ("set", Some(matches)) => ClusterSettings::new(matches).set(),
+                ("state", Some(state_matches)) => {
+                    match state_matches.subcommand() {
+                        ("history", Some(matches)) => StateHistory::new(matches).history(),
+                        ("mv", Some(matches)) => Terraform::new(matches).state_mv(),
+                        ("restore", Some(matches)) => StateHistory::new(matches).restore(),
+                        ("rm", Some(matches)) => Terraform::new(matches).state_rm(),
+                        _ => {
+                            println!("{}", state_matches.usage());
+
+                            Ok(None)
+                        }
+                    }
+                }
+                ("taint", Some(matches)) => Terraform::new(matches).taint(),
+                ("unprotect", Some(matches)) => ClusterProtection::new(
+                    matches.value_of("cluster").expect("clap should have required cluster"),
+                ).disable(),
+                ("upgrade", Some(matches)) => {
+                    ensure_aws_cli()?;
+
+                    Upgrade::new(matches).upgrade()
+                }
                 _ => {
                     println!("{}", cluster_matches.usage());
 
@@ -0,0 +1,285 @@
+use std::fs::File;
+use std::io::{Read, Write};
+
+use clap::ArgMatches;
+use toml::{Value, from_str};
+
+use cluster::parse_size;
+use config::{ClusterConfig, NODE_POOL_LABEL, NodePoolConfig, config_path};
+use error::{KawsError, KawsResult};
+use prompt::confirm;
+
+/// Adds, removes, and lists a cluster's node pools.
+///
+/// Each pool is a `[[node_pools]]` table in `kaws.toml`. Tables are appended to or removed from the
+/// file as text, so comments and formatting elsewhere in the file are preserved.
+pub struct NodePools<'a> {
+    cluster: &'a str,
+    instance_type: Option<&'a str>,
+    labels: Vec<&'a str>,
+    max_size: Option<&'a str>,
+    min_size: Option<&'a str>,
+    name: Option<&'a str>,
+    no_on_demand_fallback: bool,
+    spot: bool,
+    spot_instance_types: Vec<&'a str>,
+    spot_max_price: Option<&'a str>,
+    taints: Vec<&'a str>,
+}
+
+impl<'a> NodePools<'a> {
+    pub fn new(matches: &'a ArgMatches) -> Self {
+        NodePools {
+            cluster: matches.value_of("cluster").expect("clap should have required cluster"),
+            instance_type: matches.value_of("instance-type"),
+            labels: matches.values_of("label").map_or(vec![], |values| values.collect()),
+            max_size: matches.value_of("max-size"),
+            min_size: matches.value_of("min-size"),
+            name: matches.value_of("pool"),
+            no_on_demand_fallback: matches.is_present("no-on-demand-fallback"),
+            spot: matches.is_present("spot"),
+            spot_instance_types: matches.values_of("spot-instance-type").map_or(vec![], |values| {
+                values.collect()
+            }),
+            spot_max_price: matches.value_of("spot-max-price"),
+            taints: matches.values_of("taint").map_or(vec![], |values| values.collect()),
+        }
+    }
+
+    pub fn add(&self) -> KawsResult {
+        let name = self.name.expect("clap should have required pool");
+        let config = ClusterConfig::load(self.cluster)?;
+
+        if config.node_pools.iter().any(|pool| pool.name == name) {
+            return Err(KawsError::new(format!(
+                "Cluster \"{}\" already has a node pool named \"{}\".",
+                self.cluster,
+                name,
+            )));
+        }
+
+        let pool = NodePoolConfig {
+            instance_type: self.instance_type.expect("clap should have required instance-type").to_owned(),
+            labels: self.labels.iter().map(|label| label.to_string()).collect(),
+            max_size: parse_size("max-size", self.max_size.expect("clap should have required max-size"))?,
+            min_size: parse_size("min-size", self.min_size.expect("clap should have required min-size"))?,
+            name: name.to_owned(),
+            on_demand_fallback: !self.no_on_demand_fallback,
+            spot: self.spot,
+            spot_instance_types: self.spot_instance_types.iter().map(|instance_type| {
+                instance_type.to_string()
+            }).collect(),
+            spot_max_price: self.spot_max_price.unwrap_or("").to_owned(),
+            taints: self.taints.iter().map(|taint| taint.to_string()).collect(),
+        };
+
+        let mut contents = self.read()?;
+
+        if !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+
+        contents.push('\n');
+        contents.push_str(&pool.to_toml());
+
+        self.save(&contents, config.node_pools.len() + 1)?;
+
+        Ok(Some(format!(
+            "Added node pool \"{}\" to {}. Run `kaws cluster plan {}` to see the effect of the \
+            change, and commit clusters/{} to Git.",
+            name,
+            config_path(self.cluster),
+            self.cluster,
+            self.cluster,
+        )))
+    }
+
+    pub fn list(&self) -> KawsResult {
+        let config = ClusterConfig::load(self.cluster)?;
+
+        println!(
+            "default: {}, {} to {} nodes (the [nodes] group)",
+            if config.nodes.instance_type.is_empty() {
+                &config.instance_size
+            } else {
+                &config.nodes.instance_type
+            },
+            config.nodes.min_size,
+            config.nodes.max_size,
+        );
+
+        for pool in config.node_pools.iter() {
+            println!(
+                "{}: {}, {} to {} nodes",
+                pool.name,
+                pool.instance_type,
+                pool.min_size,
+                pool.max_size,
+            );
+
+            if pool.spot {
+                println!(
+                    "  spot: {} at up to ${} per hour, {}",
+                    if pool.spot_instance_types.is_empty() {
+                        pool.instance_type.clone()
+                    } else {
+                        pool.spot_instance_types.join(", ")
+                    },
+                    pool.spot_max_price,
+                    if pool.on_demand_fallback {
+                        format!("falling back to on-demand {}", pool.instance_type)
+                    } else {
+                        "without on-demand fallback".to_owned()
+                    },
+                );
+            }
+
+            if !pool.labels.is_empty() {
+                println!("  labels: {}", pool.labels.join(", "));
+            }
+
+            if !pool.taints.is_empty() {
+                println!("  taints: {}", pool.taints.join(", "));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn remove(&self) -> KawsResult {
+        let name = self.name.expect("clap should have required pool");
+        let config = ClusterConfig::load(self.cluster)?;
+
+        let index = match config.node_pools.iter().position(|pool| pool.name == name) {
+            Some(index) => index,
+            None => return Err(KawsError::new(format!(
+                "Cluster \"{}\" has no node pool named \"{}\".",
+                self.cluster,
+                name,
+            ))),
+        };
+
+        // Terraform tells the pools' resources apart by their position in kaws.toml, so every
+        // pool after the removed one moves into the resources of the pool before it.
+        let later_pools: Vec<&str> = config.node_pools[index + 1..].iter().map(|pool| {
+            pool.name.as_str()
+        }).collect();
+
+        if !later_pools.is_empty() {
+            println!(
+                "Node pools are identified by their position in kaws.toml, so removing \"{}\" \
+                also replaces the launch configurations and autoscaling groups of the pools after \
+                it, terminating all of their nodes without draining them: {}",
+                name,
+                later_pools.join(", "),
+            );
+
+            if !confirm("Remove the node pool anyway?", false)? {
+                return Err(KawsError::new(format!("Node pool \"{}\" was not removed.", name)));
+            }
+        }
+
+        let contents = self.read()?;
+        let lines: Vec<&str> = contents.lines().collect();
+        let mut range = None;
+
+        for (start, line) in lines.iter().enumerate() {
+            if line.trim() != "[[node_pools]]" {
+                continue;
+            }
+
+            // The table runs until the next table header, but comments and blank lines just
+            // before that header belong to the next table.
+            let mut end = lines[start + 1..].iter().position(|line| {
+                line.trim().starts_with('[')
+            }).map_or(lines.len(), |index| start + 1 + index);
+
+            while end > start + 1 && {
+                let line = lines[end - 1].trim();
+
+                line.is_empty() || line.starts_with('#')
+            } {
+                end -= 1;
+            }
+
+            let table: Value = from_str(&lines[start + 1..end].join("\n")).map_err(|error| {
+                KawsError::new(format!("Failed to parse the node pools in kaws.toml: {}", error))
+            })?;
+
+            if table.get("name").and_then(|value| value.as_str()) == Some(name) {
+                range = Some((start, end));
+
+                break;
+            }
+        }
+
+        let (mut start, end) = match range {
+            Some(range) => range,
+            None => return Err(KawsError::new(format!(
+                "Failed to find node pool \"{}\" in kaws.toml. Edit the file by hand instead.",
+                name,
+            ))),
+        };
+
+        if start > 0 && lines[start - 1].trim().is_empty() {
+            start -= 1;
+        }
+
+        let mut edited: Vec<&str> = lines[..start].to_vec();
+
+        edited.extend(lines[end..].iter().cloned());
+
+        let mut edited = edited.join("\n");
+
+        edited.push('\n');
+
+        self.save(&edited, config.node_pools.len() - 1)?;
+
+        Ok(Some(format!(
+            "Removed node pool \"{}\" from {}. Run `kaws cluster plan {}` to see the effect of the \
+            change, and commit clusters/{} to Git. Pods that select the pool's nodes with the {} \
+            label will be left pending once its instances are terminated.",
+            name,
+            config_path(self.cluster),
+            self.cluster,
+            self.cluster,
+            NODE_POOL_LABEL,
+        )))
+    }
+
+    // Private
+
+    fn read(&self) -> Result<String, KawsError> {
+        let mut contents = String::new();
+
+        File::open(config_path(self.cluster))?.read_to_string(&mut contents)?;
+
+        Ok(contents)
+    }
+
+    /// Validates the edited `kaws.toml` and writes it, along with the regenerated
+    /// `terraform.tfvars`.
+    fn save(&self, contents: &str, expected_pools: usize) -> Result<(), KawsError> {
+        let path = config_path(self.cluster);
+        let config = ClusterConfig::parse(contents, &path)?;
+
+        if config.node_pools.len() != expected_pools {
+            return Err(KawsError::new(format!(
+                "Failed to update the node pools in {}. Edit the file by hand instead.",
+                path,
+            )));
+        }
+
+        config.validate(self.cluster)?;
+
+        log_wrap!("Writing kaws.toml", {
+            let mut file = File::create(&path)?;
+
+            file.write_all(contents.as_bytes())?;
+        });
+
+        config.write_tfvars(self.cluster)?;
+
+        Ok(())
+    }
+}
@@ -1,12 +1,14 @@
 use std::fs::File;
 use std::io::{Read, Write};
+use std::net::Ipv4Addr;
 use std::process::{Command, Stdio};
 
 use hyper::Client;
-use rusoto_core::ChainProvider;
+use openssl::x509::X509;
 use serde_json::{from_slice, to_vec};
 use tempdir::TempDir;
 
+use aws::CredentialsProvider;
 use encryption::Encryptor;
 use error::{KawsError, KawsResult};
 
@@ -57,6 +59,17 @@ impl Certificate {
     pub fn as_bytes(&self) -> &[u8] {
         &self.0
     }
+
+    /// The IPv4 addresses among the certificate's subject alternative names.
+    pub fn ip_addresses(&self) -> Result<Vec<String>, KawsError> {
+        let cert = X509::from_pem(self.as_bytes())?;
+
+        Ok(cert.subject_alt_names().map_or(vec![], |names| {
+            names.iter().filter_map(|name| name.ipaddress()).filter(|ip| ip.len() == 4).map(|ip| {
+                Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]).to_string()
+            }).collect()
+        }))
+    }
 }
 
 impl From<String> for Certificate {
@@ -67,7 +80,7 @@ impl From<String> for Certificate {
 
 impl CertificateAuthority {
     pub fn from_files(
-        encryptor: &mut Encryptor<ChainProvider, Client>,
+        encryptor: &mut Encryptor<CredentialsProvider, Client>,
         cert_path: &str,
         key_path: &str,
     ) -> Result<Self, KawsError> {
@@ -83,7 +96,7 @@ impl CertificateAuthority {
     pub fn generate(common_name: &str) -> Result<Self, KawsError> {
         let mut command = Command::new("cfssl");
 
-        command.args(&[
+        command.args([
             "gencert",
             "-initca",
             "-",
@@ -143,7 +156,7 @@ impl CertificateAuthority {
         });
 
         if let Some(groups) = groups {
-            let mut names = csr_config
+            let names = csr_config
                     .get_mut("names")
                     .expect("csr_config should have a names field")
                     .as_array_mut()
@@ -162,7 +175,7 @@ impl CertificateAuthority {
 
         let mut command = Command::new("cfssl");
 
-        command.args(&[
+        command.args([
             "gencert",
             "-ca",
             &cert_path,
@@ -171,7 +184,7 @@ impl CertificateAuthority {
         ]);
 
         if let Some(san) = san {
-            command.args(&[
+            command.args([
                 "-hostname",
                 &san.join(","),
             ]);
@@ -222,7 +235,7 @@ impl CertificateAuthority {
 
         let mut command = Command::new("cfssl");
 
-        command.args(&[
+        command.args([
             "sign",
             "-ca",
             &cert_path,
@@ -271,7 +284,7 @@ impl CertificateAuthority {
 
     pub fn write_to_files(
         &self,
-        encryptor: &mut Encryptor<ChainProvider, Client>,
+        encryptor: &mut Encryptor<CredentialsProvider, Client>,
         cert_file_path: &str,
         key_file_path: &str,
     ) -> KawsResult {
@@ -342,7 +355,7 @@ impl CertificateSigningRequest {
         });
 
         if let Some(groups) = groups {
-            let mut names = csr_config
+            let names = csr_config
                     .get_mut("names")
                     .expect("csr_config should have a names field")
                     .as_array_mut()
@@ -359,7 +372,7 @@ impl CertificateSigningRequest {
 
         let mut command = Command::new("cfssl");
 
-        command.args(&[
+        command.args([
             "genkey",
             "-",
         ]);
@@ -418,7 +431,7 @@ impl From<String> for CertificateSigningRequest {
 }
 
 impl PrivateKey {
-    pub fn from_file(encryptor: &mut Encryptor<ChainProvider, Client>, path: &str)
+    pub fn from_file(encryptor: &mut Encryptor<CredentialsProvider, Client>, path: &str)
     -> Result<Self, KawsError> {
         let bytes = encryptor.decrypt_file(path)?;
 
@@ -431,7 +444,7 @@ impl PrivateKey {
 
     pub fn write_to_file(
         &self,
-        encryptor: &mut Encryptor<ChainProvider, Client>,
+        encryptor: &mut Encryptor<CredentialsProvider, Client>,
         file_path: &str,
     ) -> KawsResult {
         encryptor.encrypt_and_write_file(self.as_bytes(), file_path)?;
@@ -0,0 +1,32 @@
+use std::io::{Write, stdin, stdout};
+
+use error::KawsError;
+
+/// Asks the user a yes or no question, returning `default` if they just press enter.
+pub fn confirm(question: &str, default: bool) -> Result<bool, KawsError> {
+    let choices = if default { "[Y/n]" } else { "[y/N]" };
+
+    loop {
+        let answer = read_line(&format!("{} {} ", question, choices))?;
+
+        match answer.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("Please answer \"y\" or \"n\"."),
+        }
+    }
+}
+
+pub fn read_line(prompt: &str) -> Result<String, KawsError> {
+    print!("{}", prompt);
+    stdout().flush()?;
+
+    let mut line = String::new();
+
+    if stdin().read_line(&mut line)? == 0 {
+        return Err(KawsError::new("Reached end of input while waiting for an answer.".to_owned()));
+    }
+
+    Ok(line.trim().to_owned())
+}
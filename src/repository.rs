@@ -1,9 +1,9 @@
-use std::fs::{create_dir_all, File};
-use std::io::Write;
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
 
 use clap::ArgMatches;
 
-use error::KawsResult;
+use error::{KawsError, KawsResult};
 
 pub struct Repository<'a> {
     name: &'a str,
@@ -25,6 +25,7 @@ impl<'a> Repository<'a> {
         create_dir_all(format!("{}/terraform", self.name))?;
 
         let mut gitignore = File::create(format!("{}/.gitignore", self.name))?;
+        writeln!(&mut gitignore, ".os-manifests")?;
         writeln!(&mut gitignore, ".terraform")?;
 
         let mut main_tf = File::create(format!("{}/terraform/kaws.tf", self.name))?;
@@ -34,21 +35,56 @@ r#"module "kaws" {{
     source = "{}"
 
     account_id = "${{var.kaws_account_id}}"
-    availability_zone = "${{var.kaws_availability_zone}}"
-    cidr = "${{var.kaws_cidr}}"
+    asg_tags = ["${{var.kaws_asg_tags}}"]
+    availability_zones = ["${{var.kaws_availability_zones}}"]
+    balancers_subnets = ["${{var.kaws_balancers_subnets}}"]
+    bastion_instance_type = "${{var.kaws_bastion_instance_type}}"
+    bastion_root_volume_size = "${{var.kaws_bastion_root_volume_size}}"
+    bastion_root_volume_type = "${{var.kaws_bastion_root_volume_type}}"
+    cloud_config_ssh_keys = "${{var.kaws_cloud_config_ssh_keys}}"
+    cloud_config_units = "${{var.kaws_cloud_config_units}}"
+    cloud_config_write_files = "${{var.kaws_cloud_config_write_files}}"
     cluster = "${{var.kaws_cluster}}"
     coreos_ami = "${{var.kaws_coreos_ami}}"
+    dns_service_ip = "${{var.kaws_dns_service_ip}}"
     domain = "${{var.kaws_domain}}"
+    etcd_instance_type = "${{var.kaws_etcd_instance_type}}"
+    etcd_ips = ["${{var.kaws_etcd_ips}}"]
+    etcd_root_volume_size = "${{var.kaws_etcd_root_volume_size}}"
+    etcd_root_volume_type = "${{var.kaws_etcd_root_volume_type}}"
+    etcd_subnets = ["${{var.kaws_etcd_subnets}}"]
+    etcd_volume_size = "${{var.kaws_etcd_volume_size}}"
+    etcd_volume_type = "${{var.kaws_etcd_volume_type}}"
     iam_users = ["${{var.kaws_iam_users}}"]
     instance_size = "${{var.kaws_instance_size}}"
+    k8s_subnets = ["${{var.kaws_k8s_subnets}}"]
+    kubelet_api_servers = "${{var.kaws_kubelet_api_servers}}"
+    masters_instance_type = "${{var.kaws_masters_instance_type}}"
     masters_max_size = "${{var.kaws_masters_max_size}}"
     masters_min_size = "${{var.kaws_masters_min_size}}"
+    masters_root_volume_size = "${{var.kaws_masters_root_volume_size}}"
+    masters_root_volume_type = "${{var.kaws_masters_root_volume_type}}"
+    node_pool_instance_types = ["${{var.kaws_node_pool_instance_types}}"]
+    node_pool_labels = ["${{var.kaws_node_pool_labels}}"]
+    node_pool_max_sizes = ["${{var.kaws_node_pool_max_sizes}}"]
+    node_pool_min_sizes = ["${{var.kaws_node_pool_min_sizes}}"]
+    node_pool_spot_prices = ["${{var.kaws_node_pool_spot_prices}}"]
+    node_pool_taints = ["${{var.kaws_node_pool_taints}}"]
+    node_pools = ["${{var.kaws_node_pools}}"]
+    nodes_instance_type = "${{var.kaws_nodes_instance_type}}"
     nodes_max_size = "${{var.kaws_nodes_max_size}}"
     nodes_min_size = "${{var.kaws_nodes_min_size}}"
+    nodes_root_volume_size = "${{var.kaws_nodes_root_volume_size}}"
+    nodes_root_volume_type = "${{var.kaws_nodes_root_volume_type}}"
+    pod_cidr = "${{var.kaws_pod_cidr}}"
     propagating_vgws = ["${{var.kaws_propagating_vgws}}"]
     region = "${{var.kaws_region}}"
+    service_cidr = "${{var.kaws_service_cidr}}"
     ssh_keys = ["${{var.kaws_ssh_keys}}"]
+    tags = "${{var.kaws_tags}}"
+    topology = "${{var.kaws_topology}}"
     version = "${{var.kaws_version}}"
+    vpc_cidr = "${{var.kaws_vpc_cidr}}"
     zone_id = "${{var.kaws_zone_id}}"
 }}
 
@@ -56,12 +92,53 @@ variable "kaws_account_id" {{
   description = "Numerical account ID of the AWS account to use, e.g. `12345678`"
 }}
 
-variable "kaws_availability_zone" {{
-  description = "Availability Zone for etcd instances and EBS volumes, e.g. `us-east-1a`"
+variable "kaws_asg_tags" {{
+  description = "The tags in `tags`, as a list of autoscaling group tags that are propagated to the instances they launch"
+  type = "list"
+  default = []
+}}
+
+variable "kaws_availability_zones" {{
+  description = "A list of Availability Zones to run the cluster in"
+  type = "list"
+}}
+
+variable "kaws_balancers_subnets" {{
+  description = "IPv4 network ranges of the subnets for ELBs, one for each Availability Zone"
+  type = "list"
+}}
+
+variable "kaws_bastion_instance_type" {{
+  description = "The EC2 instance type of the bastion, e.g. `t2.micro`"
+  default = "t2.micro"
+}}
+
+variable "kaws_bastion_root_volume_size" {{
+  description = "The size in GiB of the bastion's root volume"
+  default = "8"
+}}
+
+variable "kaws_bastion_root_volume_type" {{
+  description = "The EBS volume type of the bastion's root volume, e.g. `gp2`"
+  default = "gp2"
 }}
 
-variable "kaws_cidr" {{
-  description = "IPv4 network range of the subnet where Kubernetes nodes will run, e.g. `10.0.2.0/24`"
+variable "kaws_cloud_config_ssh_keys" {{
+  description = "Extra SSH public keys for each role, rendered as YAML flow sequence items"
+  type = "map"
+  default = {{}}
+}}
+
+variable "kaws_cloud_config_units" {{
+  description = "Extra systemd units for each role, rendered as YAML block sequence items"
+  type = "map"
+  default = {{}}
+}}
+
+variable "kaws_cloud_config_write_files" {{
+  description = "Extra files to write for each role, rendered as YAML block sequence items"
+  type = "map"
+  default = {{}}
 }}
 
 variable "kaws_cluster" {{
@@ -72,17 +149,72 @@ variable "kaws_coreos_ami" {{
   description = "The AMI ID for the CoreOS image to use for servers, e.g. `ami-1234abcd`"
 }}
 
+variable "kaws_dns_service_ip" {{
+  description = "The IP address of the cluster DNS service, within the service network, e.g. `10.3.0.10`"
+  default = "10.3.0.10"
+}}
+
 variable "kaws_domain" {{
   description = "The domain name for the cluster, e.g. `example.com`"
 }}
 
+variable "kaws_etcd_instance_type" {{
+  description = "The EC2 instance type of the etcd instances, defaults to `instance_size`"
+  default = ""
+}}
+
+variable "kaws_etcd_ips" {{
+  description = "The private IP addresses of the etcd instances, one for each member of the etcd cluster"
+  type = "list"
+}}
+
+variable "kaws_etcd_root_volume_size" {{
+  description = "The size in GiB of the etcd instances' root volumes"
+  default = "8"
+}}
+
+variable "kaws_etcd_root_volume_type" {{
+  description = "The EBS volume type of the etcd instances' root volumes, e.g. `gp2`"
+  default = "gp2"
+}}
+
+variable "kaws_etcd_subnets" {{
+  description = "IPv4 network ranges of the subnets for etcd instances, one for each Availability Zone"
+  type = "list"
+}}
+
+variable "kaws_etcd_volume_size" {{
+  description = "The size in GiB of the EBS volumes for etcd's data"
+  default = "10"
+}}
+
+variable "kaws_etcd_volume_type" {{
+  description = "The EBS volume type of the volumes for etcd's data, e.g. `gp2`"
+  default = "gp2"
+}}
+
 variable "kaws_iam_users" {{
   description = "A list of IAM user names who will have access to cluster PKI secrets"
   type = "list"
 }}
 
 variable "kaws_instance_size" {{
-  description = "The EC2 instance size, e.g. `m3.medium`"
+  description = "The EC2 instance size for roles without their own instance type, e.g. `m3.medium`"
+}}
+
+variable "kaws_k8s_subnets" {{
+  description = "IPv4 network ranges of the subnets where Kubernetes nodes will run, one for each Availability Zone"
+  type = "list"
+}}
+
+variable "kaws_kubelet_api_servers" {{
+  description = "Whether the kubelet takes `--api-servers`, set by kaws for the Kubernetes version"
+  default = "true"
+}}
+
+variable "kaws_masters_instance_type" {{
+  description = "The EC2 instance type of the Kubernetes masters, defaults to `instance_size`"
+  default = ""
 }}
 
 variable "kaws_masters_max_size" {{
@@ -93,6 +225,63 @@ variable "kaws_masters_min_size" {{
   description = "The minimum number of EC2 instances the Kubernetes masters may autoscale to"
 }}
 
+variable "kaws_masters_root_volume_size" {{
+  description = "The size in GiB of the Kubernetes masters' root volumes"
+  default = "30"
+}}
+
+variable "kaws_masters_root_volume_type" {{
+  description = "The EBS volume type of the Kubernetes masters' root volumes, e.g. `gp2`"
+  default = "gp2"
+}}
+
+variable "kaws_node_pool_instance_types" {{
+  description = "The EC2 instance type of each node pool autoscaling group"
+  type = "list"
+  default = []
+}}
+
+variable "kaws_node_pool_labels" {{
+  description = "The Kubernetes labels for the nodes in each node pool autoscaling group, as comma-separated `key=value` pairs"
+  type = "list"
+  default = []
+}}
+
+variable "kaws_node_pool_max_sizes" {{
+  description = "The maximum number of EC2 instances each node pool autoscaling group may autoscale to"
+  type = "list"
+  default = []
+}}
+
+variable "kaws_node_pool_min_sizes" {{
+  description = "The minimum number of EC2 instances each node pool autoscaling group may autoscale to"
+  type = "list"
+  default = []
+}}
+
+variable "kaws_node_pool_spot_prices" {{
+  description = "The maximum hourly price of the spot instances in each node pool autoscaling group, or an empty string for on-demand instances"
+  type = "list"
+  default = []
+}}
+
+variable "kaws_node_pool_taints" {{
+  description = "The Kubernetes taints for the nodes in each node pool autoscaling group, as comma-separated `key=value:Effect` values"
+  type = "list"
+  default = []
+}}
+
+variable "kaws_node_pools" {{
+  description = "The names of the autoscaling groups of the additional pools of Kubernetes nodes"
+  type = "list"
+  default = []
+}}
+
+variable "kaws_nodes_instance_type" {{
+  description = "The EC2 instance type of the Kubernetes nodes, defaults to `instance_size`"
+  default = ""
+}}
+
 variable "kaws_nodes_max_size" {{
   description = "The maximum number of EC2 instances the Kubernetes nodes may autoscale to"
 }}
@@ -101,6 +290,21 @@ variable "kaws_nodes_min_size" {{
   description = "The minimum number of EC2 instances the Kubernetes nodes may autoscale to"
 }}
 
+variable "kaws_nodes_root_volume_size" {{
+  description = "The size in GiB of the Kubernetes nodes' root volumes"
+  default = "30"
+}}
+
+variable "kaws_nodes_root_volume_type" {{
+  description = "The EBS volume type of the Kubernetes nodes' root volumes, e.g. `gp2`"
+  default = "gp2"
+}}
+
+variable "kaws_pod_cidr" {{
+  description = "The IPv4 network range pods get their IP addresses from, e.g. `10.2.0.0/16`"
+  default = "10.2.0.0/16"
+}}
+
 variable "kaws_propagating_vgws" {{
   description = "A list of virtual gateways that should propagate routes to the route table"
   type = "list"
@@ -110,13 +314,34 @@ variable "kaws_region" {{
   description = "The AWS Region where the cluster will live, e.g. `us-east-1`"
 }}
 
+variable "kaws_service_cidr" {{
+  description = "The IPv4 network range Kubernetes services get their IP addresses from, e.g. `10.3.0.0/24`"
+  default = "10.3.0.0/24"
+}}
+
 variable "kaws_ssh_keys" {{
   description = "SSH public keys to add to ~/.ssh/authorized_keys on each server"
   type = "list"
 }}
 
+variable "kaws_tags" {{
+  description = "Tags to add to every AWS resource that supports them, in addition to `Name` and `KubernetesCluster`"
+  type = "map"
+  default = {{}}
+}}
+
+variable "kaws_topology" {{
+  description = "How the servers are connected to the Internet, `public` or `private`"
+  default = "public"
+}}
+
 variable "kaws_version" {{
-  description = "Version of Kubernetes to use, e.g. `1.0.0`"
+  description = "Version of Kubernetes to use, e.g. `1.8.4`"
+}}
+
+variable "kaws_vpc_cidr" {{
+  description = "IPv4 network range of the VPC, e.g. `10.0.0.0/16`"
+  default = "10.0.0.0/16"
 }}
 
 variable "kaws_zone_id" {{
@@ -129,3 +354,39 @@ variable "kaws_zone_id" {{
         Ok(Some(format!("New repository \"{}\" created!", self.name)))
     }
 }
+
+/// Adds `path` to a `.gitignore` file unless it already ignores it, for repositories and clusters
+/// created by older versions of kaws.
+pub fn ignore_path(gitignore_path: &str, path: &str) -> Result<(), KawsError> {
+    let mut contents = String::new();
+
+    match File::open(gitignore_path) {
+        Ok(mut file) => {
+            file.read_to_string(&mut contents)?;
+        }
+        Err(ref error) if error.kind() == ErrorKind::NotFound => {}
+        Err(error) => return Err(error.into()),
+    }
+
+    let name = path.trim_end_matches('/');
+
+    let ignored = contents.lines().any(|line| {
+        let line = line.trim().trim_end_matches('/');
+
+        line == name || line == format!("/{}", name)
+    });
+
+    if ignored {
+        return Ok(());
+    }
+
+    let mut file = OpenOptions::new().append(true).create(true).open(gitignore_path)?;
+
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        writeln!(file)?;
+    }
+
+    writeln!(file, "{}", path)?;
+
+    Ok(())
+}
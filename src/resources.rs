@@ -0,0 +1,158 @@
+use error::KawsError;
+
+/// The path of the kaws module in the root Terraform configuration.
+pub const MODULE_PATH: &'static str = "module.kaws";
+
+/// The roles that resources can be targeted by, e.g. with `kaws cluster apply --only nodes`.
+pub const ROLES: &'static [&'static str] = &["bastion", "etcd", "masters", "network", "nodes"];
+
+/// Every resource defined by the kaws Terraform module, as `(type, name, role)`. Resources used
+/// by more than one role have the role "shared" and are only changed by untargeted operations.
+/// This must be kept in sync with the `.tf` files in the terraform directory.
+const RESOURCES: &'static [(&'static str, &'static str, &'static str)] = &[
+    ("aws_autoscaling_group", "k8s_masters", "masters"),
+    ("aws_autoscaling_group", "k8s_node_pools", "nodes"),
+    ("aws_autoscaling_group", "k8s_nodes", "nodes"),
+    ("aws_ebs_volume", "etcd_01", "etcd"),
+    ("aws_ebs_volume", "etcd_02", "etcd"),
+    ("aws_ebs_volume", "etcd_03", "etcd"),
+    ("aws_ebs_volume", "etcd_04", "etcd"),
+    ("aws_ebs_volume", "etcd_05", "etcd"),
+    ("aws_eip", "nat", "network"),
+    ("aws_elb", "k8s_masters", "masters"),
+    ("aws_elb", "k8s_nodes", "nodes"),
+    ("aws_iam_instance_profile", "bastion", "bastion"),
+    ("aws_iam_instance_profile", "etcd", "etcd"),
+    ("aws_iam_instance_profile", "k8s_master", "masters"),
+    ("aws_iam_instance_profile", "k8s_node", "nodes"),
+    ("aws_iam_role", "bastion", "bastion"),
+    ("aws_iam_role", "etcd", "etcd"),
+    ("aws_iam_role", "k8s_master", "masters"),
+    ("aws_iam_role", "k8s_node", "nodes"),
+    ("aws_iam_role_policy", "bastion", "bastion"),
+    ("aws_iam_role_policy", "etcd", "etcd"),
+    ("aws_iam_role_policy", "k8s_master", "masters"),
+    ("aws_iam_role_policy", "k8s_node", "nodes"),
+    ("aws_instance", "bastion", "bastion"),
+    ("aws_instance", "etcd_01", "etcd"),
+    ("aws_instance", "etcd_02", "etcd"),
+    ("aws_instance", "etcd_03", "etcd"),
+    ("aws_instance", "etcd_04", "etcd"),
+    ("aws_instance", "etcd_05", "etcd"),
+    ("aws_internet_gateway", "outgoing", "network"),
+    ("aws_kms_alias", "etcd", "etcd"),
+    ("aws_kms_alias", "pki", "shared"),
+    ("aws_kms_key", "etcd", "etcd"),
+    ("aws_kms_key", "pki", "shared"),
+    ("aws_launch_configuration", "k8s_masters", "masters"),
+    ("aws_launch_configuration", "k8s_node_pools", "nodes"),
+    ("aws_launch_configuration", "k8s_nodes", "nodes"),
+    ("aws_nat_gateway", "outgoing", "network"),
+    ("aws_route", "k8s_outgoing_traffic", "network"),
+    ("aws_route", "main_outgoing_traffic", "network"),
+    ("aws_route", "private_outgoing_traffic", "network"),
+    ("aws_route53_record", "bastion", "bastion"),
+    ("aws_route53_record", "kubernetes", "masters"),
+    ("aws_route_table", "k8s", "network"),
+    ("aws_route_table", "private", "network"),
+    ("aws_route_table_association", "etcd_private", "network"),
+    ("aws_route_table_association", "k8s", "network"),
+    ("aws_route_table_association", "k8s_private", "network"),
+    ("aws_s3_bucket", "cloud_config", "shared"),
+    ("aws_s3_bucket_object", "bastion_cloud_config", "bastion"),
+    ("aws_s3_bucket_object", "etcd_01_cloud_config", "etcd"),
+    ("aws_s3_bucket_object", "etcd_02_cloud_config", "etcd"),
+    ("aws_s3_bucket_object", "etcd_03_cloud_config", "etcd"),
+    ("aws_s3_bucket_object", "etcd_04_cloud_config", "etcd"),
+    ("aws_s3_bucket_object", "etcd_05_cloud_config", "etcd"),
+    ("aws_s3_bucket_object", "master_cloud_config", "masters"),
+    ("aws_s3_bucket_object", "node_cloud_config", "nodes"),
+    ("aws_s3_bucket_object", "node_pool_cloud_config", "nodes"),
+    ("aws_security_group", "balancers", "network"),
+    ("aws_security_group", "bastion", "bastion"),
+    ("aws_security_group", "etcd", "etcd"),
+    ("aws_security_group", "kubernetes", "network"),
+    ("aws_subnet", "balancers", "network"),
+    ("aws_subnet", "etcd", "network"),
+    ("aws_subnet", "k8s", "network"),
+    ("aws_volume_attachment", "etcd_01", "etcd"),
+    ("aws_volume_attachment", "etcd_02", "etcd"),
+    ("aws_volume_attachment", "etcd_03", "etcd"),
+    ("aws_volume_attachment", "etcd_04", "etcd"),
+    ("aws_volume_attachment", "etcd_05", "etcd"),
+    ("aws_vpc", "kubernetes", "network"),
+];
+
+/// Resource types that a bare name refers to when resources of several types share the name, so
+/// that e.g. `etcd_01` means the instance rather than its volume or volume attachment.
+const PRIMARY_KINDS: &'static [&'static str] = &["aws_autoscaling_group", "aws_instance"];
+
+/// Resolves a resource address given on the command line to a full Terraform address.
+///
+/// Accepted forms are:
+///
+/// * A full address such as `module.kaws.aws_instance.etcd_01`, which is used as is.
+/// * A type and name such as `aws_instance.etcd_01`, or `instance.etcd_01` without the `aws_`
+///   prefix, which is resolved within the kaws module. Addresses that aren't part of the kaws
+///   module are left alone, so resources from the user's own `.tf` files can be used too.
+/// * A bare name such as `etcd_01` or `k8s_nodes`. If several kaws resources have that name, the
+///   instance or autoscaling group is chosen.
+pub fn resolve_address(address: &str) -> Result<String, KawsError> {
+    if address.starts_with("module.") {
+        return Ok(address.to_owned());
+    }
+
+    let mut parts = address.splitn(2, '.');
+    let first = parts.next().unwrap_or("");
+
+    match parts.next() {
+        Some(name) => {
+            let prefixed_kind = format!("aws_{}", first);
+
+            let found = RESOURCES.iter().find(|&&(kind, resource_name, _)| {
+                (kind == first || kind == prefixed_kind) && resource_name == name
+            });
+
+            match found {
+                Some(&(kind, name, _)) => Ok(format!("{}.{}.{}", MODULE_PATH, kind, name)),
+                None => Ok(address.to_owned()),
+            }
+        }
+        None => {
+            let mut matches: Vec<_> = RESOURCES.iter().filter(|&&(_, name, _)| name == first).collect();
+
+            if matches.len() > 1 {
+                let primary: Vec<_> = matches.iter().cloned().filter(|&&(kind, _, _)| {
+                    PRIMARY_KINDS.contains(&kind)
+                }).collect();
+
+                if primary.len() == 1 {
+                    matches = primary;
+                }
+            }
+
+            match matches.len() {
+                0 => Err(KawsError::new(format!(
+                    "No resource in the kaws module is named \"{}\".",
+                    address,
+                ))),
+                1 => Ok(format!("{}.{}.{}", MODULE_PATH, matches[0].0, matches[0].1)),
+                _ => Err(KawsError::new(format!(
+                    "\"{}\" could refer to any of these resources, so specify its type:\n{}",
+                    address,
+                    matches.iter().map(|&&(kind, name, _)| {
+                        format!("  {}.{}", kind, name)
+                    }).collect::<Vec<_>>().join("\n"),
+                ))),
+            }
+        }
+    }
+}
+
+/// Returns the full addresses of the kaws resources belonging to a role, for use with `-target`.
+pub fn role_targets(role: &str) -> Vec<String> {
+    RESOURCES.iter()
+        .filter(|&&(_, _, resource_role)| resource_role == role)
+        .map(|&(kind, name, _)| format!("{}.{}.{}", MODULE_PATH, kind, name))
+        .collect()
+}
@@ -0,0 +1,390 @@
+use std::cmp::max;
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use clap::ArgMatches;
+
+use autoscaling::{AutoScaling, Group};
+use aws::{CredentialsProvider, credentials_provider};
+use config::ClusterConfig;
+use error::{KawsError, KawsResult};
+use prompt::confirm;
+use state::State;
+use terraform::Outputs;
+
+const POLL_INTERVAL_SECS: u64 = 15;
+const REPLACEMENT_TIMEOUT_SECS: u64 = 1200;
+
+/// Replaces the stale instances of one of a cluster's roles: the masters, the nodes in `[nodes]`,
+/// or a node pool.
+pub struct Roll<'a> {
+    aws_credentials_provider: CredentialsProvider,
+    batch_size: usize,
+    cluster: &'a str,
+    role: &'a str,
+    yes: bool,
+}
+
+/// Replaces the instances of a cluster's autoscaling groups that were launched from outdated
+/// launch configurations, in batches.
+///
+/// Which instances still need replacing is worked out from the groups themselves each time, so an
+/// interrupted rollout continues where it left off when it's run again.
+pub struct Rollout<'a> {
+    autoscaling: AutoScaling<'a>,
+    cluster: &'a str,
+    context: String,
+}
+
+/// An autoscaling group as recorded in the cluster's Terraform state.
+pub struct StateGroup {
+    pub launch_configuration: String,
+    pub name: String,
+}
+
+impl<'a> Roll<'a> {
+    pub fn new(matches: &'a ArgMatches) -> Self {
+        Roll {
+            aws_credentials_provider: credentials_provider(
+                matches.value_of("cluster"),
+                matches.value_of("region"),
+                matches.value_of("aws-credentials-path"),
+                matches.value_of("aws-credentials-profile"),
+            ),
+            batch_size: matches.value_of("batch-size").map_or(1, |batch_size| {
+                batch_size.parse().expect("clap should have validated batch-size")
+            }),
+            cluster: matches.value_of("cluster").expect("clap should have required cluster"),
+            role: matches.value_of("role").expect("clap should have required role"),
+            yes: matches.is_present("yes"),
+        }
+    }
+
+    pub fn roll(&self) -> KawsResult {
+        let config = ClusterConfig::load(self.cluster)?;
+        let state = State::load(self.cluster)?;
+
+        let groups = match self.role {
+            "masters" => state_groups(&state, |address| {
+                address == "aws_autoscaling_group.k8s_masters"
+            }),
+            "nodes" => state_groups(&state, |address| address == "aws_autoscaling_group.k8s_nodes"),
+            pool => {
+                let indexes = config.node_pool_group_indexes(pool);
+
+                if indexes.is_empty() {
+                    return Err(KawsError::new(format!(
+                        "Cluster \"{}\" has no node pool named \"{}\". Give \"masters\", \"nodes\", \
+                        or the name of a node pool.",
+                        self.cluster,
+                        pool,
+                    )));
+                }
+
+                let names = Outputs::load(self.cluster)?.kubernetes_node_pool_autoscaling_groups;
+                if names.len() != config.node_pool_group_count() {
+                    return Err(KawsError::new(format!(
+                        "The node pools in kaws.toml have changed since cluster \"{}\" was last \
+                        applied. Run `kaws cluster apply {}` first.",
+                        self.cluster,
+                        self.cluster,
+                    )));
+                }
+
+                let names: Vec<&String> = indexes.iter().map(|&index| &names[index]).collect();
+
+                let mut groups = state_groups(&state, |address| {
+                    address.starts_with("aws_autoscaling_group.k8s_node_pools")
+                });
+
+                groups.retain(|group| names.contains(&&group.name));
+
+                groups
+            }
+        };
+
+        if groups.is_empty() {
+            return Err(KawsError::new(format!(
+                "No autoscaling groups for {} were found in the Terraform state. Run `kaws cluster \
+                apply {}` first.",
+                self.role,
+                self.cluster,
+            )));
+        }
+
+        let rollout = Rollout::new(self.cluster, &self.aws_credentials_provider, &config.region);
+        let mut stale = 0;
+
+        for group in groups.iter() {
+            stale += rollout.stale_instances(group)?;
+        }
+
+        if stale == 0 {
+            return Ok(Some(format!(
+                "Every instance of {} in cluster \"{}\" is up to date.",
+                self.role,
+                self.cluster,
+            )));
+        }
+
+        if !self.yes && !confirm(&format!(
+            "Replace {} stale instance(s) of {} in cluster \"{}\", {} at a time?",
+            stale,
+            self.role,
+            self.cluster,
+            self.batch_size,
+        ), false)? {
+            return Err(KawsError::new("No instances were replaced.".to_owned()));
+        }
+
+        let mut replaced = 0;
+
+        for group in groups.iter() {
+            replaced += rollout.replace_stale(group, self.role != "masters", self.batch_size)?;
+        }
+
+        Ok(Some(format!(
+            "Replaced {} instance(s) of {} in cluster \"{}\".",
+            replaced,
+            self.role,
+            self.cluster,
+        )))
+    }
+}
+
+impl<'a> Rollout<'a> {
+    pub fn new(
+        cluster: &'a str,
+        credentials_provider: &'a CredentialsProvider,
+        region: &'a str,
+    ) -> Self {
+        Rollout {
+            autoscaling: AutoScaling::new(credentials_provider, region),
+            cluster: cluster,
+            context: format!("kaws-{}", cluster),
+        }
+    }
+
+    /// Replaces every stale instance in the group, `batch_size` at a time, returning how many
+    /// were replaced. With `drain`, each instance's Kubernetes node is cordoned and drained before
+    /// the instance is terminated.
+    ///
+    /// When a batch is every instance in the group, e.g. a cluster's only master, the group is
+    /// first grown by one instance and the batch's first termination shrinks it again, so an
+    /// instance stays in service throughout.
+    pub fn replace_stale(
+        &self,
+        state_group: &StateGroup,
+        drain: bool,
+        batch_size: usize,
+    ) -> Result<usize, KawsError> {
+        let mut replaced = 0;
+
+        loop {
+            let group = self.wait_until_settled(state_group, &[])?;
+            let stale = group.stale_instances();
+
+            if stale.is_empty() {
+                return Ok(replaced);
+            }
+
+            let batch: Vec<String> = stale.iter().take(batch_size).map(|instance| {
+                instance.instance_id.clone()
+            }).collect();
+
+            println!(
+                "Replacing {} of the {} stale instance(s) in {}.",
+                batch.len(),
+                stale.len(),
+                state_group.name,
+            );
+
+            let surge = batch.len() == group.instances.len();
+
+            if surge {
+                println!(
+                    "Adding an instance to {} so one stays in service during the replacement.",
+                    state_group.name,
+                );
+
+                self.autoscaling.resize(
+                    &state_group.name,
+                    group.desired_capacity + 1,
+                    max(group.max_size, group.desired_capacity + 1),
+                )?;
+
+                self.wait_until_settled(state_group, &[])?;
+            }
+
+            for instance_id in batch.iter() {
+                let node = self.autoscaling.private_ip(instance_id)?;
+
+                println!("Replacing instance {} (node {}).", instance_id, node);
+
+                if drain {
+                    self.drain(&node)?;
+                }
+            }
+
+            for (index, instance_id) in batch.iter().enumerate() {
+                self.autoscaling.terminate(instance_id, surge && index == 0)?;
+            }
+
+            self.wait_until_settled(state_group, &batch)?;
+
+            if surge && group.max_size <= group.desired_capacity {
+                self.autoscaling.resize(
+                    &state_group.name,
+                    group.desired_capacity,
+                    group.max_size,
+                )?;
+            }
+
+            replaced += batch.len();
+        }
+    }
+
+    /// The number of instances in the group launched from an older launch configuration than the
+    /// one in the Terraform state.
+    pub fn stale_instances(&self, state_group: &StateGroup) -> Result<usize, KawsError> {
+        Ok(self.group(state_group)?.stale_instances().len())
+    }
+
+    // Private
+
+    fn drain(&self, node: &str) -> Result<(), KawsError> {
+        let registered = self.kubectl(&["get", "node", node])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?
+            .success();
+
+        if !registered {
+            println!("Node {} is not registered with Kubernetes, so it will not be drained.", node);
+
+            return Ok(());
+        }
+
+        for args in [
+            vec!["cordon", node],
+            vec!["drain", node, "--ignore-daemonsets", "--delete-local-data"],
+        ].iter() {
+            if !self.kubectl(args).status()?.success() {
+                return Err(KawsError::new(format!(
+                    "`kubectl {}` failed for node {}. Fix the problem and run the command again to \
+                    continue.",
+                    args[0],
+                    node,
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Describes the group, making sure it uses the launch configuration in the Terraform state,
+    /// since instances are only stale relative to the configuration that has been applied.
+    fn group(&self, state_group: &StateGroup) -> Result<Group, KawsError> {
+        let group = self.autoscaling.group(&state_group.name)?;
+
+        if group.launch_configuration_name.as_ref() != Some(&state_group.launch_configuration) {
+            return Err(KawsError::new(format!(
+                "Autoscaling group {} does not use launch configuration {} from the Terraform \
+                state. Run `kaws cluster apply {}` first.",
+                state_group.name,
+                state_group.launch_configuration,
+                self.cluster,
+            )));
+        }
+
+        Ok(group)
+    }
+
+    fn is_node_ready(&self, node: &str) -> Result<bool, KawsError> {
+        let output = self.kubectl(&[
+            "get",
+            "node",
+            node,
+            "--output",
+            "jsonpath={.status.conditions[?(@.type==\"Ready\")].status}",
+        ]).output()?;
+
+        Ok(output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "True")
+    }
+
+    fn kubectl(&self, args: &[&str]) -> Command {
+        let mut command = Command::new("kubectl");
+
+        command.args(["--context", &self.context]).args(args);
+
+        command
+    }
+
+    /// Whether the group is back at its desired capacity without the terminated instances, every
+    /// instance passes the health checks of the group's ELBs, and the nodes of its up-to-date
+    /// instances are ready.
+    fn is_settled(&self, group: &Group, terminated: &[String]) -> Result<bool, KawsError> {
+        if !group.is_settled() || group.instances.iter().any(|instance| {
+            terminated.contains(&instance.instance_id)
+        }) {
+            return Ok(false);
+        }
+
+        for load_balancer_name in group.load_balancer_names.iter() {
+            let in_service = self.autoscaling.in_service_instances(load_balancer_name)?;
+
+            if group.instances.iter().any(|instance| !in_service.contains(&instance.instance_id)) {
+                return Ok(false);
+            }
+        }
+
+        for instance in group.instances.iter().filter(|instance| !group.is_stale(instance)) {
+            if !self.is_node_ready(&self.autoscaling.private_ip(&instance.instance_id)?)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn wait_until_settled(
+        &self,
+        state_group: &StateGroup,
+        terminated: &[String],
+    ) -> Result<Group, KawsError> {
+        let started = Instant::now();
+
+        loop {
+            let group = self.group(state_group)?;
+
+            if self.is_settled(&group, terminated)? {
+                return Ok(group);
+            }
+
+            if started.elapsed() > Duration::from_secs(REPLACEMENT_TIMEOUT_SECS) {
+                return Err(KawsError::new(format!(
+                    "Timed out after {} minutes waiting for the instances in {} to become healthy \
+                    and their nodes to become ready. Run the command again to continue once they \
+                    are.",
+                    REPLACEMENT_TIMEOUT_SECS / 60,
+                    state_group.name,
+                )));
+            }
+
+            sleep(Duration::from_secs(POLL_INTERVAL_SECS));
+        }
+    }
+}
+
+/// The cluster's autoscaling groups whose Terraform addresses are accepted by `matches`, in order
+/// of address.
+pub fn state_groups<F>(state: &State, matches: F) -> Vec<StateGroup> where F: Fn(&str) -> bool {
+    state.resources_of_type("aws_autoscaling_group").into_iter().filter(|&(address, _)| {
+        matches(address)
+    }).filter_map(|(_, resource)| resource.primary.as_ref().map(|instance| StateGroup {
+        launch_configuration: instance.attributes.get("launch_configuration").cloned()
+            .unwrap_or_default(),
+        name: instance.id.clone(),
+    })).collect()
+}
@@ -0,0 +1,230 @@
+use std::process::{Command, ExitStatus, Output};
+
+use rusoto_core::ProvideAwsCredentials;
+
+use aws::set_credentials_env;
+use error::{KawsError, KawsResult};
+
+const CONFIG_DIR: &'static str = "terraform";
+const REDACTED: &'static str = "[REDACTED]";
+
+/// Builds and runs a single invocation of Terraform for a cluster.
+///
+/// Arguments are assembled in the order Terraform expects: the subcommand, kaws's own flags, any
+/// extra arguments the user passed through on the command line, the configuration directory, and
+/// finally operands such as resource addresses. In dry-run mode the command line is printed, with
+/// credentials and variable values redacted, instead of being run.
+pub struct TerraformCommand<'a> {
+    args: Vec<String>,
+    config: bool,
+    credentials: Option<&'a dyn ProvideAwsCredentials>,
+    dry_run: bool,
+    extra_args: Vec<&'a str>,
+    operands: Vec<String>,
+    subcommand: &'a str,
+}
+
+impl<'a> TerraformCommand<'a> {
+    /// `subcommand` may contain more than one word, e.g. "state mv".
+    pub fn new(subcommand: &'a str) -> Self {
+        TerraformCommand {
+            args: vec![],
+            config: false,
+            credentials: None,
+            dry_run: false,
+            extra_args: vec![],
+            operands: vec![],
+            subcommand: subcommand,
+        }
+    }
+
+    pub fn arg<S>(&mut self, arg: S) -> &mut Self where S: Into<String> {
+        self.args.push(arg.into());
+
+        self
+    }
+
+    pub fn args(&mut self, args: &[&str]) -> &mut Self {
+        self.args.extend(args.iter().map(|arg| arg.to_string()));
+
+        self
+    }
+
+    /// Appends the Terraform configuration directory, for subcommands that read the configuration.
+    pub fn config(&mut self) -> &mut Self {
+        self.config = true;
+
+        self
+    }
+
+    /// Points `terraform import`, which takes the configuration directory as an option rather than
+    /// an argument, at the configuration.
+    pub fn config_option(&mut self) -> &mut Self {
+        self.arg(format!("-config={}", CONFIG_DIR))
+    }
+
+    /// Passes AWS credentials to Terraform. They are only resolved when the command is run.
+    pub fn credentials(&mut self, provider: &'a dyn ProvideAwsCredentials) -> &mut Self {
+        self.credentials = Some(provider);
+
+        self
+    }
+
+    pub fn dry_run(&mut self, dry_run: bool) -> &mut Self {
+        self.dry_run = dry_run;
+
+        self
+    }
+
+    /// Arguments supplied by the user to be passed through to Terraform unchanged.
+    pub fn extra_args(&mut self, extra_args: Option<&Vec<&'a str>>) -> &mut Self {
+        if let Some(extra_args) = extra_args {
+            self.extra_args.extend(extra_args.iter().cloned());
+        }
+
+        self
+    }
+
+    pub fn operand<S>(&mut self, operand: S) -> &mut Self where S: Into<String> {
+        self.operands.push(operand.into());
+
+        self
+    }
+
+    pub fn state(&mut self, cluster: &str) -> &mut Self {
+        self.arg(format!("-state=clusters/{}/terraform.tfstate", cluster))
+    }
+
+    pub fn var_file(&mut self, cluster: &str) -> &mut Self {
+        self.arg(format!("-var-file=clusters/{}/terraform.tfvars", cluster))
+    }
+
+    /// The command line that would be run, with secrets redacted.
+    pub fn command_line(&self) -> String {
+        let mut words = vec![];
+
+        if self.credentials.is_some() {
+            words.push(format!("AWS_ACCESS_KEY_ID={}", REDACTED));
+            words.push(format!("AWS_SECRET_ACCESS_KEY={}", REDACTED));
+            words.push(format!("AWS_SESSION_TOKEN={}", REDACTED));
+        }
+
+        words.push("terraform".to_owned());
+
+        let mut redact_next = false;
+
+        for arg in self.all_args() {
+            words.push(quote(&if redact_next {
+                redact_next = false;
+
+                redact_var(arg)
+            } else if arg == "-var" {
+                redact_next = true;
+
+                arg.to_owned()
+            } else if let Some(var) = arg.strip_prefix("-var=") {
+                format!("-var={}", redact_var(var))
+            } else {
+                arg.to_owned()
+            }));
+        }
+
+        words.join(" ")
+    }
+
+    /// Runs the command with inherited standard streams, or prints it in dry-run mode.
+    pub fn run(&self) -> KawsResult {
+        match self.status()? {
+            Some(exit_status) => check_exit_status(self.subcommand, exit_status),
+            None => Ok(None),
+        }
+    }
+
+    /// Runs the command with inherited standard streams and returns its exit status, or prints it
+    /// and returns `None` in dry-run mode.
+    pub fn status(&self) -> Result<Option<ExitStatus>, KawsError> {
+        if self.dry_run {
+            println!("{}", self.command_line());
+
+            return Ok(None);
+        }
+
+        let mut command = self.command()?;
+
+        debug!("Running `{}`", self.command_line());
+
+        Ok(Some(command.status()?))
+    }
+
+    /// Runs the command and captures its output. This is for commands whose output kaws reads
+    /// itself, so it ignores dry-run mode and leaves checking the exit status to the caller.
+    pub fn output(&self) -> Result<Output, KawsError> {
+        let mut command = self.command()?;
+
+        debug!("Running `{}`", self.command_line());
+
+        Ok(command.output()?)
+    }
+
+    // Private
+
+    fn all_args(&self) -> Vec<&str> {
+        let mut args: Vec<&str> = self.subcommand.split_whitespace().collect();
+
+        args.extend(self.args.iter().map(|arg| arg.as_str()));
+        args.extend(self.extra_args.iter().cloned());
+
+        if self.config {
+            args.push(CONFIG_DIR);
+        }
+
+        args.extend(self.operands.iter().map(|operand| operand.as_str()));
+
+        args
+    }
+
+    fn command(&self) -> Result<Command, KawsError> {
+        let mut command = Command::new("terraform");
+
+        command.args(self.all_args());
+
+        if let Some(provider) = self.credentials {
+            set_credentials_env(&mut command, &provider.credentials()?);
+        }
+
+        Ok(command)
+    }
+}
+
+pub fn check_exit_status(subcommand: &str, exit_status: ExitStatus) -> KawsResult {
+    if exit_status.success() {
+        return Ok(None);
+    }
+
+    match exit_status.code() {
+        Some(code) => Err(KawsError::with_exit_code(
+            format!("`terraform {}` failed with exit code {}.", subcommand, code),
+            code,
+        )),
+        None => Err(KawsError::new(
+            format!("`terraform {}` was terminated by a signal.", subcommand),
+        )),
+    }
+}
+
+/// Quotes an argument for display if the shell would otherwise split or interpret it.
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && arg.chars().all(|c| c.is_alphanumeric() || "-_=./:,@%+[]".contains(c)) {
+        arg.to_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Replaces the value of a `NAME=VALUE` variable assignment, which may hold a secret.
+fn redact_var(assignment: &str) -> String {
+    match assignment.find('=') {
+        Some(index) => format!("{}={}", &assignment[..index], REDACTED),
+        None => assignment.to_owned(),
+    }
+}
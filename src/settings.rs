@@ -0,0 +1,390 @@
+use std::fs::File;
+use std::io::{Read, Write};
+
+use ansi_term::Colour::{Green, Red};
+use clap::ArgMatches;
+use toml::{Value, from_str};
+
+use config::{ClusterConfig, config_path, toml_list, toml_string};
+use error::{KawsError, KawsResult};
+use prompt::confirm;
+use state::State;
+
+/// Reads and changes individual settings in a cluster's `kaws.toml`.
+///
+/// Settings are named by their path in the file, e.g. `instance_size` or `nodes.max_size`. Changes
+/// are made by editing only the lines that hold the changed settings, so comments and formatting
+/// elsewhere in the file are preserved.
+pub struct ClusterSettings<'a> {
+    assignments: Vec<&'a str>,
+    cluster: &'a str,
+    key: Option<&'a str>,
+    yes: bool,
+}
+
+/// A single setting's change to `kaws.toml`, as the lines removed and added.
+pub struct Change {
+    added: Vec<String>,
+    key: String,
+    removed: Vec<String>,
+}
+
+impl<'a> ClusterSettings<'a> {
+    pub fn new(matches: &'a ArgMatches) -> Self {
+        ClusterSettings {
+            assignments: matches.values_of("assignments").map_or(vec![], |values| values.collect()),
+            cluster: matches.value_of("cluster").expect("clap should have required cluster"),
+            key: matches.value_of("key"),
+            yes: matches.is_present("yes"),
+        }
+    }
+
+    pub fn get(&self) -> KawsResult {
+        let settings = settings(&ClusterConfig::load(self.cluster)?)?;
+
+        match self.key {
+            Some(key) => {
+                let value = lookup(&settings, key)?;
+
+                match value {
+                    Value::String(ref string) => println!("{}", string),
+                    Value::Array(ref values) => for value in values {
+                        match *value {
+                            Value::String(ref string) => println!("{}", string),
+                            ref value => println!("{}", value),
+                        }
+                    },
+                    ref value => println!("{}", value),
+                }
+            }
+            None => for (key, value) in settings.iter() {
+                println!("{} = {}", key, value);
+            },
+        }
+
+        Ok(None)
+    }
+
+    pub fn set(&self) -> KawsResult {
+        let path = config_path(self.cluster);
+        let settings = settings(&ClusterConfig::load(self.cluster)?)?;
+
+        let mut contents = String::new();
+
+        File::open(&path)?.read_to_string(&mut contents)?;
+
+        let mut changes = vec![];
+
+        for assignment in self.assignments.iter() {
+            let (key, raw_value) = match assignment.find('=') {
+                Some(index) => (assignment[..index].trim(), assignment[index + 1..].trim()),
+                None => return Err(KawsError::new(format!(
+                    "\"{}\" is not a valid setting. Settings must be given as key=value.",
+                    assignment,
+                ))),
+            };
+
+            if key == "node_pools" || key.starts_with("node_pools.") {
+                return Err(KawsError::new(format!(
+                    "{} cannot be set from the command line. Use `kaws cluster nodepool` or edit \
+                    kaws.toml instead.",
+                    key,
+                )));
+            }
+
+            let value = parse_value(key, raw_value, lookup(&settings, key)?)?;
+            let (edited, change) = edit(&contents, key, &value)?;
+
+            // Moving the servers between public and private subnets replaces etcd, which holds all
+            // of the cluster's data.
+            if key == "topology" && change.is_some() && !State::load(self.cluster)?.is_empty() {
+                return Err(KawsError::new(format!(
+                    "The topology of cluster \"{}\" cannot be changed once it has been applied, \
+                    since that would replace its etcd instances and the bastion.",
+                    self.cluster,
+                )));
+            }
+
+            contents = edited;
+
+            if let Some(change) = change {
+                changes.push(change);
+            }
+        }
+
+        if changes.is_empty() {
+            return Ok(Some(format!("No changes to {}.", path)));
+        }
+
+        let config = ClusterConfig::parse(&contents, &path)?;
+
+        config.validate(self.cluster)?;
+
+        for change in changes.iter() {
+            println!("{}:", change.key);
+
+            for line in change.removed.iter() {
+                println!("{}", Red.paint(format!("- {}", line)));
+            }
+
+            for line in change.added.iter() {
+                println!("{}", Green.paint(format!("+ {}", line)));
+            }
+        }
+
+        println!();
+
+        if !self.yes && !confirm(&format!("Write these changes to {}?", path), true)? {
+            return Err(KawsError::new(format!("{} was not changed.", path)));
+        }
+
+        log_wrap!("Writing kaws.toml", {
+            let mut file = File::create(&path)?;
+
+            file.write_all(contents.as_bytes())?;
+        });
+
+        config.write_tfvars(self.cluster)?;
+
+        Ok(Some(format!(
+            "Updated {}. Run `kaws cluster plan {}` to see the effect of the changes, \
+            and commit clusters/{} to Git.",
+            path,
+            self.cluster,
+            self.cluster,
+        )))
+    }
+}
+
+/// Lists every setting with its current value, including settings missing from the file, which
+/// have their default values.
+fn settings(config: &ClusterConfig) -> Result<Vec<(String, Value)>, KawsError> {
+    let value = Value::try_from(config).map_err(|error| {
+        KawsError::new(format!("Failed to serialize cluster settings: {}", error))
+    })?;
+
+    let mut settings = vec![];
+
+    flatten("", value, &mut settings);
+
+    Ok(settings)
+}
+
+fn flatten(prefix: &str, value: Value, settings: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Table(table) => for (key, value) in table {
+            let key = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+
+            flatten(&key, value, settings);
+        },
+        // Arrays of tables, such as node pools, are listed by the name of each table.
+        Value::Array(ref values) if !values.is_empty() && values.iter().all(|value| value.is_table()) => {
+            for (index, value) in values.iter().enumerate() {
+                let name = value.get("name").and_then(|name| name.as_str()).map_or(
+                    index.to_string(),
+                    |name| name.to_owned(),
+                );
+
+                flatten(&format!("{}.{}", prefix, name), value.clone(), settings);
+            }
+        }
+        value => settings.push((prefix.to_owned(), value)),
+    }
+}
+
+fn lookup<'b>(settings: &'b [(String, Value)], key: &str) -> Result<&'b Value, KawsError> {
+    match settings.iter().find(|&(name, _)| name == key) {
+        Some((_, value)) => Ok(value),
+        None => Err(KawsError::new(format!(
+            "Unknown setting \"{}\". Valid settings are:\n{}",
+            key,
+            settings.iter().map(|(name, _)| {
+                format!("  {}", name)
+            }).collect::<Vec<String>>().join("\n"),
+        ))),
+    }
+}
+
+/// Converts a value given on the command line to TOML, using the type of the setting's current
+/// value. Lists are given as comma-separated values, and an empty value clears the list.
+fn parse_value(key: &str, value: &str, current: &Value) -> Result<String, KawsError> {
+    match *current {
+        Value::Array(ref values) if values.iter().all(|value| value.is_str()) => {
+            let values: Vec<String> = value
+                .split(',')
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+                .map(|value| value.to_owned())
+                .collect();
+
+            Ok(toml_list(&values))
+        }
+        Value::Boolean(_) => match value {
+            "true" | "false" => Ok(value.to_owned()),
+            _ => Err(KawsError::new(format!("{} must be true or false.", key))),
+        },
+        Value::Integer(_) => match value.parse::<u32>() {
+            Ok(number) => Ok(number.to_string()),
+            Err(_) => Err(KawsError::new(format!("{} must be a non-negative integer.", key))),
+        },
+        Value::String(_) => Ok(toml_string(value)),
+        _ => Err(KawsError::new(format!(
+            "{} cannot be set from the command line. Edit kaws.toml instead.",
+            key,
+        ))),
+    }
+}
+
+/// Sets `key` to the TOML `value` in the document `contents`, replacing the lines that currently
+/// assign it or adding a line if the key is missing. All other lines are left as they are.
+pub fn edit(contents: &str, key: &str, value: &str) -> Result<(String, Option<Change>), KawsError> {
+    let (table, name) = match key.rfind('.') {
+        Some(index) => (Some(&key[..index]), &key[index + 1..]),
+        None => (None, key),
+    };
+
+    let mut lines: Vec<String> = contents.lines().map(|line| line.to_owned()).collect();
+    let mut current_table: Option<String> = None;
+    let mut first_table = None;
+    let mut table_end = None;
+    let mut found = None;
+
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with('[') {
+            first_table = first_table.or(Some(index));
+
+            current_table = Some(if trimmed.starts_with("[[") {
+                trimmed.to_owned()
+            } else {
+                trimmed.trim_start_matches('[').split(']').next().unwrap_or("").trim().to_owned()
+            });
+
+            continue;
+        }
+
+        if current_table.as_deref() != table {
+            continue;
+        }
+
+        if !trimmed.is_empty() && !trimmed.starts_with('#') {
+            table_end = Some(index);
+        }
+
+        if found.is_none() && assigned_key(trimmed) == Some(name) {
+            found = Some(index);
+        }
+    }
+
+    let new_line = format!("{} = {}", name, value);
+
+    let (start, end, indentation) = match found {
+        Some(start) => {
+            // A value such as a list may continue over several lines, so the assignment ends at
+            // the first line that completes a valid TOML value.
+            let mut end = start + 1;
+
+            while from_str::<Value>(&lines[start..end].join("\n")).is_err() {
+                if end == lines.len() {
+                    return Err(KawsError::new(format!(
+                        "Failed to find the end of {} in kaws.toml. Edit the file by hand instead.",
+                        key,
+                    )));
+                }
+
+                end += 1;
+            }
+
+            let indentation: String = lines[start].chars().take_while(|c| c.is_whitespace()).collect();
+
+            (start, end, indentation)
+        }
+        None => {
+            let position = match table {
+                Some(table) => match table_end {
+                    Some(index) => index + 1,
+                    None => {
+                        lines.push("".to_owned());
+                        lines.push(format!("[{}]", table));
+
+                        lines.len()
+                    }
+                },
+                None => match first_table {
+                    // Settings outside any table must come before the first table, and before any
+                    // comments that describe it.
+                    Some(mut index) => {
+                        while index > 0 && lines[index - 1].trim().starts_with('#') {
+                            index -= 1;
+                        }
+
+                        if index > 0 && lines[index - 1].trim().is_empty() {
+                            index -= 1;
+                        } else {
+                            lines.insert(index, "".to_owned());
+                        }
+
+                        index
+                    }
+                    None => lines.len(),
+                },
+            };
+
+            (position, position, "".to_owned())
+        }
+    };
+
+    let new_line = format!("{}{}", indentation, new_line);
+    let removed: Vec<String> = lines[start..end].to_vec();
+
+    if removed == [new_line.clone()] {
+        return Ok((contents.to_owned(), None));
+    }
+
+    let mut edited: Vec<String> = lines[..start].to_vec();
+
+    edited.push(new_line.clone());
+    edited.extend(lines[end..].iter().cloned());
+
+    let mut edited = edited.join("\n");
+
+    edited.push('\n');
+
+    // Make sure the edit did what was intended before showing it to the user.
+    let expected: Value = from_str(&format!("value = {}", value)).map_err(|error| {
+        KawsError::new(format!("Failed to parse the new value of {}: {}", key, error))
+    })?;
+
+    let document: Value = from_str(&edited).map_err(|error| {
+        KawsError::new(format!(
+            "Failed to update {} in kaws.toml: {}. Edit the file by hand instead.",
+            key,
+            error,
+        ))
+    })?;
+
+    let actual = key.split('.').try_fold(&document, |value, part| value.get(part));
+
+    if actual != expected.get("value") {
+        return Err(KawsError::new(format!(
+            "Failed to update {} in kaws.toml. Edit the file by hand instead.",
+            key,
+        )));
+    }
+
+    Ok((edited, Some(Change {
+        added: vec![new_line],
+        key: key.to_owned(),
+        removed: removed,
+    })))
+}
+
+/// The key assigned by a line of TOML, if the line is an assignment.
+fn assigned_key(line: &str) -> Option<&str> {
+    if line.starts_with('#') {
+        return None;
+    }
+
+    line.find('=').map(|index| line[..index].trim().trim_matches('"'))
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{Value, from_str};
+
+use error::KawsError;
+
+#[derive(Deserialize)]
+pub struct State {
+    #[serde(default)]
+    modules: Vec<Module>,
+}
+
+#[derive(Deserialize)]
+struct Module {
+    #[serde(default)]
+    outputs: HashMap<String, Value>,
+    path: Vec<String>,
+    #[serde(default)]
+    resources: HashMap<String, Resource>,
+}
+
+#[derive(Deserialize)]
+pub struct Resource {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub primary: Option<ResourceInstance>,
+}
+
+#[derive(Deserialize)]
+pub struct ResourceInstance {
+    pub id: String,
+    #[serde(default)]
+    pub attributes: HashMap<String, String>,
+}
+
+impl State {
+    /// Loads the Terraform state for the given cluster. A cluster that has never been applied has
+    /// no state file, which is treated the same as a state with no resources.
+    pub fn load(cluster: &str) -> Result<Self, KawsError> {
+        let mut file = match File::open(state_path(cluster)) {
+            Ok(file) => file,
+            Err(ref error) if error.kind() == ErrorKind::NotFound => {
+                return Ok(State { modules: vec![] });
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        if contents.trim().is_empty() {
+            return Ok(State { modules: vec![] });
+        }
+
+        Ok(from_str(&contents)?)
+    }
+
+    /// Whether the kaws module has no resources, i.e. the cluster hasn't been applied yet or has
+    /// been destroyed.
+    pub fn is_empty(&self) -> bool {
+        self.kaws_module().is_none_or(|module| module.resources.is_empty())
+    }
+
+    /// Whether the kaws module has the given output, which it doesn't until the cluster has been
+    /// applied.
+    pub fn has_output(&self, name: &str) -> bool {
+        self.kaws_module().is_some_and(|module| module.outputs.contains_key(name))
+    }
+
+    /// Returns the kaws module's resources of the given type, sorted by address.
+    pub fn resources_of_type(&self, kind: &str) -> Vec<(&str, &Resource)> {
+        let mut resources: Vec<(&str, &Resource)> = match self.kaws_module() {
+            Some(module) => module.resources.iter().filter(|&(_, resource)| {
+                resource.kind == kind
+            }).map(|(address, resource)| (address.as_str(), resource)).collect(),
+            None => vec![],
+        };
+
+        resources.sort_by(|a, b| a.0.cmp(b.0));
+
+        resources
+    }
+
+    // Private
+
+    fn kaws_module(&self) -> Option<&Module> {
+        self.modules.iter().find(|module| module.path == ["root", "kaws"])
+    }
+}
+
+pub fn state_path(cluster: &str) -> String {
+    format!("clusters/{}/terraform.tfstate", cluster)
+}
+
+pub fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock should be later than the Unix epoch")
+        .as_secs()
+}
+
+/// Formats a Unix timestamp as a UTC date and time, e.g. "2017-10-19 14:03:52 UTC".
+pub fn format_timestamp(timestamp: u64) -> String {
+    let seconds = timestamp % 86400;
+
+    // Converts days since the Unix epoch to a civil date, from Howard Hinnant's `civil_from_days`.
+    let z = (timestamp / 86400) as i64 + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+    )
+}
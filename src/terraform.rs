@@ -1,201 +1,663 @@
-use std::process::{Command, Stdio};
+use std::fs::{create_dir_all, remove_file, OpenOptions};
+use std::process::Command;
 
 use clap::ArgMatches;
-use rusoto_core::{ChainProvider, ProvideAwsCredentials};
+use serde_json::{Map, Value, from_slice, from_value, to_string_pretty, to_value};
+
+use aws::{CredentialsProvider, credentials_provider};
+use cluster::{Cluster, ClusterProtection};
+use config::{ClusterConfig, config_path, tfvars_path};
+use error::{KawsError, KawsExitResult, KawsResult, Success};
+use history::StateHistory;
+use prompt::{confirm, read_line};
+use repository::ignore_path;
+use resources::{MODULE_PATH, resolve_address, role_targets};
+use runner::{TerraformCommand, check_exit_status};
+use state::{State, timestamp};
+
+// Runs on an etcd member to produce a gzipped tarball of a backup of etcd's data directory on
+// stdout.
+const ETCD_SNAPSHOT_COMMAND: &'static str = "\
+sudo rm -rf /tmp/kaws-etcd-backup && \
+sudo etcdctl backup --data-dir /var/lib/etcd2/data --backup-dir /tmp/kaws-etcd-backup && \
+sudo tar -czf - -C /tmp kaws-etcd-backup && \
+sudo rm -rf /tmp/kaws-etcd-backup";
+
+/// The outputs of the kaws Terraform module for a cluster, as defined in `outputs.tf`.
+#[derive(Deserialize, Serialize)]
+pub struct Outputs {
+    pub domain: String,
+    pub internet_gateway_id: String,
+    /// The names of the node pools' autoscaling groups, in the order of
+    /// `ClusterConfig::node_pool_group_indexes`.
+    #[serde(default)]
+    pub kubernetes_node_pool_autoscaling_groups: Vec<String>,
+    pub kubernetes_nodes_elb_dns_name: String,
+    pub kubernetes_nodes_elb_zone_id: String,
+    pub kubernetes_route_table_id: String,
+    pub kubernetes_security_group_id: String,
+    pub kubernetes_subnet_id: String,
+    #[serde(default)]
+    pub kubernetes_subnet_ids: Vec<String>,
+    pub main_route_table_id: String,
+    #[serde(default)]
+    pub nat_gateway_public_ips: Vec<String>,
+    pub pki_kms_key: String,
+    #[serde(default)]
+    pub private_route_table_ids: Vec<String>,
+    pub region: String,
+    pub ssh_bastion_security_group_id: String,
+    pub vpc_id: String,
+}
 
-use aws::credentials_provider;
-use error::{KawsError, KawsResult};
+#[derive(Deserialize)]
+struct RawOutput {
+    value: Value,
+}
 
 pub struct Terraform<'a> {
-    aws_credentials_provider: ChainProvider,
+    addresses: Vec<&'a str>,
+    aws_credentials_provider: CredentialsProvider,
     cluster: &'a str,
+    destination: Option<&'a str>,
+    detailed_exitcode: bool,
+    dry_run: bool,
+    id: Option<&'a str>,
+    json: bool,
+    only: Vec<&'a str>,
     output: Option<&'a str>,
+    source: Option<&'a str>,
+    state_history: StateHistory<'a>,
     terraform_args: Option<Vec<&'a str>>,
 }
 
 impl<'a> Terraform<'a> {
     pub fn new(matches: &'a ArgMatches) -> Terraform<'a> {
+        let aws_credentials_provider = credentials_provider(
+            matches.value_of("cluster"),
+            matches.value_of("region"),
+            matches.value_of("aws-credentials-path"),
+            matches.value_of("aws-credentials-profile"),
+        );
+
         Terraform {
-            aws_credentials_provider: credentials_provider(
-                matches.value_of("aws-credentials-path"),
-                matches.value_of("aws-credentials-profile"),
-            ),
+            addresses: matches.values_of("address").map_or(vec![], |values| values.collect()),
+            aws_credentials_provider: aws_credentials_provider.clone(),
             cluster: matches.value_of("cluster").expect("clap should have required cluster"),
+            destination: matches.value_of("destination"),
+            detailed_exitcode: matches.is_present("detailed-exitcode"),
+            dry_run: matches.is_present("dry-run"),
+            id: matches.value_of("id"),
+            json: matches.is_present("json"),
+            only: matches.values_of("only").map_or(vec![], |values| values.collect()),
             output: matches.value_of("output"),
+            source: matches.value_of("source"),
+            state_history: StateHistory::with_credentials_provider(
+                matches,
+                aws_credentials_provider,
+            ),
             terraform_args: matches.values_of("terraform-args").map(|values| values.collect()),
         }
     }
 
     pub fn apply(&mut self) -> KawsResult {
-        self.init()?;
-
-        let mut command = Command::new("terraform");
-
-        command.args(&[
-            "apply",
-            "-backup=-",
-            &format!("-state=clusters/{}/terraform.tfstate", self.cluster),
-            &format!("-var-file=clusters/{}/terraform.tfvars", self.cluster),
-        ]);
-
-        if self.terraform_args.is_some() {
-            command.args(self.terraform_args.as_ref().unwrap());
-        }
-
-        command.arg("terraform").env(
-            "AWS_ACCESS_KEY_ID",
-            self.aws_credentials_provider.credentials().expect(
-                "Failed to get AWS credentials"
-            ).aws_access_key_id(),
-        ).env(
-            "AWS_SECRET_ACCESS_KEY",
-            self.aws_credentials_provider.credentials().expect(
-                "Failed to get AWS credentials"
-            ).aws_secret_access_key(),
-        );
+        self.prepare()?;
 
-        command.status()?;
+        if let Some(warning) = self.shifted_node_pools_warning()? {
+            println!("{}", warning);
 
-        Ok(None)
+            if !self.dry_run && !confirm("Apply anyway?", false)? {
+                return Err(KawsError::new(format!(
+                    "Cluster \"{}\" was not changed.",
+                    self.cluster,
+                )));
+            }
+        }
+
+        self.sync_master_cert()?;
+
+        if !self.dry_run {
+            self.state_history.save("apply")?;
+        }
+
+        let mut command = self.command("apply");
+
+        command.arg("-backup=-").state(self.cluster).var_file(self.cluster).config();
+
+        self.add_role_targets(&mut command);
+
+        command.run()
+    }
+
+    /// The provider of the AWS credentials Terraform runs with, for commands that also call AWS
+    /// themselves, so they share the same session.
+    pub fn aws_credentials_provider(&self) -> CredentialsProvider {
+        self.aws_credentials_provider.clone()
     }
 
     pub fn destroy(&mut self) -> KawsResult {
-        self.init()?;
-
-        let mut command = Command::new("terraform");
-
-        command.args(&[
-            "destroy",
-            "-backup=-",
-            &format!("-state=clusters/{}/terraform.tfstate", self.cluster),
-            &format!("-var-file=clusters/{}/terraform.tfvars", self.cluster),
-        ]);
-
-        if self.terraform_args.is_some() {
-            command.args(self.terraform_args.as_ref().unwrap());
-        }
-
-        command.arg("terraform").env(
-            "AWS_ACCESS_KEY_ID",
-            self.aws_credentials_provider.credentials().expect(
-                "Failed to get AWS credentials"
-            ).aws_access_key_id(),
-        ).env(
-            "AWS_SECRET_ACCESS_KEY",
-            self.aws_credentials_provider.credentials().expect(
-                "Failed to get AWS credentials"
-            ).aws_secret_access_key(),
-        );
+        if ClusterProtection::new(self.cluster).is_enabled() {
+            return Err(KawsError::new(format!(
+                "Cluster \"{cluster}\" is protected and cannot be destroyed. \
+                Run `kaws cluster unprotect {cluster}` first if you really want to destroy it.",
+                cluster = self.cluster,
+            )));
+        }
 
-        let exit_status = command.status()?;
+        // Terraform accepts its flags with one or two dashes, and booleans as `-force=true`.
+        let forced = self.terraform_args.as_ref().is_some_and(|args| args.iter().any(|arg| {
+            arg.strip_prefix("--").or_else(|| arg.strip_prefix('-')).is_some_and(|flag| {
+                flag == "force" || flag.starts_with("force=")
+            })
+        }));
+
+        if forced {
+            return Err(KawsError::new(
+                "-force cannot be passed to `terraform destroy`. \
+                kaws asks for confirmation before destroying a cluster.".to_owned()
+            ));
+        }
+
+        self.prepare()?;
+
+        if self.dry_run {
+            return self.command("destroy")
+                .args(&["-backup=-", "-force"])
+                .state(self.cluster)
+                .var_file(self.cluster)
+                .config()
+                .run();
+        }
+
+        let state = State::load(self.cluster)?;
 
-        if exit_status.success() {
-            Ok(Some(format!(
+        self.warn_about_data_loss(&state);
+
+        if confirm("Take a final snapshot of etcd's data before destroying the cluster?", true)? {
+            self.snapshot_etcd(&state)?;
+        }
+
+        let answer = read_line(&format!(
+            "Type the name of the cluster to confirm that \"{}\" should be destroyed: ",
+            self.cluster,
+        ))?;
+
+        if answer != self.cluster {
+            return Err(KawsError::new(
+                "The name entered did not match the cluster. Nothing was destroyed.".to_owned()
+            ));
+        }
+
+        self.state_history.save("destroy")?;
+
+        let exit_status = self.command("destroy")
+            .args(&["-backup=-", "-force"])
+            .state(self.cluster)
+            .var_file(self.cluster)
+            .config()
+            .status()?;
+
+        match exit_status {
+            Some(exit_status) if exit_status.success() => Ok(Some(format!(
                 "Destroyed cluster \"{}\"! You should remove clusters/{} from Git.",
                 self.cluster,
                 self.cluster,
-            )))
-        } else {
-            Err(KawsError::new(format!("Failed to destroy cluster!")))
+            ))),
+            Some(exit_status) => check_exit_status("destroy", exit_status),
+            None => Ok(None),
         }
     }
 
+    pub fn import(&mut self) -> KawsResult {
+        let address = resolve_address(self.address())?;
+
+        self.prepare()?;
+
+        if !self.dry_run {
+            self.state_history.save("import")?;
+        }
+
+        self.command("import")
+            .arg("-backup=-")
+            .state(self.cluster)
+            .var_file(self.cluster)
+            .config_option()
+            .operand(address)
+            .operand(self.id.expect("clap should have required id"))
+            .run()
+    }
+
     pub fn output(&mut self) -> KawsResult {
-        self.init()?;
+        if self.json {
+            let outputs = to_value(Outputs::load(self.cluster)?)?;
+
+            let value = match self.output {
+                Some(name) => match outputs.get(name) {
+                    Some(value) => value.clone(),
+                    None => return Err(KawsError::new(format!("Unknown output \"{}\".", name))),
+                },
+                None => outputs,
+            };
+
+            println!("{}", to_string_pretty(&value)?);
+
+            return Ok(None);
+        }
 
-        let mut command = Command::new("terraform");
+        Terraform::init()?;
 
-        command.args(&[
-            "output",
-            "-module=kaws",
-            &format!("-state=clusters/{}/terraform.tfstate", self.cluster),
-        ]);
+        let mut command = TerraformCommand::new("output");
+
+        command.arg("-module=kaws").state(self.cluster);
 
         if let Some(output) = self.output {
             command.arg(output);
         }
 
-        command.status()?;
+        command.run()
+    }
 
-        Ok(None)
+    pub fn plan(&mut self) -> KawsExitResult {
+        self.prepare()?;
+        self.report_stale_master_cert()?;
+
+        if let Some(warning) = self.shifted_node_pools_warning()? {
+            println!("{}", warning);
+        }
+
+        let mut command = self.command("plan");
+
+        command.arg("-module-depth=-1").state(self.cluster).var_file(self.cluster).config();
+
+        self.add_role_targets(&mut command);
+
+        if self.detailed_exitcode {
+            command.arg("-detailed-exitcode");
+        }
+
+        let exit_status = match command.status()? {
+            Some(exit_status) => exit_status,
+            None => return Ok(Success::from(None)),
+        };
+
+        // With -detailed-exitcode, Terraform exits with 2 when the plan succeeded but contains
+        // changes, so the distinction is passed on to the caller rather than reported as a failure
+        // to run the plan.
+        if exit_status.code() == Some(2) && self.uses_detailed_exitcode() {
+            return Ok(Success::with_exit_code(
+                format!("The plan for cluster \"{}\" contains changes.", self.cluster),
+                2,
+            ));
+        }
+
+        check_exit_status("plan", exit_status).map(Success::from)
+    }
+
+    pub fn refresh(&mut self) -> KawsResult {
+        self.prepare()?;
+
+        if !self.dry_run {
+            self.state_history.save("refresh")?;
+        }
+
+        self.command("refresh")
+            .arg("-backup=-")
+            .state(self.cluster)
+            .var_file(self.cluster)
+            .config()
+            .run()
     }
 
-    pub fn plan(&mut self) -> KawsResult {
-        self.init()?;
+    pub fn state_mv(&mut self) -> KawsResult {
+        let source = resolve_address(self.source.expect("clap should have required source"))?;
+        let destination = self.destination.expect("clap should have required destination");
+        let in_module = source.starts_with(&format!("{}.", MODULE_PATH));
 
-        let mut command = Command::new("terraform");
+        // A destination without a module stays in the source's module, and a bare name that isn't
+        // a kaws resource keeps the source's type, which makes renaming a kaws resource as simple
+        // as `kaws cluster state mv CLUSTER etcd_01 etcd_04`.
+        let destination = if destination.starts_with("module.") {
+            destination.to_owned()
+        } else if in_module && !destination.contains('.') && resolve_address(destination).is_err() {
+            let kind = &source[..source.rfind('.').expect("resolved addresses should have a type")];
 
-        command.args(&[
-            "plan",
-            "-module-depth=-1",
-            &format!("-state=clusters/{}/terraform.tfstate", self.cluster),
-            &format!("-var-file=clusters/{}/terraform.tfvars", self.cluster),
-        ]);
+            format!("{}.{}", kind, destination)
+        } else {
+            let resolved = resolve_address(destination)?;
+
+            if in_module && !resolved.starts_with("module.") {
+                format!("{}.{}", MODULE_PATH, resolved)
+            } else {
+                resolved
+            }
+        };
 
-        if self.terraform_args.is_some() {
-            command.args(self.terraform_args.as_ref().unwrap());
+        if !self.dry_run {
+            self.state_history.save("state-mv")?;
         }
 
-        command.arg("terraform").env(
-            "AWS_ACCESS_KEY_ID",
-            self.aws_credentials_provider.credentials().expect(
-                "Failed to get AWS credentials"
-            ).aws_access_key_id(),
-        ).env(
-            "AWS_SECRET_ACCESS_KEY",
-            self.aws_credentials_provider.credentials().expect(
-                "Failed to get AWS credentials"
-            ).aws_secret_access_key(),
-        );
+        TerraformCommand::new("state mv")
+            .dry_run(self.dry_run)
+            .arg("-backup=-")
+            .state(self.cluster)
+            .operand(source)
+            .operand(destination)
+            .run()
+    }
+
+    pub fn state_rm(&mut self) -> KawsResult {
+        let mut command = TerraformCommand::new("state rm");
+
+        command.dry_run(self.dry_run).arg("-backup=-").state(self.cluster);
+
+        for address in &self.addresses {
+            command.operand(resolve_address(address)?);
+        }
+
+        if !self.dry_run {
+            self.state_history.save("state-rm")?;
+        }
+
+        command.run()
+    }
+
+    pub fn taint(&mut self) -> KawsResult {
+        let address = resolve_address(self.address())?;
+        let module_prefix = format!("{}.", MODULE_PATH);
+
+        let mut command = TerraformCommand::new("taint");
 
-        command.status()?;
+        command.dry_run(self.dry_run).arg("-backup=-").state(self.cluster);
+
+        // `terraform taint` takes the module as an option and the address relative to it.
+        if address.starts_with(&module_prefix) {
+            command.arg(format!("-module={}", &MODULE_PATH["module.".len()..]));
+            command.operand(&address[module_prefix.len()..]);
+        } else {
+            command.operand(address.as_str());
+        }
+
+        if !self.dry_run {
+            self.state_history.save("taint")?;
+        }
+
+        command.run()
+    }
+
+    // Private
+
+    /// Limits the command to the resources of the roles selected with `--only`.
+    fn add_role_targets(&self, command: &mut TerraformCommand) {
+        for role in &self.only {
+            for target in role_targets(role) {
+                command.arg(format!("-target={}", target));
+            }
+        }
+    }
+
+    fn address(&self) -> &'a str {
+        self.addresses.first().cloned().expect("clap should have required address")
+    }
+
+    /// Starts a command for a Terraform subcommand that talks to AWS, with the user's extra
+    /// arguments and AWS credentials.
+    fn command<'b>(&'b self, subcommand: &'b str) -> TerraformCommand<'b> {
+        let mut command = TerraformCommand::new(subcommand);
+
+        command
+            .credentials(&self.aws_credentials_provider)
+            .dry_run(self.dry_run)
+            .extra_args(self.terraform_args.as_ref());
+
+        command
+    }
+
+    /// Initializes Terraform and renders the cluster's `terraform.tfvars` from its `kaws.toml`. A
+    /// dry run only validates `kaws.toml` and prints what would be done, leaving the working tree
+    /// unchanged.
+    fn prepare(&self) -> KawsResult {
+        if !self.dry_run {
+            Terraform::init()?;
+
+            return ClusterConfig::render_tfvars(self.cluster);
+        }
+
+        println!("{}", TerraformCommand::new("init").config().command_line());
+
+        if ClusterConfig::exists(self.cluster) {
+            ClusterConfig::load(self.cluster)?.validate(self.cluster)?;
+
+            println!(
+                "# Render {} from {}",
+                tfvars_path(self.cluster),
+                config_path(self.cluster),
+            );
+        }
 
         Ok(None)
     }
 
-    pub fn refresh(&mut self) -> KawsResult {
-        self.init()?;
-
-        let mut command = Command::new("terraform");
-
-        command.args(&[
-            "refresh",
-            "-backup=-",
-            &format!("-state=clusters/{}/terraform.tfstate", self.cluster),
-            &format!("-var-file=clusters/{}/terraform.tfvars", self.cluster),
-        ]);
-
-        if self.terraform_args.is_some() {
-            command.args(self.terraform_args.as_ref().unwrap());
-        }
-
-        command.arg("terraform").env(
-            "AWS_ACCESS_KEY_ID",
-            self.aws_credentials_provider.credentials().expect(
-                "Failed to get AWS credentials"
-            ).aws_access_key_id(),
-        ).env(
-            "AWS_SECRET_ACCESS_KEY",
-            self.aws_credentials_provider.credentials().expect(
-                "Failed to get AWS credentials"
-            ).aws_secret_access_key(),
+    /// Points out that `apply` will regenerate the masters' certificate, which the plan can't show
+    /// since the certificate is only read from its file.
+    fn report_stale_master_cert(&self) -> KawsResult {
+        if !ClusterConfig::exists(self.cluster) {
+            return Ok(None);
+        }
+
+        let config = ClusterConfig::load(self.cluster)?;
+
+        let stale = Cluster::new(self.cluster, &config.region).stale_master_cert(&config)?;
+
+        if let Some(service_ip) = stale {
+            println!(
+                "The Kubernetes masters' certificate does not include {}, the address of the \
+                Kubernetes API service. `kaws cluster apply {}` will regenerate it before applying \
+                the plan, which also changes the masters' cloud-config.",
+                service_ip,
+                self.cluster,
+            );
+        }
+
+        Ok(None)
+    }
+
+    /// Describes the node pools whose autoscaling groups Terraform will replace, along with their
+    /// instances, because the groups at their positions in `aws_autoscaling_group.k8s_node_pools`
+    /// have changed since the last apply, e.g. after a pool before them was removed.
+    fn shifted_node_pools_warning(&self) -> Result<Option<String>, KawsError> {
+        if !ClusterConfig::exists(self.cluster) {
+            return Ok(None);
+        }
+
+        let config = ClusterConfig::load(self.cluster)?;
+        let state = State::load(self.cluster)?;
+        let groups = config.node_pool_group_names();
+        let prefix = format!("kaws-k8s-nodes-{}-", self.cluster);
+        let mut pools: Vec<&str> = vec![];
+
+        for (address, resource) in state.resources_of_type("aws_autoscaling_group") {
+            // Terraform leaves the index off the address while a resource's count is 1.
+            let index = match address {
+                "aws_autoscaling_group.k8s_node_pools" => 0,
+                _ => match address.strip_prefix("aws_autoscaling_group.k8s_node_pools.").and_then(|index| {
+                    index.parse().ok()
+                }) {
+                    Some(index) => index,
+                    None => continue,
+                },
+            };
+
+            let (pool, group) = match groups.get(index) {
+                Some(&(pool, ref group)) => (pool, group),
+                None => continue,
+            };
+
+            // The group's name is its prefix followed by a unique ID without dashes.
+            let applied = resource.primary.as_ref().and_then(|instance| {
+                instance.id.strip_prefix(&prefix)
+            }).and_then(|rest| rest.rfind('-').map(|dash| &rest[..dash]));
+
+            if applied != Some(group.as_str()) && !pools.contains(&pool) {
+                pools.push(pool);
+            }
+        }
+
+        if pools.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(format!(
+            "Node pools are identified by their position in kaws.toml, and the positions of these \
+            pools' autoscaling groups have changed since the last apply, so Terraform will replace \
+            the groups and all of their instances: {}. The old instances are terminated without \
+            their nodes being cordoned or drained, so drain them with `kubectl drain` first to \
+            reschedule their pods gracefully.",
+            pools.join(", "),
+        )))
+    }
+
+    /// Keeps the masters' certificate in step with the cluster's service network before applying.
+    /// A dry run leaves the certificate as it is.
+    fn sync_master_cert(&self) -> KawsResult {
+        if self.dry_run || !ClusterConfig::exists(self.cluster) {
+            return Ok(None);
+        }
+
+        let config = ClusterConfig::load(self.cluster)?;
+
+        Cluster::new(self.cluster, &config.region)
+            .sync_master_cert(&config, &self.aws_credentials_provider)
+    }
+
+    fn snapshot_etcd(&self, state: &State) -> KawsResult {
+        let domain = Outputs::load(self.cluster)?.domain;
+
+        let etcd_ip = state.resources_of_type("aws_instance").into_iter().filter(|&(address, _)| {
+            address.starts_with("aws_instance.etcd")
+        }).filter_map(|(_, resource)| {
+            resource.primary.as_ref().and_then(|instance| instance.attributes.get("private_ip"))
+        }).next();
+
+        let etcd_ip = match etcd_ip {
+            Some(etcd_ip) => etcd_ip,
+            None => return Err(KawsError::new(
+                "No etcd instances were found in the Terraform state.".to_owned()
+            )),
+        };
+
+        let snapshot_path = format!(
+            "{}/etcd-{}.tar.gz",
+            backups_path(self.cluster),
+            timestamp(),
         );
 
-        command.status()?;
+        // Clusters created by older versions of kaws don't ignore their backups.
+        ignore_path(&format!("clusters/{}/.gitignore", self.cluster), "backups/")?;
+        create_dir_all(backups_path(self.cluster))?;
+
+        let snapshot_file = OpenOptions::new().write(true).create_new(true).open(&snapshot_path)?;
+
+        let exit_status = Command::new("ssh").args([
+            "-o",
+            &format!("ProxyCommand=ssh -W %h:%p core@bastion.{}", domain),
+            &format!("core@{}", etcd_ip),
+            ETCD_SNAPSHOT_COMMAND,
+        ]).stdout(snapshot_file).status()?;
+
+        if !exit_status.success() {
+            remove_file(&snapshot_path)?;
+
+            return Err(KawsError::new(format!(
+                "Failed to take a snapshot of etcd's data from {}.",
+                etcd_ip,
+            )));
+        }
+
+        println!(
+            "Saved a snapshot of etcd's data to {}. It contains cluster secrets, so do not commit \
+            it to Git.",
+            snapshot_path,
+        );
 
         Ok(None)
     }
 
-    fn init(&self) -> KawsResult {
-        let exit_status = Command::new("terraform").args(&[
-            "init",
-            "terraform",
-        ]).stdout(Stdio::null()).status()?;
+    fn warn_about_data_loss(&self, state: &State) {
+        let volumes = state.resources_of_type("aws_ebs_volume");
+        let buckets = state.resources_of_type("aws_s3_bucket");
+
+        if volumes.is_empty() && buckets.is_empty() {
+            return;
+        }
+
+        println!("Destroying cluster \"{}\" will permanently delete:\n", self.cluster);
+
+        for (address, resource) in volumes.into_iter().chain(buckets) {
+            if let Some(ref instance) = resource.primary {
+                println!("* {} ({})", instance.id, address);
+            }
+        }
+
+        println!();
+    }
+
+    fn uses_detailed_exitcode(&self) -> bool {
+        self.detailed_exitcode || self.terraform_args.as_ref().is_some_and(|args| {
+            args.contains(&"-detailed-exitcode")
+        })
+    }
+
+    pub fn init() -> KawsResult {
+        let output = TerraformCommand::new("init").config().output()?;
 
-        if exit_status.success() {
+        if output.status.success() {
             Ok(None)
         } else {
-            Err(KawsError::new("Failed to initialize Terraform!".to_string()))
+            Err(KawsError::with_std_streams(
+                "Failed to initialize Terraform!".to_string(),
+                String::from_utf8_lossy(&output.stdout).to_string(),
+                String::from_utf8_lossy(&output.stderr).to_string(),
+            ))
         }
     }
 }
+
+impl Outputs {
+    pub fn load(cluster: &str) -> Result<Self, KawsError> {
+        Terraform::init()?;
+
+        let output = TerraformCommand::new("output")
+            .args(&["-json", "-module=kaws"])
+            .state(cluster)
+            .output()?;
+
+        if !output.status.success() {
+            return Err(KawsError::with_std_streams(
+                format!("Failed to read the Terraform outputs for cluster \"{}\".", cluster),
+                String::from_utf8_lossy(&output.stdout).to_string(),
+                String::from_utf8_lossy(&output.stderr).to_string(),
+            ));
+        }
+
+        let raw: Map<String, Value> = from_slice(&output.stdout)?;
+        let mut values = Map::new();
+
+        for (name, raw_output) in raw {
+            let raw_output: RawOutput = from_value(raw_output)?;
+
+            values.insert(name, raw_output.value);
+        }
+
+        from_value(Value::Object(values)).map_err(|error| KawsError::new(format!(
+            "The Terraform outputs for cluster \"{}\" are incomplete ({}). \
+            Run `kaws cluster apply {}` to update them.",
+            cluster,
+            error,
+            cluster,
+        )))
+    }
+}
+
+fn backups_path(cluster: &str) -> String {
+    format!("clusters/{}/backups", cluster)
+}
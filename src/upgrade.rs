@@ -0,0 +1,145 @@
+use std::fs::File;
+use std::io::{Read, Write};
+
+use clap::ArgMatches;
+
+use config::{ClusterConfig, config_path, toml_string};
+use error::{KawsError, KawsResult};
+use prompt::confirm;
+use rollout::{Rollout, state_groups};
+use settings::edit;
+use state::State;
+use terraform::Terraform;
+use version::KubernetesVersion;
+
+/// Upgrades a cluster to a new version of Kubernetes.
+///
+/// The version in `kaws.toml` is changed and applied, which gives the masters' and nodes'
+/// autoscaling groups new launch configurations, and then the instances launched from the old ones
+/// are replaced: the masters first, one at a time, and then the nodes of the default group and the
+/// node pools, each drained before it is terminated. Running the same upgrade again after an
+/// interruption picks up with the instances that haven't been replaced yet.
+pub struct Upgrade<'a> {
+    cluster: &'a str,
+    matches: &'a ArgMatches<'a>,
+    version: &'a str,
+    yes: bool,
+}
+
+impl<'a> Upgrade<'a> {
+    pub fn new(matches: &'a ArgMatches<'a>) -> Self {
+        Upgrade {
+            cluster: matches.value_of("cluster").expect("clap should have required cluster"),
+            matches: matches,
+            version: matches.value_of("version").expect("clap should have required version"),
+            yes: matches.is_present("yes"),
+        }
+    }
+
+    pub fn upgrade(&self) -> KawsResult {
+        let config = ClusterConfig::load(self.cluster)?;
+
+        let current: KubernetesVersion = config.kubernetes_version.parse().map_err(|error| {
+            KawsError::new(format!("kubernetes_version: {}", error))
+        })?;
+        let target: KubernetesVersion = self.version.parse().map_err(KawsError::new)?;
+
+        let question = if current == target {
+            format!(
+                "Cluster \"{}\" is already set to Kubernetes {}. Continue replacing the instances \
+                that still run an older version?",
+                self.cluster,
+                target,
+            )
+        } else {
+            current.check_upgrade(&target).map_err(KawsError::new)?;
+
+            format!(
+                "Upgrade cluster \"{}\" from Kubernetes {} to {}? Every master and node will be \
+                replaced.",
+                self.cluster,
+                current,
+                target,
+            )
+        };
+
+        if !self.yes && !confirm(&question, false)? {
+            return Err(KawsError::new(format!("Cluster \"{}\" was not upgraded.", self.cluster)));
+        }
+
+        if current != target {
+            self.set_version(&target)?;
+        }
+
+        let mut terraform = Terraform::new(self.matches);
+
+        // On Terraform versions that ask for confirmation, declining the apply also ends up here.
+        if let Err(error) = terraform.apply() {
+            return Err(KawsError::with_exit_code(
+                format!(
+                    "{} kubernetes_version in kaws.toml is set to {}, but no instances have been \
+                    replaced. Run `kaws cluster upgrade {} {}` again to resume the upgrade.",
+                    error,
+                    target,
+                    self.cluster,
+                    target,
+                ),
+                error.exit_code(),
+            ));
+        }
+
+        let credentials_provider = terraform.aws_credentials_provider();
+        let rollout = Rollout::new(self.cluster, &credentials_provider, &config.region);
+        let state = State::load(self.cluster)?;
+
+        let masters = state_groups(&state, |address| {
+            address == "aws_autoscaling_group.k8s_masters"
+        });
+
+        // This includes the node pools' groups, "aws_autoscaling_group.k8s_node_pools.N".
+        let nodes = state_groups(&state, |address| {
+            address.starts_with("aws_autoscaling_group.k8s_node")
+        });
+
+        for group in masters.iter() {
+            rollout.replace_stale(group, false, 1)?;
+        }
+
+        for group in nodes.iter() {
+            rollout.replace_stale(group, true, 1)?;
+        }
+
+        Ok(Some(format!(
+            "Cluster \"{}\" is running Kubernetes {}! Commit clusters/{} to Git.",
+            self.cluster,
+            target,
+            self.cluster,
+        )))
+    }
+
+    // Private
+
+    /// Changes `kubernetes_version` in `kaws.toml`, leaving the rest of the file as it is.
+    fn set_version(&self, version: &KubernetesVersion) -> Result<(), KawsError> {
+        let path = config_path(self.cluster);
+        let mut contents = String::new();
+
+        File::open(&path)?.read_to_string(&mut contents)?;
+
+        let value = toml_string(&version.to_string());
+        let (contents, _) = edit(&contents, "kubernetes_version", &value)?;
+        let config = ClusterConfig::parse(&contents, &path)?;
+
+        config.validate(self.cluster)?;
+
+        log_wrap!("Writing kaws.toml", {
+            let mut file = File::create(&path)?;
+
+            file.write_all(contents.as_bytes())?;
+        });
+
+        config.write_tfvars(self.cluster)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+/// A Kubernetes release version, e.g. "1.8.4" or "1.9.0-beta.2", parsed according to Semantic
+/// Versioning.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KubernetesVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// The pre-release identifiers after the "-", e.g. "beta.2".
+    pub pre_release: Option<String>,
+}
+
+/// The settings that differ between the Kubernetes minor versions kaws supports.
+pub struct Release {
+    pub minor: u32,
+    /// Whether the kubelet still takes `--api-servers`, rather than reading the API server's
+    /// address from its kubeconfig file.
+    pub kubelet_api_servers: bool,
+}
+
+/// The Kubernetes 1.x minor versions kaws supports, oldest first.
+///
+/// There is deliberately no setting for the API server's storage backend: every supported
+/// release is run with `--storage-backend=etcd2`, because kaws clusters run etcd2 and their data
+/// is stored in the v2 API. Moving to etcd3 requires upgrading the etcd cluster and migrating its
+/// data offline, which kaws doesn't do, so the flag stays pinned rather than following each
+/// release's default.
+pub const RELEASES: &'static [Release] = &[
+    Release { minor: 7, kubelet_api_servers: true },
+    Release { minor: 8, kubelet_api_servers: false },
+    Release { minor: 9, kubelet_api_servers: false },
+    Release { minor: 10, kubelet_api_servers: false },
+];
+
+impl KubernetesVersion {
+    /// The settings for this version's minor release, or an error listing the supported range.
+    pub fn release(&self) -> Result<&'static Release, String> {
+        let release = if self.major == 1 {
+            RELEASES.iter().find(|release| release.minor == self.minor)
+        } else {
+            None
+        };
+
+        release.ok_or_else(|| format!(
+            "Kubernetes {} is not supported by this version of kaws. Supported versions are 1.{}.0 \
+            through 1.{}.x.",
+            self,
+            RELEASES[0].minor,
+            RELEASES[RELEASES.len() - 1].minor,
+        ))
+    }
+
+    /// Checks that a cluster running this version can be upgraded to `target`. Kubernetes only
+    /// supports upgrading one minor version at a time, and downgrades aren't supported at all.
+    pub fn check_upgrade(&self, target: &KubernetesVersion) -> Result<(), String> {
+        target.release()?;
+
+        if target < self {
+            return Err(format!(
+                "The cluster runs Kubernetes {}, which is newer than {}. Downgrades are not \
+                supported.",
+                self,
+                target,
+            ));
+        }
+
+        if target.major != self.major || target.minor > self.minor + 1 {
+            return Err(format!(
+                "The cluster runs Kubernetes {}, so it can only be upgraded to a {}.{}.x or {}.{}.x \
+                release. Upgrade one minor version at a time.",
+                self,
+                self.major,
+                self.minor,
+                self.major,
+                self.minor + 1,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for KubernetesVersion {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+
+        if let Some(ref pre_release) = self.pre_release {
+            write!(f, "-{}", pre_release)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for KubernetesVersion {
+    type Err = String;
+
+    fn from_str(version: &str) -> Result<Self, Self::Err> {
+        if version.starts_with('v') {
+            return Err("Kubernetes version should be specified without the leading 'v'".to_owned());
+        }
+
+        let invalid = || format!(
+            "\"{}\" is not a valid Kubernetes version. Give the full version, e.g. \"1.8.4\".",
+            version,
+        );
+
+        let (core, pre_release) = match version.find('-') {
+            Some(index) => (&version[..index], Some(&version[index + 1..])),
+            None => (version, None),
+        };
+
+        let numbers: Vec<u32> = core.split('.').map(|number| {
+            // Leading zeros aren't allowed by Semantic Versioning.
+            if number.is_empty() || (number.len() > 1 && number.starts_with('0')) {
+                Err(())
+            } else {
+                number.parse().map_err(|_| ())
+            }
+        }).collect::<Result<_, _>>().map_err(|_| invalid())?;
+
+        if numbers.len() != 3 {
+            return Err(invalid());
+        }
+
+        if let Some(pre_release) = pre_release {
+            let valid = pre_release.split('.').all(|identifier| {
+                !identifier.is_empty() &&
+                    identifier.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            });
+
+            if !valid {
+                return Err(invalid());
+            }
+        }
+
+        Ok(KubernetesVersion {
+            major: numbers[0],
+            minor: numbers[1],
+            patch: numbers[2],
+            pre_release: pre_release.map(|pre_release| pre_release.to_owned()),
+        })
+    }
+}
+
+impl Ord for KubernetesVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch).cmp(&(other.major, other.minor, other.patch)).then_with(|| {
+            match (&self.pre_release, &other.pre_release) {
+                (&None, &None) => Ordering::Equal,
+                (&None, &Some(_)) => Ordering::Greater,
+                (&Some(_), &None) => Ordering::Less,
+                (Some(a), Some(b)) => compare_pre_releases(a, b),
+            }
+        })
+    }
+}
+
+impl PartialOrd for KubernetesVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compares pre-release identifiers as Semantic Versioning specifies: numeric identifiers
+/// numerically, others lexically, numeric ones before the others, and shorter lists first.
+fn compare_pre_releases(a: &str, b: &str) -> Ordering {
+    let mut a_identifiers = a.split('.');
+    let mut b_identifiers = b.split('.');
+
+    loop {
+        let ordering = match (a_identifiers.next(), b_identifiers.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) => match (a.parse::<u64>(), b.parse::<u64>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => a.cmp(b),
+            },
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KubernetesVersion;
+
+    fn version(version: &str) -> KubernetesVersion {
+        version.parse().unwrap()
+    }
+
+    #[test]
+    fn check_upgrade_allows_patch_and_next_minor_releases() {
+        assert!(version("1.8.4").check_upgrade(&version("1.8.4")).is_ok());
+        assert!(version("1.8.4").check_upgrade(&version("1.8.5")).is_ok());
+        assert!(version("1.8.4").check_upgrade(&version("1.9.0")).is_ok());
+        assert!(version("1.9.0-beta.2").check_upgrade(&version("1.9.0")).is_ok());
+    }
+
+    #[test]
+    fn check_upgrade_rejects_skipped_minor_releases() {
+        assert!(version("1.7.11").check_upgrade(&version("1.9.0")).is_err());
+    }
+
+    #[test]
+    fn check_upgrade_rejects_downgrades() {
+        assert!(version("1.9.0").check_upgrade(&version("1.8.4")).is_err());
+        assert!(version("1.9.0").check_upgrade(&version("1.9.0-rc.1")).is_err());
+    }
+
+    #[test]
+    fn check_upgrade_rejects_unsupported_releases() {
+        assert!(version("1.10.0").check_upgrade(&version("1.11.0")).is_err());
+        assert!(version("1.10.0").check_upgrade(&version("2.0.0")).is_err());
+    }
+}
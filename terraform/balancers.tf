@@ -3,7 +3,7 @@ resource "aws_elb" "k8s_masters" {
   idle_timeout = 3600
   name = "kaws-k8s-masters-${var.cluster}"
   security_groups = ["${aws_security_group.balancers.id}"]
-  subnets = ["${aws_subnet.balancers.id}"]
+  subnets = ["${aws_subnet.balancers.*.id}"]
 
   listener {
     instance_port = 443
@@ -20,10 +20,7 @@ resource "aws_elb" "k8s_masters" {
     unhealthy_threshold = 2
   }
 
-  tags {
-    Name = "kaws-k8s-masters"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-k8s-masters", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_elb" "k8s_nodes" {
@@ -31,7 +28,7 @@ resource "aws_elb" "k8s_nodes" {
   idle_timeout = 3600
   name = "kaws-k8s-nodes-${var.cluster}"
   security_groups = ["${aws_security_group.balancers.id}"]
-  subnets = ["${aws_subnet.balancers.id}"]
+  subnets = ["${aws_subnet.balancers.*.id}"]
 
   listener {
     instance_port = 30000
@@ -55,8 +52,5 @@ resource "aws_elb" "k8s_nodes" {
     unhealthy_threshold = 2
   }
 
-  tags {
-    Name = "kaws-k8s-nodes"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-k8s-nodes", "KubernetesCluster", var.cluster))}"
 }
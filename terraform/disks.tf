@@ -1,40 +1,55 @@
 resource "aws_ebs_volume" "etcd_01" {
-  availability_zone = "${var.availability_zone}"
+  availability_zone = "${element(var.availability_zones, 0)}"
   encrypted = true
   kms_key_id = "${aws_kms_key.etcd.arn}"
-  size = "10"
-  type = "gp2"
+  size = "${var.etcd_volume_size}"
+  type = "${var.etcd_volume_type}"
 
-  tags {
-    Name = "kaws-etcd-${var.cluster}-01"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-01", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_ebs_volume" "etcd_02" {
-  availability_zone = "${var.availability_zone}"
+  availability_zone = "${element(var.availability_zones, 1)}"
+  count = "${length(var.etcd_ips) >= 2 ? 1 : 0}"
   encrypted = true
   kms_key_id = "${aws_kms_key.etcd.arn}"
-  size = "10"
-  type = "gp2"
+  size = "${var.etcd_volume_size}"
+  type = "${var.etcd_volume_type}"
 
-  tags {
-    Name = "kaws-etcd-${var.cluster}-02"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-02", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_ebs_volume" "etcd_03" {
-  availability_zone = "${var.availability_zone}"
+  availability_zone = "${element(var.availability_zones, 2)}"
+  count = "${length(var.etcd_ips) >= 3 ? 1 : 0}"
   encrypted = true
   kms_key_id = "${aws_kms_key.etcd.arn}"
-  size = "10"
-  type = "gp2"
+  size = "${var.etcd_volume_size}"
+  type = "${var.etcd_volume_type}"
 
-  tags {
-    Name = "kaws-etcd-${var.cluster}-03"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-03", "KubernetesCluster", var.cluster))}"
+}
+
+resource "aws_ebs_volume" "etcd_04" {
+  availability_zone = "${element(var.availability_zones, 3)}"
+  count = "${length(var.etcd_ips) >= 4 ? 1 : 0}"
+  encrypted = true
+  kms_key_id = "${aws_kms_key.etcd.arn}"
+  size = "${var.etcd_volume_size}"
+  type = "${var.etcd_volume_type}"
+
+  tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-04", "KubernetesCluster", var.cluster))}"
+}
+
+resource "aws_ebs_volume" "etcd_05" {
+  availability_zone = "${element(var.availability_zones, 4)}"
+  count = "${length(var.etcd_ips) >= 5 ? 1 : 0}"
+  encrypted = true
+  kms_key_id = "${aws_kms_key.etcd.arn}"
+  size = "${var.etcd_volume_size}"
+  type = "${var.etcd_volume_type}"
+
+  tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-05", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_volume_attachment" "etcd_01" {
@@ -44,13 +59,29 @@ resource "aws_volume_attachment" "etcd_01" {
 }
 
 resource "aws_volume_attachment" "etcd_02" {
+  count = "${length(var.etcd_ips) >= 2 ? 1 : 0}"
   device_name = "/dev/xvdf"
-  instance_id = "${aws_instance.etcd_02.id}"
-  volume_id = "${aws_ebs_volume.etcd_02.id}"
+  instance_id = "${join("", aws_instance.etcd_02.*.id)}"
+  volume_id = "${join("", aws_ebs_volume.etcd_02.*.id)}"
 }
 
 resource "aws_volume_attachment" "etcd_03" {
+  count = "${length(var.etcd_ips) >= 3 ? 1 : 0}"
+  device_name = "/dev/xvdf"
+  instance_id = "${join("", aws_instance.etcd_03.*.id)}"
+  volume_id = "${join("", aws_ebs_volume.etcd_03.*.id)}"
+}
+
+resource "aws_volume_attachment" "etcd_04" {
+  count = "${length(var.etcd_ips) >= 4 ? 1 : 0}"
+  device_name = "/dev/xvdf"
+  instance_id = "${join("", aws_instance.etcd_04.*.id)}"
+  volume_id = "${join("", aws_ebs_volume.etcd_04.*.id)}"
+}
+
+resource "aws_volume_attachment" "etcd_05" {
+  count = "${length(var.etcd_ips) >= 5 ? 1 : 0}"
   device_name = "/dev/xvdf"
-  instance_id = "${aws_instance.etcd_03.id}"
-  volume_id = "${aws_ebs_volume.etcd_03.id}"
+  instance_id = "${join("", aws_instance.etcd_05.*.id)}"
+  volume_id = "${join("", aws_ebs_volume.etcd_05.*.id)}"
 }
@@ -1,8 +1,22 @@
 resource "aws_internet_gateway" "outgoing" {
   vpc_id = "${aws_vpc.kubernetes.id}"
 
-  tags {
-    Name = "kaws-outgoing-${var.cluster}"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-outgoing-${var.cluster}", "KubernetesCluster", var.cluster))}"
+}
+
+resource "aws_eip" "nat" {
+  count = "${var.topology == "private" ? length(var.availability_zones) : 0}"
+  vpc = true
+
+  tags = "${merge(var.tags, map("Name", "kaws-nat-${var.cluster}-${element(var.availability_zones, count.index)}", "KubernetesCluster", var.cluster))}"
+}
+
+resource "aws_nat_gateway" "outgoing" {
+  allocation_id = "${element(aws_eip.nat.*.id, count.index)}"
+  count = "${var.topology == "private" ? length(var.availability_zones) : 0}"
+  subnet_id = "${element(aws_subnet.balancers.*.id, count.index)}"
+
+  tags = "${merge(var.tags, map("Name", "kaws-nat-${var.cluster}-${element(var.availability_zones, count.index)}", "KubernetesCluster", var.cluster))}"
+
+  depends_on = ["aws_internet_gateway.outgoing"]
 }
@@ -6,6 +6,10 @@ output "internet_gateway_id" {
   value = "${aws_internet_gateway.outgoing.id}"
 }
 
+output "kubernetes_node_pool_autoscaling_groups" {
+  value = ["${aws_autoscaling_group.k8s_node_pools.*.name}"]
+}
+
 output "kubernetes_nodes_elb_dns_name" {
   value = "${aws_elb.k8s_nodes.dns_name}"
 }
@@ -15,7 +19,7 @@ output "kubernetes_nodes_elb_zone_id" {
 }
 
 output "kubernetes_route_table_id" {
-  value = "${aws_route_table.k8s.id}"
+  value = "${join("", aws_route_table.k8s.*.id)}"
 }
 
 output "kubernetes_security_group_id" {
@@ -23,13 +27,21 @@ output "kubernetes_security_group_id" {
 }
 
 output "kubernetes_subnet_id" {
-  value = "${aws_subnet.k8s.id}"
+  value = "${element(aws_subnet.k8s.*.id, 0)}"
+}
+
+output "kubernetes_subnet_ids" {
+  value = ["${aws_subnet.k8s.*.id}"]
 }
 
 output "main_route_table_id" {
   value = "${aws_vpc.kubernetes.main_route_table_id}"
 }
 
+output "nat_gateway_public_ips" {
+  value = ["${aws_nat_gateway.outgoing.*.public_ip}"]
+}
+
 output "pki_kms_key" {
   value = "${aws_kms_key.pki.key_id}"
 }
@@ -38,6 +50,10 @@ output "ssh_bastion_security_group_id" {
   value = "${aws_security_group.bastion.id}"
 }
 
+output "private_route_table_ids" {
+  value = ["${aws_route_table.private.*.id}"]
+}
+
 output "region" {
   value = "${var.region}"
 }
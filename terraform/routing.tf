@@ -1,20 +1,20 @@
 resource "aws_route_table" "k8s" {
+  count = "${var.topology == "public" ? 1 : 0}"
   propagating_vgws = ["${var.propagating_vgws}"]
   vpc_id = "${aws_vpc.kubernetes.id}"
 
-  tags {
-    Name = "kaws-k8s-${var.cluster}"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-k8s-${var.cluster}", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_route_table_association" "k8s" {
-  route_table_id = "${aws_route_table.k8s.id}"
-  subnet_id = "${aws_subnet.k8s.id}"
+  count = "${var.topology == "public" ? length(var.availability_zones) : 0}"
+  route_table_id = "${join("", aws_route_table.k8s.*.id)}"
+  subnet_id = "${element(aws_subnet.k8s.*.id, count.index)}"
 }
 
 resource "aws_route" "k8s_outgoing_traffic" {
-  route_table_id = "${aws_route_table.k8s.id}"
+  count = "${var.topology == "public" ? 1 : 0}"
+  route_table_id = "${join("", aws_route_table.k8s.*.id)}"
   destination_cidr_block = "0.0.0.0/0"
   gateway_id = "${aws_internet_gateway.outgoing.id}"
 }
@@ -24,3 +24,36 @@ resource "aws_route" "main_outgoing_traffic" {
   destination_cidr_block = "0.0.0.0/0"
   gateway_id = "${aws_internet_gateway.outgoing.id}"
 }
+
+/*
+Note: In the private topology, each Availability Zone has its own route table, which sends outgoing
+traffic from its etcd and Kubernetes subnets through the NAT gateway in the same zone, so that the
+zones don't depend on each other's NAT gateways.
+*/
+
+resource "aws_route_table" "private" {
+  count = "${var.topology == "private" ? length(var.availability_zones) : 0}"
+  propagating_vgws = ["${var.propagating_vgws}"]
+  vpc_id = "${aws_vpc.kubernetes.id}"
+
+  tags = "${merge(var.tags, map("Name", "kaws-private-${var.cluster}-${element(var.availability_zones, count.index)}", "KubernetesCluster", var.cluster))}"
+}
+
+resource "aws_route_table_association" "etcd_private" {
+  count = "${var.topology == "private" ? length(var.availability_zones) : 0}"
+  route_table_id = "${element(aws_route_table.private.*.id, count.index)}"
+  subnet_id = "${element(aws_subnet.etcd.*.id, count.index)}"
+}
+
+resource "aws_route_table_association" "k8s_private" {
+  count = "${var.topology == "private" ? length(var.availability_zones) : 0}"
+  route_table_id = "${element(aws_route_table.private.*.id, count.index)}"
+  subnet_id = "${element(aws_subnet.k8s.*.id, count.index)}"
+}
+
+resource "aws_route" "private_outgoing_traffic" {
+  count = "${var.topology == "private" ? length(var.availability_zones) : 0}"
+  route_table_id = "${element(aws_route_table.private.*.id, count.index)}"
+  destination_cidr_block = "0.0.0.0/0"
+  nat_gateway_id = "${element(aws_nat_gateway.outgoing.*.id, count.index)}"
+}
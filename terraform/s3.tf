@@ -1,10 +1,7 @@
 resource "aws_s3_bucket" "cloud_config" {
   bucket = "kaws-${var.account_id}-${var.cluster}"
 
-  tags {
-    Name = "kaws-${var.account_id}-${var.cluster}"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-${var.account_id}-${var.cluster}", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_s3_bucket_object" "bastion_cloud_config" {
@@ -22,17 +19,35 @@ resource "aws_s3_bucket_object" "etcd_01_cloud_config" {
 }
 
 resource "aws_s3_bucket_object" "etcd_02_cloud_config" {
+  count = "${length(var.etcd_ips) >= 2 ? 1 : 0}"
   bucket = "${aws_s3_bucket.cloud_config.id}"
   key = "etcd_02_cloud_config.yml"
-  content = "${data.template_file.etcd_02_cloud_config.rendered}"
-  etag = "${md5(data.template_file.etcd_02_cloud_config.rendered)}"
+  content = "${join("", data.template_file.etcd_02_cloud_config.*.rendered)}"
+  etag = "${md5(join("", data.template_file.etcd_02_cloud_config.*.rendered))}"
 }
 
 resource "aws_s3_bucket_object" "etcd_03_cloud_config" {
+  count = "${length(var.etcd_ips) >= 3 ? 1 : 0}"
   bucket = "${aws_s3_bucket.cloud_config.id}"
   key = "etcd_03_cloud_config.yml"
-  content = "${data.template_file.etcd_03_cloud_config.rendered}"
-  etag = "${md5(data.template_file.etcd_03_cloud_config.rendered)}"
+  content = "${join("", data.template_file.etcd_03_cloud_config.*.rendered)}"
+  etag = "${md5(join("", data.template_file.etcd_03_cloud_config.*.rendered))}"
+}
+
+resource "aws_s3_bucket_object" "etcd_04_cloud_config" {
+  count = "${length(var.etcd_ips) >= 4 ? 1 : 0}"
+  bucket = "${aws_s3_bucket.cloud_config.id}"
+  key = "etcd_04_cloud_config.yml"
+  content = "${join("", data.template_file.etcd_04_cloud_config.*.rendered)}"
+  etag = "${md5(join("", data.template_file.etcd_04_cloud_config.*.rendered))}"
+}
+
+resource "aws_s3_bucket_object" "etcd_05_cloud_config" {
+  count = "${length(var.etcd_ips) >= 5 ? 1 : 0}"
+  bucket = "${aws_s3_bucket.cloud_config.id}"
+  key = "etcd_05_cloud_config.yml"
+  content = "${join("", data.template_file.etcd_05_cloud_config.*.rendered)}"
+  etag = "${md5(join("", data.template_file.etcd_05_cloud_config.*.rendered))}"
 }
 
 resource "aws_s3_bucket_object" "master_cloud_config" {
@@ -48,3 +63,11 @@ resource "aws_s3_bucket_object" "node_cloud_config" {
   content = "${data.template_file.node_cloud_config.rendered}"
   etag = "${md5(data.template_file.node_cloud_config.rendered)}"
 }
+
+resource "aws_s3_bucket_object" "node_pool_cloud_config" {
+  count = "${length(var.node_pools)}"
+  bucket = "${aws_s3_bucket.cloud_config.id}"
+  key = "node_pool_${element(var.node_pools, count.index)}_cloud_config.yml"
+  content = "${element(data.template_file.node_pool_cloud_config.*.rendered, count.index)}"
+  etag = "${md5(element(data.template_file.node_pool_cloud_config.*.rendered, count.index))}"
+}
@@ -22,9 +22,7 @@ data "aws_iam_policy_document" "etcd" {
   statement {
     actions = ["s3:GetObject"]
     resources = [
-      "arn:aws:s3:::${aws_s3_bucket.cloud_config.id}/${aws_s3_bucket_object.etcd_01_cloud_config.id}",
-      "arn:aws:s3:::${aws_s3_bucket.cloud_config.id}/${aws_s3_bucket_object.etcd_02_cloud_config.id}",
-      "arn:aws:s3:::${aws_s3_bucket.cloud_config.id}/${aws_s3_bucket_object.etcd_03_cloud_config.id}",
+      "arn:aws:s3:::${aws_s3_bucket.cloud_config.id}/etcd_*_cloud_config.yml",
     ]
   }
 }
@@ -66,6 +64,7 @@ data "aws_iam_policy_document" "k8s_node" {
     actions = ["s3:GetObject"]
     resources = [
       "arn:aws:s3:::${aws_s3_bucket.cloud_config.id}/${aws_s3_bucket_object.node_cloud_config.id}",
+      "arn:aws:s3:::${aws_s3_bucket.cloud_config.id}/node_pool_*_cloud_config.yml",
     ]
   }
 
@@ -271,6 +270,8 @@ resource "aws_kms_key" "pki" {
   description = "kaws ${var.cluster} etcd and k8s PKI"
   policy = "${data.aws_iam_policy_document.kms_key.json}"
 
+  tags = "${merge(var.tags, map("Name", "kaws-${var.cluster}", "KubernetesCluster", var.cluster))}"
+
   provisioner "local-exec" {
     command = "kaws cluster generate-pki all ${var.cluster} --domain ${var.domain} --kms-key ${aws_kms_key.pki.key_id} --region ${var.region}"
   }
@@ -283,6 +284,8 @@ resource "aws_kms_alias" "pki" {
 
 resource "aws_kms_key" "etcd" {
   description = "kaws ${var.cluster} EBS encryption for etcd"
+
+  tags = "${merge(var.tags, map("Name", "kaws-${var.cluster}-etcd", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_kms_alias" "etcd" {
@@ -316,10 +319,7 @@ resource "aws_security_group" "balancers" {
     cidr_blocks = ["0.0.0.0/0"]
   }
 
-  tags {
-    Name = "kaws-balancers-${var.cluster}"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-balancers-${var.cluster}", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_security_group" "bastion" {
@@ -341,10 +341,7 @@ resource "aws_security_group" "bastion" {
     cidr_blocks = ["0.0.0.0/0"]
   }
 
-  tags {
-    Name = "kaws-bastion-${var.cluster}"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-bastion-${var.cluster}", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_security_group" "etcd" {
@@ -374,10 +371,7 @@ resource "aws_security_group" "etcd" {
     cidr_blocks = ["0.0.0.0/0"]
   }
 
-  tags {
-    Name = "kaws-etcd-${var.cluster}"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_security_group" "kubernetes" {
@@ -439,8 +433,5 @@ resource "aws_security_group" "kubernetes" {
     cidr_blocks = ["0.0.0.0/0"]
   }
 
-  tags {
-    Name = "kaws-k8s-${var.cluster}"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-k8s-${var.cluster}", "KubernetesCluster", var.cluster))}"
 }
@@ -2,73 +2,155 @@ resource "aws_instance" "bastion" {
   ami = "${var.coreos_ami}"
   associate_public_ip_address = true
   iam_instance_profile = "${aws_iam_instance_profile.bastion.name}"
-  instance_type = "t2.micro"
-  subnet_id = "${aws_subnet.k8s.id}"
+  instance_type = "${var.bastion_instance_type}"
+  subnet_id = "${var.topology == "public" ? element(aws_subnet.k8s.*.id, 0) : element(aws_subnet.balancers.*.id, 0)}"
   user_data = "${replace("${data.template_file.user_data.rendered}", "__FILE__", "bastion_cloud_config.yml")}"
   vpc_security_group_ids = ["${aws_security_group.bastion.id}"]
 
-  tags {
-    Name = "kaws-bastion-${var.cluster}"
-    KubernetesCluster = "${var.cluster}"
+  # Root volume settings only apply to new instances, since changing them would replace
+  # instances that were created before the settings existed.
+  lifecycle {
+    ignore_changes = ["root_block_device"]
   }
+
+  root_block_device {
+    volume_type = "${var.bastion_root_volume_type}"
+    volume_size = "${var.bastion_root_volume_size}"
+  }
+
+  tags = "${merge(var.tags, map("Name", "kaws-bastion-${var.cluster}", "KubernetesCluster", var.cluster))}"
+  volume_tags = "${merge(var.tags, map("Name", "kaws-bastion-${var.cluster}", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_instance" "etcd_01" {
   ami = "${var.coreos_ami}"
-  associate_public_ip_address = true
-  availability_zone = "${var.availability_zone}"
+  associate_public_ip_address = "${var.topology == "public"}"
+  availability_zone = "${element(var.availability_zones, 0)}"
   iam_instance_profile = "${aws_iam_instance_profile.etcd.name}"
-  instance_type = "${var.instance_size}"
-  private_ip = "10.0.1.4"
-  subnet_id = "${aws_subnet.etcd.id}"
+  instance_type = "${coalesce(var.etcd_instance_type, var.instance_size)}"
+  private_ip = "${element(var.etcd_ips, 0)}"
+  subnet_id = "${element(aws_subnet.etcd.*.id, 0)}"
   user_data = "${replace("${data.template_file.user_data.rendered}", "__FILE__", "etcd_01_cloud_config.yml")}"
   vpc_security_group_ids = ["${aws_security_group.etcd.id}"]
 
-  tags {
-    Name = "kaws-etcd-${var.cluster}-01"
-    KubernetesCluster = "${var.cluster}"
+  lifecycle {
+    ignore_changes = ["root_block_device"]
+  }
+
+  root_block_device {
+    volume_type = "${var.etcd_root_volume_type}"
+    volume_size = "${var.etcd_root_volume_size}"
   }
+
+  tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-01", "KubernetesCluster", var.cluster))}"
+  volume_tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-01", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_instance" "etcd_02" {
   ami = "${var.coreos_ami}"
-  associate_public_ip_address = true
-  availability_zone = "${var.availability_zone}"
+  associate_public_ip_address = "${var.topology == "public"}"
+  availability_zone = "${element(var.availability_zones, 1)}"
+  count = "${length(var.etcd_ips) >= 2 ? 1 : 0}"
   iam_instance_profile = "${aws_iam_instance_profile.etcd.name}"
-  instance_type = "${var.instance_size}"
-  private_ip = "10.0.1.5"
-  subnet_id = "${aws_subnet.etcd.id}"
+  instance_type = "${coalesce(var.etcd_instance_type, var.instance_size)}"
+  private_ip = "${element(var.etcd_ips, 1)}"
+  subnet_id = "${element(aws_subnet.etcd.*.id, 1)}"
   user_data = "${replace("${data.template_file.user_data.rendered}", "__FILE__", "etcd_02_cloud_config.yml")}"
   vpc_security_group_ids = ["${aws_security_group.etcd.id}"]
 
-  tags {
-    Name = "kaws-etcd-${var.cluster}-02"
-    KubernetesCluster = "${var.cluster}"
+  lifecycle {
+    ignore_changes = ["root_block_device"]
   }
+
+  root_block_device {
+    volume_type = "${var.etcd_root_volume_type}"
+    volume_size = "${var.etcd_root_volume_size}"
+  }
+
+  tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-02", "KubernetesCluster", var.cluster))}"
+  volume_tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-02", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_instance" "etcd_03" {
   ami = "${var.coreos_ami}"
-  associate_public_ip_address = true
-  availability_zone = "${var.availability_zone}"
+  associate_public_ip_address = "${var.topology == "public"}"
+  availability_zone = "${element(var.availability_zones, 2)}"
+  count = "${length(var.etcd_ips) >= 3 ? 1 : 0}"
   iam_instance_profile = "${aws_iam_instance_profile.etcd.name}"
-  instance_type = "${var.instance_size}"
-  private_ip = "10.0.1.6"
-  subnet_id = "${aws_subnet.etcd.id}"
+  instance_type = "${coalesce(var.etcd_instance_type, var.instance_size)}"
+  private_ip = "${element(var.etcd_ips, 2)}"
+  subnet_id = "${element(aws_subnet.etcd.*.id, 2)}"
   user_data = "${replace("${data.template_file.user_data.rendered}", "__FILE__", "etcd_03_cloud_config.yml")}"
   vpc_security_group_ids = ["${aws_security_group.etcd.id}"]
 
-  tags {
-    Name = "kaws-etcd-${var.cluster}-03"
-    KubernetesCluster = "${var.cluster}"
+  lifecycle {
+    ignore_changes = ["root_block_device"]
+  }
+
+  root_block_device {
+    volume_type = "${var.etcd_root_volume_type}"
+    volume_size = "${var.etcd_root_volume_size}"
+  }
+
+  tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-03", "KubernetesCluster", var.cluster))}"
+  volume_tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-03", "KubernetesCluster", var.cluster))}"
+}
+
+resource "aws_instance" "etcd_04" {
+  ami = "${var.coreos_ami}"
+  associate_public_ip_address = "${var.topology == "public"}"
+  availability_zone = "${element(var.availability_zones, 3)}"
+  count = "${length(var.etcd_ips) >= 4 ? 1 : 0}"
+  iam_instance_profile = "${aws_iam_instance_profile.etcd.name}"
+  instance_type = "${coalesce(var.etcd_instance_type, var.instance_size)}"
+  private_ip = "${element(var.etcd_ips, 3)}"
+  subnet_id = "${element(aws_subnet.etcd.*.id, 3)}"
+  user_data = "${replace("${data.template_file.user_data.rendered}", "__FILE__", "etcd_04_cloud_config.yml")}"
+  vpc_security_group_ids = ["${aws_security_group.etcd.id}"]
+
+  lifecycle {
+    ignore_changes = ["root_block_device"]
+  }
+
+  root_block_device {
+    volume_type = "${var.etcd_root_volume_type}"
+    volume_size = "${var.etcd_root_volume_size}"
+  }
+
+  tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-04", "KubernetesCluster", var.cluster))}"
+  volume_tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-04", "KubernetesCluster", var.cluster))}"
+}
+
+resource "aws_instance" "etcd_05" {
+  ami = "${var.coreos_ami}"
+  associate_public_ip_address = "${var.topology == "public"}"
+  availability_zone = "${element(var.availability_zones, 4)}"
+  count = "${length(var.etcd_ips) >= 5 ? 1 : 0}"
+  iam_instance_profile = "${aws_iam_instance_profile.etcd.name}"
+  instance_type = "${coalesce(var.etcd_instance_type, var.instance_size)}"
+  private_ip = "${element(var.etcd_ips, 4)}"
+  subnet_id = "${element(aws_subnet.etcd.*.id, 4)}"
+  user_data = "${replace("${data.template_file.user_data.rendered}", "__FILE__", "etcd_05_cloud_config.yml")}"
+  vpc_security_group_ids = ["${aws_security_group.etcd.id}"]
+
+  lifecycle {
+    ignore_changes = ["root_block_device"]
+  }
+
+  root_block_device {
+    volume_type = "${var.etcd_root_volume_type}"
+    volume_size = "${var.etcd_root_volume_size}"
   }
+
+  tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-05", "KubernetesCluster", var.cluster))}"
+  volume_tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-05", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_launch_configuration" "k8s_masters" {
-  associate_public_ip_address = true
+  associate_public_ip_address = "${var.topology == "public"}"
   iam_instance_profile = "${aws_iam_instance_profile.k8s_master.name}"
   image_id = "${var.coreos_ami}"
-  instance_type = "${var.instance_size}"
+  instance_type = "${coalesce(var.masters_instance_type, var.instance_size)}"
   name_prefix = "kaws-k8s-masters-${var.cluster}-"
   security_groups = ["${aws_security_group.kubernetes.id}"]
   user_data = "${replace("${data.template_file.user_data.rendered}", "__FILE__", "master_cloud_config.yml")}"
@@ -78,8 +160,8 @@ resource "aws_launch_configuration" "k8s_masters" {
   }
 
   root_block_device {
-    volume_type = "gp2"
-    volume_size = 30
+    volume_type = "${var.masters_root_volume_type}"
+    volume_size = "${var.masters_root_volume_size}"
   }
 }
 
@@ -91,30 +173,22 @@ resource "aws_autoscaling_group" "k8s_masters" {
   max_size = "${var.masters_max_size}"
   min_size = "${var.masters_min_size}"
   name = "kaws-k8s-masters-${var.cluster}"
-  vpc_zone_identifier = ["${aws_subnet.k8s.id}"]
+  vpc_zone_identifier = ["${aws_subnet.k8s.*.id}"]
 
   lifecycle {
     create_before_destroy = true
   }
 
-  tag {
-    key = "Name"
-    value = "kaws-k8s-master-${var.cluster}"
-    propagate_at_launch = true
-  }
-
-  tag {
-    key = "KubernetesCluster"
-    value = "${var.cluster}"
-    propagate_at_launch = true
-  }
+  # `tag` blocks can't be combined with `tags`, so the Name and KubernetesCluster tags are
+  # prepended to the tags from kaws.toml.
+  tags = ["${concat(list(map("key", "Name", "value", "kaws-k8s-master-${var.cluster}", "propagate_at_launch", "true"), map("key", "KubernetesCluster", "value", var.cluster, "propagate_at_launch", "true")), var.asg_tags)}"]
 }
 
 resource "aws_launch_configuration" "k8s_nodes" {
-  associate_public_ip_address = true
+  associate_public_ip_address = "${var.topology == "public"}"
   iam_instance_profile = "${aws_iam_instance_profile.k8s_node.name}"
   image_id = "${var.coreos_ami}"
-  instance_type = "${var.instance_size}"
+  instance_type = "${coalesce(var.nodes_instance_type, var.instance_size)}"
   name_prefix = "kaws-k8s-nodes-${var.cluster}-"
   security_groups = ["${aws_security_group.kubernetes.id}"]
   user_data = "${replace("${data.template_file.user_data.rendered}", "__FILE__", "node_cloud_config.yml")}"
@@ -124,8 +198,8 @@ resource "aws_launch_configuration" "k8s_nodes" {
   }
 
   root_block_device {
-    volume_type = "gp2"
-    volume_size = 30
+    volume_type = "${var.nodes_root_volume_type}"
+    volume_size = "${var.nodes_root_volume_size}"
   }
 }
 
@@ -137,21 +211,58 @@ resource "aws_autoscaling_group" "k8s_nodes" {
   max_size = "${var.nodes_max_size}"
   min_size = "${var.nodes_min_size}"
   name = "kaws-k8s-nodes-${var.cluster}"
-  vpc_zone_identifier = ["${aws_subnet.k8s.id}"]
+  vpc_zone_identifier = ["${aws_subnet.k8s.*.id}"]
+
+  lifecycle {
+    create_before_destroy = true
+  }
+
+  tags = ["${concat(list(map("key", "Name", "value", "kaws-k8s-node-${var.cluster}", "propagate_at_launch", "true"), map("key", "KubernetesCluster", "value", var.cluster, "propagate_at_launch", "true")), var.asg_tags)}"]
+}
+
+resource "aws_launch_configuration" "k8s_node_pools" {
+  count = "${length(var.node_pools)}"
+  associate_public_ip_address = "${var.topology == "public"}"
+  iam_instance_profile = "${aws_iam_instance_profile.k8s_node.name}"
+  image_id = "${var.coreos_ami}"
+  instance_type = "${element(var.node_pool_instance_types, count.index)}"
+  name_prefix = "kaws-k8s-nodes-${var.cluster}-${element(var.node_pools, count.index)}-"
+  security_groups = ["${aws_security_group.kubernetes.id}"]
+  spot_price = "${element(var.node_pool_spot_prices, count.index)}"
+  user_data = "${replace("${data.template_file.user_data.rendered}", "__FILE__", "node_pool_${element(var.node_pools, count.index)}_cloud_config.yml")}"
 
   lifecycle {
     create_before_destroy = true
   }
 
-  tag {
-    key = "Name"
-    value = "kaws-k8s-node-${var.cluster}"
-    propagate_at_launch = true
+  root_block_device {
+    volume_type = "${var.nodes_root_volume_type}"
+    volume_size = "${var.nodes_root_volume_size}"
   }
+}
+
+/*
+Note: The node pools' autoscaling groups use `name_prefix` rather than `name` because their indexes
+shift when a pool before them is removed. The groups of the following pools are then replaced, and
+the new groups must be created before the old ones with the same pool names are destroyed.
+Destroying the old groups terminates their instances without cordoning or draining their nodes, so
+`kaws cluster plan` and `kaws cluster apply` warn about the pools whose groups have shifted.
+*/
 
-  tag {
-    key = "KubernetesCluster"
-    value = "${var.cluster}"
-    propagate_at_launch = true
+resource "aws_autoscaling_group" "k8s_node_pools" {
+  count = "${length(var.node_pools)}"
+  health_check_grace_period = 300
+  health_check_type = "ELB"
+  launch_configuration = "${element(aws_launch_configuration.k8s_node_pools.*.name, count.index)}"
+  load_balancers = ["${aws_elb.k8s_nodes.name}"]
+  max_size = "${element(var.node_pool_max_sizes, count.index)}"
+  min_size = "${element(var.node_pool_min_sizes, count.index)}"
+  name_prefix = "kaws-k8s-nodes-${var.cluster}-${element(var.node_pools, count.index)}-"
+  vpc_zone_identifier = ["${aws_subnet.k8s.*.id}"]
+
+  lifecycle {
+    create_before_destroy = true
   }
+
+  tags = ["${concat(list(map("key", "Name", "value", "kaws-k8s-node-${var.cluster}-${element(var.node_pools, count.index)}", "propagate_at_launch", "true"), map("key", "KubernetesCluster", "value", var.cluster, "propagate_at_launch", "true")), var.asg_tags)}"]
 }
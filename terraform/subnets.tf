@@ -1,32 +1,26 @@
 resource "aws_subnet" "balancers" {
-  availability_zone = "${var.availability_zone}"
-  cidr_block = "10.0.0.0/24"
+  availability_zone = "${element(var.availability_zones, count.index)}"
+  cidr_block = "${element(var.balancers_subnets, count.index)}"
+  count = "${length(var.availability_zones)}"
   vpc_id = "${aws_vpc.kubernetes.id}"
 
-  tags {
-    Name = "kaws-balancers-${var.cluster}"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-balancers-${var.cluster}-${element(var.availability_zones, count.index)}", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_subnet" "etcd" {
-  availability_zone = "${var.availability_zone}"
-  cidr_block = "10.0.1.0/24"
+  availability_zone = "${element(var.availability_zones, count.index)}"
+  cidr_block = "${element(var.etcd_subnets, count.index)}"
+  count = "${length(var.availability_zones)}"
   vpc_id = "${aws_vpc.kubernetes.id}"
 
-  tags {
-    Name = "kaws-etcd-${var.cluster}"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-etcd-${var.cluster}-${element(var.availability_zones, count.index)}", "KubernetesCluster", var.cluster))}"
 }
 
 resource "aws_subnet" "k8s" {
-  availability_zone = "${var.availability_zone}"
-  cidr_block = "${var.cidr}"
+  availability_zone = "${element(var.availability_zones, count.index)}"
+  cidr_block = "${element(var.k8s_subnets, count.index)}"
+  count = "${length(var.availability_zones)}"
   vpc_id = "${aws_vpc.kubernetes.id}"
 
-  tags {
-    Name = "kaws-k8s-${var.cluster}"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-k8s-${var.cluster}-${element(var.availability_zones, count.index)}", "KubernetesCluster", var.cluster))}"
 }
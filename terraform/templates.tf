@@ -15,62 +15,130 @@ data template_file "user_data" {
   }
 }
 
+# The bastion has no units or files of its own, so kaws includes the `units:` and `write_files:`
+# keys in the fragment's values, and leaves them out without a fragment.
 data "template_file" "bastion_cloud_config" {
   template = "${file("${path.module}/templates/bastion_cloud_config.yml")}"
 
   vars {
-    ssh_public_keys = "${join(", ", var.ssh_keys)}"
+    extra_units = "${lookup(var.cloud_config_units, "bastion", "")}"
+    extra_write_files = "${lookup(var.cloud_config_write_files, "bastion", "")}"
+    ssh_public_keys = "${join(", ", var.ssh_keys)}${lookup(var.cloud_config_ssh_keys, "bastion", "")}"
   }
 }
 
+# Every member is listed by the name and peer URL etcd will know it by, e.g.
+# "etcd_01=https://10.0.1.4:2380,etcd_02=https://10.0.1.5:2380".
+data "template_file" "etcd_initial_cluster" {
+  template = "${join(",", formatlist("%s=https://%s:2380", slice(list("etcd_01", "etcd_02", "etcd_03", "etcd_04", "etcd_05"), 0, length(var.etcd_ips)), var.etcd_ips))}"
+}
+
 data "template_file" "etcd_01_cloud_config" {
   template = "${file("${path.module}/templates/etcd_cloud_config.yml")}"
 
   vars {
     etcd_ca_cert = "${base64encode(file("clusters/${var.cluster}/etcd-ca.pem"))}",
+    etcd_endpoints = "https://${join(":2379,https://", var.etcd_ips)}:2379"
+    etcd_initial_cluster = "${data.template_file.etcd_initial_cluster.rendered}"
     etcd_peer_ca_cert = "${base64encode(file("clusters/${var.cluster}/etcd-peer-ca.pem"))}",
     etcd_peer_cert = "${base64encode(file("clusters/${var.cluster}/etcd-peer.pem"))}",
     etcd_peer_key = "${file("clusters/${var.cluster}/etcd-peer-key-encrypted.base64")}",
     etcd_server_cert = "${base64encode(file("clusters/${var.cluster}/etcd-server.pem"))}",
     etcd_server_key = "${file("clusters/${var.cluster}/etcd-server-key-encrypted.base64")}",
+    extra_units = "${lookup(var.cloud_config_units, "etcd", "")}"
+    extra_write_files = "${lookup(var.cloud_config_write_files, "etcd", "")}"
     kms_key_id = "${aws_kms_key.pki.key_id}"
     name = "etcd_01"
     region = "${var.region}"
-    ssh_public_keys = "${join(", ", var.ssh_keys)}"
+    ssh_public_keys = "${join(", ", var.ssh_keys)}${lookup(var.cloud_config_ssh_keys, "etcd", "")}"
   }
 }
 
 data "template_file" "etcd_02_cloud_config" {
+  count = "${length(var.etcd_ips) >= 2 ? 1 : 0}"
   template = "${file("${path.module}/templates/etcd_cloud_config.yml")}"
 
   vars {
     etcd_ca_cert = "${base64encode(file("clusters/${var.cluster}/etcd-ca.pem"))}",
+    etcd_endpoints = "https://${join(":2379,https://", var.etcd_ips)}:2379"
+    etcd_initial_cluster = "${data.template_file.etcd_initial_cluster.rendered}"
     etcd_peer_ca_cert = "${base64encode(file("clusters/${var.cluster}/etcd-peer-ca.pem"))}",
     etcd_peer_cert = "${base64encode(file("clusters/${var.cluster}/etcd-peer.pem"))}",
     etcd_peer_key = "${file("clusters/${var.cluster}/etcd-peer-key-encrypted.base64")}",
     etcd_server_cert = "${base64encode(file("clusters/${var.cluster}/etcd-server.pem"))}",
     etcd_server_key = "${file("clusters/${var.cluster}/etcd-server-key-encrypted.base64")}",
+    extra_units = "${lookup(var.cloud_config_units, "etcd", "")}"
+    extra_write_files = "${lookup(var.cloud_config_write_files, "etcd", "")}"
     kms_key_id = "${aws_kms_key.pki.key_id}"
     name = "etcd_02"
     region = "${var.region}"
-    ssh_public_keys = "${join(", ", var.ssh_keys)}"
+    ssh_public_keys = "${join(", ", var.ssh_keys)}${lookup(var.cloud_config_ssh_keys, "etcd", "")}"
   }
 }
 
 data "template_file" "etcd_03_cloud_config" {
+  count = "${length(var.etcd_ips) >= 3 ? 1 : 0}"
   template = "${file("${path.module}/templates/etcd_cloud_config.yml")}"
 
   vars {
     etcd_ca_cert = "${base64encode(file("clusters/${var.cluster}/etcd-ca.pem"))}",
+    etcd_endpoints = "https://${join(":2379,https://", var.etcd_ips)}:2379"
+    etcd_initial_cluster = "${data.template_file.etcd_initial_cluster.rendered}"
     etcd_peer_ca_cert = "${base64encode(file("clusters/${var.cluster}/etcd-peer-ca.pem"))}",
     etcd_peer_cert = "${base64encode(file("clusters/${var.cluster}/etcd-peer.pem"))}",
     etcd_peer_key = "${file("clusters/${var.cluster}/etcd-peer-key-encrypted.base64")}",
     etcd_server_cert = "${base64encode(file("clusters/${var.cluster}/etcd-server.pem"))}",
     etcd_server_key = "${file("clusters/${var.cluster}/etcd-server-key-encrypted.base64")}",
+    extra_units = "${lookup(var.cloud_config_units, "etcd", "")}"
+    extra_write_files = "${lookup(var.cloud_config_write_files, "etcd", "")}"
     kms_key_id = "${aws_kms_key.pki.key_id}"
     name = "etcd_03"
     region = "${var.region}"
-    ssh_public_keys = "${join(", ", var.ssh_keys)}"
+    ssh_public_keys = "${join(", ", var.ssh_keys)}${lookup(var.cloud_config_ssh_keys, "etcd", "")}"
+  }
+}
+
+data "template_file" "etcd_04_cloud_config" {
+  count = "${length(var.etcd_ips) >= 4 ? 1 : 0}"
+  template = "${file("${path.module}/templates/etcd_cloud_config.yml")}"
+
+  vars {
+    etcd_ca_cert = "${base64encode(file("clusters/${var.cluster}/etcd-ca.pem"))}",
+    etcd_endpoints = "https://${join(":2379,https://", var.etcd_ips)}:2379"
+    etcd_initial_cluster = "${data.template_file.etcd_initial_cluster.rendered}"
+    etcd_peer_ca_cert = "${base64encode(file("clusters/${var.cluster}/etcd-peer-ca.pem"))}",
+    etcd_peer_cert = "${base64encode(file("clusters/${var.cluster}/etcd-peer.pem"))}",
+    etcd_peer_key = "${file("clusters/${var.cluster}/etcd-peer-key-encrypted.base64")}",
+    etcd_server_cert = "${base64encode(file("clusters/${var.cluster}/etcd-server.pem"))}",
+    etcd_server_key = "${file("clusters/${var.cluster}/etcd-server-key-encrypted.base64")}",
+    extra_units = "${lookup(var.cloud_config_units, "etcd", "")}"
+    extra_write_files = "${lookup(var.cloud_config_write_files, "etcd", "")}"
+    kms_key_id = "${aws_kms_key.pki.key_id}"
+    name = "etcd_04"
+    region = "${var.region}"
+    ssh_public_keys = "${join(", ", var.ssh_keys)}${lookup(var.cloud_config_ssh_keys, "etcd", "")}"
+  }
+}
+
+data "template_file" "etcd_05_cloud_config" {
+  count = "${length(var.etcd_ips) >= 5 ? 1 : 0}"
+  template = "${file("${path.module}/templates/etcd_cloud_config.yml")}"
+
+  vars {
+    etcd_ca_cert = "${base64encode(file("clusters/${var.cluster}/etcd-ca.pem"))}",
+    etcd_endpoints = "https://${join(":2379,https://", var.etcd_ips)}:2379"
+    etcd_initial_cluster = "${data.template_file.etcd_initial_cluster.rendered}"
+    etcd_peer_ca_cert = "${base64encode(file("clusters/${var.cluster}/etcd-peer-ca.pem"))}",
+    etcd_peer_cert = "${base64encode(file("clusters/${var.cluster}/etcd-peer.pem"))}",
+    etcd_peer_key = "${file("clusters/${var.cluster}/etcd-peer-key-encrypted.base64")}",
+    etcd_server_cert = "${base64encode(file("clusters/${var.cluster}/etcd-server.pem"))}",
+    etcd_server_key = "${file("clusters/${var.cluster}/etcd-server-key-encrypted.base64")}",
+    extra_units = "${lookup(var.cloud_config_units, "etcd", "")}"
+    extra_write_files = "${lookup(var.cloud_config_write_files, "etcd", "")}"
+    kms_key_id = "${aws_kms_key.pki.key_id}"
+    name = "etcd_05"
+    region = "${var.region}"
+    ssh_public_keys = "${join(", ", var.ssh_keys)}${lookup(var.cloud_config_ssh_keys, "etcd", "")}"
   }
 }
 
@@ -79,16 +147,23 @@ data "template_file" "master_cloud_config" {
 
   vars {
     cluster = "${var.cluster}"
+    dns_service_ip = "${var.dns_service_ip}"
     domain = "${var.domain}"
     etcd_ca_cert = "${base64encode(file("clusters/${var.cluster}/etcd-ca.pem"))}",
     etcd_client_cert = "${base64encode(file("clusters/${var.cluster}/etcd-client.pem"))}",
     etcd_client_key = "${file("clusters/${var.cluster}/etcd-client-key-encrypted.base64")}",
+    etcd_endpoints = "https://${join(":2379,https://", var.etcd_ips)}:2379"
+    extra_units = "${lookup(var.cloud_config_units, "masters", "")}"
+    extra_write_files = "${lookup(var.cloud_config_write_files, "masters", "")}"
     k8s_ca_cert = "${base64encode(file("clusters/${var.cluster}/k8s-ca.pem"))}",
     k8s_master_cert = "${base64encode(file("clusters/${var.cluster}/k8s-master.pem"))}",
     k8s_master_key = "${file("clusters/${var.cluster}/k8s-master-key-encrypted.base64")}",
     kms_key_id = "${aws_kms_key.pki.key_id}"
+    kubelet_api_args = "${var.kubelet_api_servers == "true" ? "--api-servers=http://127.0.0.1:8080" : "--kubeconfig=/etc/kubernetes/master-kubeconfig.yml"}"
+    pod_cidr = "${var.pod_cidr}"
     region = "${var.region}"
-    ssh_public_keys = "${join(", ", var.ssh_keys)}"
+    service_cidr = "${var.service_cidr}"
+    ssh_public_keys = "${join(", ", var.ssh_keys)}${lookup(var.cloud_config_ssh_keys, "masters", "")}"
     version = "${var.version}"
   }
 }
@@ -98,16 +173,48 @@ data "template_file" "node_cloud_config" {
 
   vars {
     cluster = "${var.cluster}"
+    dns_service_ip = "${var.dns_service_ip}"
+    etcd_ca_cert = "${base64encode(file("clusters/${var.cluster}/etcd-ca.pem"))}",
+    etcd_client_cert = "${base64encode(file("clusters/${var.cluster}/etcd-client.pem"))}",
+    etcd_client_key = "${file("clusters/${var.cluster}/etcd-client-key-encrypted.base64")}",
+    etcd_endpoints = "https://${join(":2379,https://", var.etcd_ips)}:2379"
+    extra_units = "${lookup(var.cloud_config_units, "nodes", "")}"
+    extra_write_files = "${lookup(var.cloud_config_write_files, "nodes", "")}"
+    k8s_ca_cert = "${base64encode(file("clusters/${var.cluster}/k8s-ca.pem"))}",
+    k8s_node_cert = "${base64encode(file("clusters/${var.cluster}/k8s-node.pem"))}",
+    k8s_node_key = "${file("clusters/${var.cluster}/k8s-node-key-encrypted.base64")}",
+    kms_key_id = "${aws_kms_key.pki.key_id}"
+    kubelet_api_args = "${var.kubelet_api_servers == "true" ? "--api-servers=https://kubernetes.${var.domain}:443" : ""}"
+    kubelet_node_args = "--node-labels=kaws/node-pool=default"
+    master_ip = "kubernetes.${var.domain}"
+    region = "${var.region}"
+    ssh_public_keys = "${join(", ", var.ssh_keys)}${lookup(var.cloud_config_ssh_keys, "nodes", "")}"
+    version = "${var.version}"
+  }
+}
+
+data "template_file" "node_pool_cloud_config" {
+  count = "${length(var.node_pools)}"
+  template = "${file("${path.module}/templates/node_cloud_config.yml")}"
+
+  vars {
+    cluster = "${var.cluster}"
+    dns_service_ip = "${var.dns_service_ip}"
     etcd_ca_cert = "${base64encode(file("clusters/${var.cluster}/etcd-ca.pem"))}",
     etcd_client_cert = "${base64encode(file("clusters/${var.cluster}/etcd-client.pem"))}",
     etcd_client_key = "${file("clusters/${var.cluster}/etcd-client-key-encrypted.base64")}",
+    etcd_endpoints = "https://${join(":2379,https://", var.etcd_ips)}:2379"
+    extra_units = "${lookup(var.cloud_config_units, "nodes", "")}"
+    extra_write_files = "${lookup(var.cloud_config_write_files, "nodes", "")}"
     k8s_ca_cert = "${base64encode(file("clusters/${var.cluster}/k8s-ca.pem"))}",
     k8s_node_cert = "${base64encode(file("clusters/${var.cluster}/k8s-node.pem"))}",
     k8s_node_key = "${file("clusters/${var.cluster}/k8s-node-key-encrypted.base64")}",
     kms_key_id = "${aws_kms_key.pki.key_id}"
+    kubelet_api_args = "${var.kubelet_api_servers == "true" ? "--api-servers=https://kubernetes.${var.domain}:443" : ""}"
+    kubelet_node_args = "--node-labels=${element(var.node_pool_labels, count.index)}${element(var.node_pool_taints, count.index) == "" ? "" : " --register-with-taints=${element(var.node_pool_taints, count.index)}"}"
     master_ip = "kubernetes.${var.domain}"
     region = "${var.region}"
-    ssh_public_keys = "${join(", ", var.ssh_keys)}"
+    ssh_public_keys = "${join(", ", var.ssh_keys)}${lookup(var.cloud_config_ssh_keys, "nodes", "")}"
     version = "${var.version}"
   }
 }
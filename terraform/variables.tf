@@ -2,12 +2,53 @@ variable "account_id" {
   description = "Numerical account ID of the AWS account to use, e.g. `12345678`"
 }
 
-variable "availability_zone" {
-  description = "Availability Zone for etcd instances and EBS volumes, e.g. `us-east-1a`"
+variable "asg_tags" {
+  description = "The tags in `tags`, as a list of autoscaling group tags that are propagated to the instances they launch"
+  type = "list"
+  default = []
+}
+
+variable "availability_zones" {
+  description = "A list of Availability Zones to run the cluster in"
+  type = "list"
+}
+
+variable "balancers_subnets" {
+  description = "IPv4 network ranges of the subnets for ELBs, one for each Availability Zone"
+  type = "list"
+}
+
+variable "bastion_instance_type" {
+  description = "The EC2 instance type of the bastion, e.g. `t2.micro`"
+  default = "t2.micro"
+}
+
+variable "bastion_root_volume_size" {
+  description = "The size in GiB of the bastion's root volume"
+  default = "8"
 }
 
-variable "cidr" {
-  description = "IPv4 network range of the subnet where Kubernetes nodes will run, e.g. `10.0.2.0/24`"
+variable "bastion_root_volume_type" {
+  description = "The EBS volume type of the bastion's root volume, e.g. `gp2`"
+  default = "gp2"
+}
+
+variable "cloud_config_ssh_keys" {
+  description = "Extra SSH public keys for each role, rendered as YAML flow sequence items"
+  type = "map"
+  default = {}
+}
+
+variable "cloud_config_units" {
+  description = "Extra systemd units for each role, rendered as YAML block sequence items"
+  type = "map"
+  default = {}
+}
+
+variable "cloud_config_write_files" {
+  description = "Extra files to write for each role, rendered as YAML block sequence items"
+  type = "map"
+  default = {}
 }
 
 variable "cluster" {
@@ -18,17 +59,72 @@ variable "coreos_ami" {
   description = "The AMI ID for the CoreOS image to use for servers, e.g. `ami-1234abcd`"
 }
 
+variable "dns_service_ip" {
+  description = "The IP address of the cluster DNS service, within the service network, e.g. `10.3.0.10`"
+  default = "10.3.0.10"
+}
+
 variable "domain" {
   description = "The domain name for the cluster, e.g. `example.com`"
 }
 
+variable "etcd_instance_type" {
+  description = "The EC2 instance type of the etcd instances, defaults to `instance_size`"
+  default = ""
+}
+
+variable "etcd_ips" {
+  description = "The private IP addresses of the etcd instances, one for each member of the etcd cluster"
+  type = "list"
+}
+
+variable "etcd_root_volume_size" {
+  description = "The size in GiB of the etcd instances' root volumes"
+  default = "8"
+}
+
+variable "etcd_root_volume_type" {
+  description = "The EBS volume type of the etcd instances' root volumes, e.g. `gp2`"
+  default = "gp2"
+}
+
+variable "etcd_subnets" {
+  description = "IPv4 network ranges of the subnets for etcd instances, one for each Availability Zone"
+  type = "list"
+}
+
+variable "etcd_volume_size" {
+  description = "The size in GiB of the EBS volumes for etcd's data"
+  default = "10"
+}
+
+variable "etcd_volume_type" {
+  description = "The EBS volume type of the volumes for etcd's data, e.g. `gp2`"
+  default = "gp2"
+}
+
 variable "iam_users" {
   description = "A list of IAM user names who will have access to cluster PKI secrets"
   type = "list"
 }
 
 variable "instance_size" {
-  description = "The EC2 instance size, e.g. `m3.medium`"
+  description = "The EC2 instance size for roles without their own instance type, e.g. `m3.medium`"
+}
+
+variable "k8s_subnets" {
+  description = "IPv4 network ranges of the subnets where Kubernetes nodes will run, one for each Availability Zone"
+  type = "list"
+}
+
+variable "kubelet_api_servers" {
+  description = "Whether the kubelet is given the API server's address with `--api-servers` (`true`) or reads it from its kubeconfig file (`false`), set by kaws for the Kubernetes version"
+  default = "true"
+}
+
+variable "masters_instance_type" {
+  description = "The EC2 instance type of the Kubernetes masters, defaults to `instance_size`"
+  default = ""
 }
 
 variable "masters_max_size" {
@@ -39,6 +135,63 @@ variable "masters_min_size" {
   description = "The minimum number of EC2 instances the Kubernetes masters may autoscale to"
 }
 
+variable "masters_root_volume_size" {
+  description = "The size in GiB of the Kubernetes masters' root volumes"
+  default = "30"
+}
+
+variable "masters_root_volume_type" {
+  description = "The EBS volume type of the Kubernetes masters' root volumes, e.g. `gp2`"
+  default = "gp2"
+}
+
+variable "node_pool_instance_types" {
+  description = "The EC2 instance type of each node pool autoscaling group"
+  type = "list"
+  default = []
+}
+
+variable "node_pool_labels" {
+  description = "The Kubernetes labels for the nodes in each node pool autoscaling group, as comma-separated `key=value` pairs"
+  type = "list"
+  default = []
+}
+
+variable "node_pool_max_sizes" {
+  description = "The maximum number of EC2 instances each node pool autoscaling group may autoscale to"
+  type = "list"
+  default = []
+}
+
+variable "node_pool_min_sizes" {
+  description = "The minimum number of EC2 instances each node pool autoscaling group may autoscale to"
+  type = "list"
+  default = []
+}
+
+variable "node_pool_spot_prices" {
+  description = "The maximum hourly price of the spot instances in each node pool autoscaling group, or an empty string for on-demand instances"
+  type = "list"
+  default = []
+}
+
+variable "node_pool_taints" {
+  description = "The Kubernetes taints for the nodes in each node pool autoscaling group, as comma-separated `key=value:Effect` values"
+  type = "list"
+  default = []
+}
+
+variable "node_pools" {
+  description = "The names of the autoscaling groups of the additional pools of Kubernetes nodes"
+  type = "list"
+  default = []
+}
+
+variable "nodes_instance_type" {
+  description = "The EC2 instance type of the Kubernetes nodes, defaults to `instance_size`"
+  default = ""
+}
+
 variable "nodes_max_size" {
   description = "The maximum number of EC2 instances the Kubernetes nodes may autoscale to"
 }
@@ -47,6 +200,21 @@ variable "nodes_min_size" {
   description = "The minimum number of EC2 instances the Kubernetes nodes may autoscale to"
 }
 
+variable "nodes_root_volume_size" {
+  description = "The size in GiB of the Kubernetes nodes' root volumes"
+  default = "30"
+}
+
+variable "nodes_root_volume_type" {
+  description = "The EBS volume type of the Kubernetes nodes' root volumes, e.g. `gp2`"
+  default = "gp2"
+}
+
+variable "pod_cidr" {
+  description = "The IPv4 network range pods get their IP addresses from, e.g. `10.2.0.0/16`"
+  default = "10.2.0.0/16"
+}
+
 variable "propagating_vgws" {
   description = "A list of virtual gateways that should propagate routes to the route table"
   type = "list"
@@ -56,13 +224,34 @@ variable "region" {
   description = "The AWS Region where the cluster will live, e.g. `us-east-1`"
 }
 
+variable "service_cidr" {
+  description = "The IPv4 network range Kubernetes services get their IP addresses from, e.g. `10.3.0.0/24`"
+  default = "10.3.0.0/24"
+}
+
 variable "ssh_keys" {
   description = "SSH public keys to add to ~/.ssh/authorized_keys on each server"
   type = "list"
 }
 
+variable "tags" {
+  description = "Tags to add to every AWS resource that supports them, in addition to `Name` and `KubernetesCluster`"
+  type = "map"
+  default = {}
+}
+
+variable "topology" {
+  description = "How the servers are connected to the Internet: `public` to give every server a public IP address, or `private` to put etcd and Kubernetes in private subnets behind NAT gateways"
+  default = "public"
+}
+
 variable "version" {
-  description = "Version of Kubernetes to use, e.g. `1.0.0`"
+  description = "Version of Kubernetes to use, e.g. `1.8.4`"
+}
+
+variable "vpc_cidr" {
+  description = "IPv4 network range of the VPC, e.g. `10.0.0.0/16`"
+  default = "10.0.0.0/16"
 }
 
 variable "zone_id" {
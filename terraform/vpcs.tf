@@ -1,9 +1,6 @@
 resource "aws_vpc" "kubernetes" {
   enable_dns_hostnames = true
-  cidr_block = "10.0.0.0/16"
+  cidr_block = "${var.vpc_cidr}"
 
-  tags {
-    Name = "kaws-${var.cluster}"
-    KubernetesCluster = "${var.cluster}"
-  }
+  tags = "${merge(var.tags, map("Name", "kaws-${var.cluster}", "KubernetesCluster", var.cluster))}"
 }